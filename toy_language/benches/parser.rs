@@ -0,0 +1,70 @@
+use std::fmt::Write;
+use std::hint::black_box;
+use std::time::Instant;
+
+use toy_language::parser;
+
+const FUNCTIONS: usize = 20_000;
+const ITERATIONS: usize = 10;
+
+fn main() {
+    let source = generate(FUNCTIONS);
+    let megabytes = source.len() as f64 / 1e6;
+    println!("source: {megabytes:.1} MB, {FUNCTIONS} functions");
+    measure("tokens", megabytes, || {
+        black_box(parser::tokens(&source));
+    });
+    measure("parse", megabytes, || {
+        black_box(parser::parse(&source));
+    });
+}
+
+// Reports the fastest of several runs, which is the least disturbed by
+// whatever else the machine is doing.
+fn measure(name: &str, megabytes: f64, mut f: impl FnMut()) {
+    f();
+    let seconds = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed().as_secs_f64()
+        })
+        .fold(f64::INFINITY, f64::min);
+    println!(
+        "{name}: {:.1} ms, {:.1} MB/s",
+        seconds * 1e3,
+        megabytes / seconds
+    );
+}
+
+fn generate(functions: usize) -> String {
+    let mut source = String::new();
+    source.push_str("operator infixl 30 <+> combine;\n");
+    source.push_str("struct Point {\n    x: int;\n    y: int;\n}\n");
+    source.push_str("combine(a: int, b: int): int {\n    return(a * 31 + b);\n}\n");
+    for index in 0..functions {
+        write!(
+            source,
+            "\
+// Function number {index}.
+function_{index}(count: int, point: Point, values: [int]): int {{
+    total := 0;
+    index := 0;
+    while (index < count) {{
+        if (values[index % len(values)] > point.x) {{
+            total = total + values[index % len(values)] * point.y;
+        }} else {{
+            total = total <+> index - -1;
+        }}
+        index = index + 1;
+    }}
+    label := \"result of function_{index}\";
+    ratio: float = 1.5 * 2.25;
+    return(total);
+}}
+"
+        )
+        .unwrap();
+    }
+    source
+}
@@ -1,9 +1,147 @@
+use crate::pre_ast::Operator;
+use crate::span::Span;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    String,
+    Ptr,
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>),
+    Struct(usize),
+    Enum(usize),
+    Func(Vec<Ty>, Box<Ty>),
+    Trait(usize),
+}
+
+#[derive(Clone, Debug)]
 pub enum Expr {
     Variable(usize),
-    Global(usize),
+    Global(usize, Span),
     Func(usize),
+    Closure(usize, Vec<usize>),
     Integer(i64),
     Float(f64),
+    Boolean(bool),
     String(String),
-    Call(Box<Expr>, Vec<Expr>),
+    Tuple(Vec<Expr>),
+    Array(Ty, Vec<Expr>),
+    NewArray(Ty, Box<Expr>, Span),
+    Index(Ty, Box<Expr>, Box<Expr>, Span),
+    Length(Box<Expr>),
+    Builtin(Builtin, Vec<Expr>, Span),
+    Struct(usize, Vec<Expr>),
+    Field(usize, usize, Box<Expr>),
+    Variant(usize, usize, Vec<Expr>),
+    Match(usize, Box<Expr>, Vec<Arm>, Ty),
+    TraitObject(usize, Box<Expr>),
+    DynamicCall(usize, Box<Expr>, Vec<Expr>, Ty),
+    Negation(Box<Expr>, Span),
+    BinaryOperation(Operator, Box<Expr>, Box<Expr>, Span),
+    Overflow(Overflow, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>, Ty),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Overflow {
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+#[derive(Clone, Debug)]
+pub enum Builtin {
+    Print(Option<Ty>, bool),
+    Format(Vec<Ty>),
+    ReadLine,
+    ParseInt,
+    Args,
+    Abs,
+    Min,
+    Max,
+    Sqrt,
+    Sin,
+    Pow,
+    Exit,
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Variable(usize),
+    Global(usize),
+    Tuple(Vec<Pattern>),
+    Index(Ty, Expr, Expr, Span),
+    Field(usize, usize, Expr),
+    Coerce(usize, Box<Pattern>),
+    Discard,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arm {
+    pub variant: Option<usize>,
+    pub bindings: Vec<Pattern>,
+    pub body: Vec<Stmt>,
+    pub value: Expr,
+}
+
+#[derive(Clone, Debug)]
+pub enum Stmt {
+    Expr(Expr),
+    Assign(Pattern, Expr),
+    Block(Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>, Span),
+    While(Expr, Vec<Stmt>, Span),
+    Return(Expr, Span),
+}
+
+#[derive(Debug)]
+pub struct Func {
+    pub name: String,
+    pub span: Span,
+    pub num_params: usize,
+    pub ret_ty: Ty,
+    pub vars: Vec<Ty>,
+    pub boxed: Vec<bool>,
+    pub captures: Option<Vec<(usize, bool)>>,
+    pub body: Vec<Stmt>,
+    pub external: bool,
+}
+
+#[derive(Debug)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, Ty)>,
+}
+
+#[derive(Debug)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<(String, Vec<Ty>)>,
+}
+
+#[derive(Debug)]
+pub struct ImplDef {
+    pub trait_index: usize,
+    pub ty: Ty,
+    pub methods: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub structs: Vec<StructDef>,
+    pub enums: Vec<EnumDef>,
+    pub impls: Vec<ImplDef>,
+    pub funcs: Vec<Func>,
+    pub lambdas: Vec<Func>,
+    pub globals: Vec<Ty>,
+    pub init: Func,
+    pub main: Option<usize>,
+}
+
+impl Ty {
+    pub fn unit() -> Ty {
+        Ty::Tuple(Vec::new())
+    }
 }
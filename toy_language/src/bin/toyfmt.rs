@@ -0,0 +1,128 @@
+use std::io::Read;
+
+use toy_language::pre_ast::{Ast, Stmt, TermKind};
+use toy_language::{error, formatter, parser, span};
+
+const USAGE: &str = "\
+usage: toyfmt [--check] [FILE...]
+Formats the given files in place, or stdin to stdout.
+
+options:
+  --check   only list the files that are not formatted, exiting with 1 if
+            there are any
+  --help    show this help
+
+Errors are reported on stderr and make toyfmt exit with 2.";
+
+fn main() {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        if arg == "--check" {
+            check = true;
+        } else if arg == "--help" {
+            println!("{USAGE}");
+            return;
+        } else if arg.starts_with('-') {
+            eprintln!("{USAGE}");
+            eprintln!("toyfmt: unknown option `{arg}`");
+            std::process::exit(2);
+        } else {
+            paths.push(arg);
+        }
+    }
+    std::panic::set_hook(Box::new(|_| {}));
+    if paths.is_empty() {
+        let mut source = String::new();
+        if let Err(error) = std::io::stdin().read_to_string(&mut source) {
+            fail("<stdin>", &error.to_string());
+        }
+        let formatted = reformat(&source).unwrap_or_else(|message| fail("<stdin>", &message));
+        if check {
+            std::process::exit(i32::from(formatted != source));
+        }
+        print!("{formatted}");
+        return;
+    }
+    let mut unformatted = false;
+    let mut failed = false;
+    for path in &paths {
+        let result = std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| Ok((reformat(&source)?, source)));
+        let (formatted, source) = match result {
+            Ok(result) => result,
+            Err(message) => {
+                eprintln!("toyfmt: {path}: {message}");
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{path}");
+            unformatted = true;
+        } else if let Err(error) = std::fs::write(path, formatted) {
+            eprintln!("toyfmt: {path}: {error}");
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(2);
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn fail(path: &str, message: &str) -> ! {
+    eprintln!("toyfmt: {path}: {message}");
+    std::process::exit(2);
+}
+
+// Formats the source, or describes why it can't be formatted.
+fn reformat(source: &str) -> Result<String, String> {
+    span::clear();
+    std::panic::catch_unwind(|| {
+        let mut ast = parser::parse(source);
+        let formatted = formatter::format(source);
+        let mut reparsed = parser::parse(&formatted);
+        erase_redundant_commas(&mut ast);
+        erase_redundant_commas(&mut reparsed);
+        span::clear();
+        assert!(
+            reparsed == ast,
+            "formatting would change the meaning of the program"
+        );
+        formatted
+    })
+    .map_err(|payload| {
+        let message = error::message(&*payload).to_owned();
+        match span::current() {
+            Some(span) if span.start <= source.len() => {
+                let (line, column) = span.line_column(source);
+                format!("{line}:{column}: {message}")
+            }
+            _ => message,
+        }
+    })
+}
+
+// Forgets the trailing commas that the formatter drops, those in brackets
+// with other than one element, where they don't change the meaning.
+fn erase_redundant_commas(ast: &mut Ast) {
+    let mut pending: Vec<_> = ast.stmts.iter().flat_map(Stmt::terms).collect();
+    while let Some(id) = pending.pop() {
+        if let TermKind::Bracketed {
+            elements,
+            has_trailing_comma,
+            ..
+        } = &mut ast[id].kind
+        {
+            *has_trailing_comma &= elements.len() == 1;
+        }
+        pending.extend(ast[id].kind.children());
+    }
+}
@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensServerCapabilities, ServerCapabilities, TextDocumentContentChangeEvent,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde::de::DeserializeOwned;
+use toy_language::parser::{self, Incremental, Token, Trivia};
+use toy_language::resolver::{self, Reference, ReferenceKind, Symbol, SymbolKind};
+use toy_language::span::{self, Span};
+use toy_language::{error, macros, module, simplify, source_map};
+
+const KEYWORDS: &[&str] = &[
+    "if", "else", "while", "return", "match", "struct", "enum", "trait", "impl", "for", "extern",
+    "fn", "operator", "prefix", "postfix", "infix", "infixl", "infixr", "true", "false", "pub",
+    "import", "const", "comptime", "macro",
+];
+const TYPES: &[&str] = &["int", "float", "bool", "string", "ptr", "Self"];
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::OPERATOR,
+];
+
+struct Document {
+    path: String,
+    source: Incremental,
+    lines: LineIndex,
+    references: Vec<Reference>,
+    symbols: Vec<Symbol>,
+    diagnostics: Vec<Diagnostic>,
+    imports: Vec<Import>,
+}
+
+// A file imported by a document, as of its last analysis.
+struct Import {
+    uri: Url,
+    start: usize,
+    text: String,
+    lines: LineIndex,
+}
+
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+fn main() {
+    // Errors in documents are panics too, so every panic is logged in one line.
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map_or_else(String::new, |location| format!(" at {location}"));
+        eprintln!(
+            "toylsp: panicked{location}: {}",
+            error::message(info.payload())
+        );
+    }));
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    };
+    connection
+        .initialize(serde_json::to_value(capabilities).unwrap())
+        .expect("failed to initialize");
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request).unwrap() {
+                    break;
+                }
+                let response =
+                    handle_request(&documents, request).unwrap_or_else(|response| response);
+                connection.sender.send(Message::Response(response)).unwrap();
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut documents, notification) {
+                    let diagnostics = documents
+                        .get(&uri)
+                        .map_or_else(Vec::new, |document| document.diagnostics.clone());
+                    let params = PublishDiagnosticsParams {
+                        uri,
+                        diagnostics,
+                        version: None,
+                    };
+                    let notification =
+                        Notification::new("textDocument/publishDiagnostics".to_owned(), params);
+                    connection
+                        .sender
+                        .send(Message::Notification(notification))
+                        .unwrap();
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    drop(connection);
+    io_threads.join().unwrap();
+}
+
+fn handle_notification(
+    documents: &mut HashMap<Url, Document>,
+    notification: Notification,
+) -> Option<Url> {
+    match notification.method.as_str() {
+        "textDocument/didOpen" => {
+            let params: DidOpenTextDocumentParams = notification_params(&notification)?;
+            let mut document = Document::new(&params.text_document.uri, params.text_document.text);
+            document.analyze();
+            documents.insert(params.text_document.uri.clone(), document);
+            Some(params.text_document.uri)
+        }
+        "textDocument/didChange" => {
+            let params: DidChangeTextDocumentParams = notification_params(&notification)?;
+            let document = documents.get_mut(&params.text_document.uri)?;
+            for change in params.content_changes {
+                document.change(change);
+            }
+            document.analyze();
+            Some(params.text_document.uri)
+        }
+        "textDocument/didClose" => {
+            let params: DidCloseTextDocumentParams = notification_params(&notification)?;
+            documents.remove(&params.text_document.uri);
+            Some(params.text_document.uri)
+        }
+        _ => None,
+    }
+}
+
+// Answers a request, failing with an error response.
+fn handle_request(
+    documents: &HashMap<Url, Document>,
+    request: Request,
+) -> Result<Response, Response> {
+    let id = request.id.clone();
+    match request.method.as_str() {
+        "textDocument/hover" => {
+            let params: HoverParams = request_params(&request)?;
+            let position = params.text_document_position_params;
+            let hover = documents
+                .get(&position.text_document.uri)
+                .and_then(|document| {
+                    let reference = document.reference_at(position.position)?;
+                    Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: format!("```\n{}: {}\n```", reference.name, reference.ty),
+                        }),
+                        range: Some(document.range(reference.span)),
+                    })
+                });
+            Ok(Response::new_ok(id, hover))
+        }
+        "textDocument/definition" => {
+            let params: GotoDefinitionParams = request_params(&request)?;
+            let position = params.text_document_position_params;
+            let uri = position.text_document.uri;
+            let location = documents.get(&uri).and_then(|document| {
+                let reference = document.reference_at(position.position)?;
+                let location = match document.import_range(reference.definition) {
+                    Some((uri, range)) => Location { uri, range },
+                    None => Location {
+                        uri: uri.clone(),
+                        range: document.range(reference.definition),
+                    },
+                };
+                Some(GotoDefinitionResponse::Scalar(location))
+            });
+            Ok(Response::new_ok(id, location))
+        }
+        "textDocument/documentSymbol" => {
+            let params: DocumentSymbolParams = request_params(&request)?;
+            let symbols = documents
+                .get(&params.text_document.uri)
+                .map(|document| DocumentSymbolResponse::Nested(document.document_symbols()));
+            Ok(Response::new_ok(id, symbols))
+        }
+        "textDocument/semanticTokens/full" => {
+            let params: SemanticTokensParams = request_params(&request)?;
+            let tokens = documents
+                .get(&params.text_document.uri)
+                .map(|document| SemanticTokens {
+                    result_id: None,
+                    data: document.semantic_tokens(),
+                });
+            Ok(Response::new_ok(id, tokens))
+        }
+        method => Err(Response::new_err(
+            id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unsupported request `{method}`"),
+        )),
+    }
+}
+
+fn request_params<P: DeserializeOwned>(request: &Request) -> Result<P, Response> {
+    serde_json::from_value(request.params.clone()).map_err(|error| {
+        Response::new_err(
+            request.id.clone(),
+            ErrorCode::InvalidParams as i32,
+            format!("invalid parameters for `{}`: {error}", request.method),
+        )
+    })
+}
+
+// Decodes the parameters of a notification, logging why they are invalid as
+// there is no response to report it in.
+fn notification_params<P: DeserializeOwned>(notification: &Notification) -> Option<P> {
+    serde_json::from_value(notification.params.clone())
+        .map_err(|error| {
+            eprintln!(
+                "toylsp: invalid parameters for `{}`: {error}",
+                notification.method
+            )
+        })
+        .ok()
+}
+
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, (String, Option<Span>)> {
+    span::clear();
+    std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = error::message(&*payload).to_owned();
+        (message, span::current())
+    })
+}
+
+impl Document {
+    fn new(uri: &Url, text: String) -> Document {
+        let path = uri.to_file_path().map_or_else(
+            |_| uri.to_string(),
+            |path| path.to_string_lossy().into_owned(),
+        );
+        Document {
+            path,
+            lines: LineIndex::new(&text),
+            source: Incremental::new(text),
+            references: Vec::new(),
+            symbols: Vec::new(),
+            diagnostics: Vec::new(),
+            imports: Vec::new(),
+        }
+    }
+    fn change(&mut self, change: TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let span = Span::new(
+                    self.lines.offset(self.source.text(), range.start),
+                    self.lines.offset(self.source.text(), range.end),
+                );
+                let len = self.source.text().len();
+                // A failed reparse leaves the source unparsed; the error is
+                // reported when the document is analyzed.
+                let _ = catch(|| self.source.edit(span, &change.text));
+                self.shift(span, change.text.len(), len);
+            }
+            None => {
+                self.source = Incremental::new(change.text);
+                self.references.clear();
+                self.symbols.clear();
+                self.imports.clear();
+            }
+        }
+        self.lines = LineIndex::new(self.source.text());
+    }
+    // Moves what the last analysis found past an edit of `span` into text of
+    // `len` bytes, and forgets what the edit touched, so that it can be used
+    // until the document is analyzed without errors again.
+    fn shift(&mut self, span: Span, len: usize, document_len: usize) {
+        let moved = |offset: usize| offset + len - (span.end - span.start);
+        let shift = |other: Span| {
+            if other.end < span.start {
+                Some(other)
+            } else if other.start > span.end || other.start >= document_len {
+                Some(Span::new(moved(other.start), moved(other.end)))
+            } else {
+                None
+            }
+        };
+        self.references.retain_mut(|reference| {
+            match (shift(reference.span), shift(reference.definition)) {
+                (Some(span), Some(definition)) => {
+                    reference.span = span;
+                    reference.definition = definition;
+                    true
+                }
+                _ => false,
+            }
+        });
+        self.symbols.retain_mut(|symbol| match shift(symbol.span) {
+            Some(span) => {
+                symbol.span = span;
+                true
+            }
+            None => false,
+        });
+        for import in &mut self.imports {
+            import.start = moved(import.start);
+        }
+    }
+    fn analyze(&mut self) {
+        self.diagnostics.clear();
+        source_map::clear();
+        source_map::add(&self.path, self.source.text());
+        if let Err(error) = catch(|| {
+            self.source.ast();
+        }) {
+            return self.diagnostic(error);
+        }
+        let ast = self.source.ast();
+        if let Ok(symbols) = catch(|| resolver::symbols(ast)) {
+            self.symbols = symbols;
+        }
+        let mut ast = ast.clone();
+        match catch(|| {
+            let mut modules = module::load(&mut ast);
+            macros::expand(&mut ast, &mut modules);
+            resolver::analyze(&ast, &modules)
+        }) {
+            Ok((mut program, index)) => {
+                let len = self.source.text().len();
+                for (span, message) in simplify::simplify(&mut program) {
+                    let span = source_map::origin(span);
+                    if span.end <= len {
+                        self.diagnostics.push(Diagnostic {
+                            range: self.range(span),
+                            severity: Some(DiagnosticSeverity::WARNING),
+                            source: Some("toy_language".to_owned()),
+                            message: message.to_owned(),
+                            ..Default::default()
+                        });
+                    }
+                }
+                self.references = index.references;
+                for reference in &mut self.references {
+                    reference.span = source_map::origin(reference.span);
+                    reference.definition = source_map::origin(reference.definition);
+                }
+                self.references
+                    .retain(|reference| reference.span.end <= len);
+            }
+            // The references of the last analysis are kept.
+            Err(error) => return self.diagnostic(error),
+        }
+        self.imports = source_map::files()
+            .into_iter()
+            .skip(1)
+            .filter_map(|file| {
+                let text = source_map::source(file);
+                Some(Import {
+                    uri: Url::from_file_path(source_map::path(file)).ok()?,
+                    start: source_map::start(file),
+                    lines: LineIndex::new(&text),
+                    text,
+                })
+            })
+            .collect();
+    }
+    // Errors in imported files are reported at the top of the document, and
+    // those in expansions of macros where the macros are defined.
+    fn diagnostic(&mut self, (message, span): (String, Option<Span>)) {
+        let (span, message) = match span.map(|span| (span, source_map::origin(span))) {
+            Some((span, origin)) if origin != span => (
+                Some(origin),
+                format!("{}: {message}", source_map::location(span)),
+            ),
+            span => (span.map(|(span, _)| span), message),
+        };
+        let (span, message) = match span {
+            Some(span) if span.end > self.source.text().len() => (
+                Span::new(0, 0),
+                format!("{}: {message}", source_map::location(span)),
+            ),
+            span => (span.unwrap_or(Span::new(0, 0)), message),
+        };
+        self.diagnostics.push(Diagnostic {
+            range: self.range(span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("toy_language".to_owned()),
+            message,
+            ..Default::default()
+        });
+    }
+    fn range(&self, span: Span) -> Range {
+        Range::new(
+            self.lines.position(self.source.text(), span.start),
+            self.lines.position(self.source.text(), span.end),
+        )
+    }
+    fn import_range(&self, span: Span) -> Option<(Url, Range)> {
+        if span.end <= self.source.text().len() {
+            return None;
+        }
+        let import = self
+            .imports
+            .iter()
+            .rev()
+            .find(|import| import.start <= span.start)?;
+        let position = |offset| import.lines.position(&import.text, offset - import.start);
+        let range = Range::new(position(span.start), position(span.end));
+        Some((import.uri.clone(), range))
+    }
+    fn reference_at(&self, position: Position) -> Option<&Reference> {
+        let offset = self.lines.offset(self.source.text(), position);
+        self.references
+            .iter()
+            .filter(|reference| reference.span.start <= offset && offset <= reference.span.end)
+            .min_by_key(|reference| reference.span.end - reference.span.start)
+    }
+    fn document_symbols(&self) -> Vec<DocumentSymbol> {
+        self.symbols
+            .iter()
+            .map(|symbol| {
+                let detail = self
+                    .references
+                    .iter()
+                    .find(|reference| reference.span == symbol.span)
+                    .map(|reference| reference.ty.clone());
+                let kind = match symbol.kind {
+                    SymbolKind::Func => lsp_types::SymbolKind::FUNCTION,
+                    SymbolKind::Global => lsp_types::SymbolKind::VARIABLE,
+                    SymbolKind::Struct => lsp_types::SymbolKind::STRUCT,
+                    SymbolKind::Enum => lsp_types::SymbolKind::ENUM,
+                    SymbolKind::Trait => lsp_types::SymbolKind::INTERFACE,
+                    SymbolKind::Const => lsp_types::SymbolKind::CONSTANT,
+                };
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: symbol.name.clone(),
+                    detail,
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    range: self.range(symbol.span),
+                    selection_range: self.range(symbol.span),
+                    children: None,
+                }
+            })
+            .collect()
+    }
+    fn semantic_tokens(&self) -> Vec<SemanticToken> {
+        let Ok(lexemes) = catch(|| parser::tokens(self.source.text())) else {
+            return Vec::new();
+        };
+        let references: HashMap<usize, ReferenceKind> = self
+            .references
+            .iter()
+            .map(|reference| (reference.span.start, reference.kind))
+            .collect();
+        let type_names: Vec<&str> = self
+            .symbols
+            .iter()
+            .filter(|symbol| {
+                matches!(
+                    symbol.kind,
+                    SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Trait
+                )
+            })
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        let mut classified = Vec::new();
+        let mut offset = 0;
+        for lexeme in &lexemes {
+            for &(kind, text) in &lexeme.trivia {
+                if kind != Trivia::Whitespace {
+                    classified.push((Span::new(offset, offset + text.len()), 6));
+                }
+                offset += text.len();
+            }
+            let span = Span::new(offset, offset + lexeme.text.len());
+            offset = span.end;
+            let token_type = match &lexeme.token {
+                Some(Token::Identifier(name)) if KEYWORDS.contains(name) => 0,
+                Some(Token::Identifier(name)) => match references.get(&span.start) {
+                    Some(ReferenceKind::Func) => 1,
+                    Some(ReferenceKind::Variable | ReferenceKind::Global) => 2,
+                    None if TYPES.contains(name) || type_names.contains(name) => 3,
+                    None => continue,
+                },
+                Some(Token::Integer(_) | Token::Float(_)) => 4,
+                Some(Token::StringLiteral(_)) => 5,
+                Some(Token::Symbol(_)) => 7,
+                _ => continue,
+            };
+            classified.push((span, token_type));
+        }
+        let mut tokens = Vec::new();
+        let mut prev = Position::new(0, 0);
+        for (span, token_type) in classified {
+            let mut start = span.start;
+            for line in self.source.text()[span.start..span.end].split_inclusive('\n') {
+                let text = line.trim_end_matches(['\n', '\r']);
+                let position = self.lines.position(self.source.text(), start);
+                start += line.len();
+                if text.is_empty() {
+                    continue;
+                }
+                let delta_line = position.line - prev.line;
+                let delta_start = if delta_line == 0 {
+                    position.character - prev.character
+                } else {
+                    position.character
+                };
+                tokens.push(SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length: text.encode_utf16().count() as u32,
+                    token_type,
+                    token_modifiers_bitset: 0,
+                });
+                prev = position;
+            }
+        }
+        tokens
+    }
+}
+
+impl LineIndex {
+    fn new(text: &str) -> LineIndex {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        LineIndex { starts }
+    }
+    fn position(&self, text: &str, offset: usize) -> Position {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let character = text[self.starts[line]..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+    fn offset(&self, text: &str, position: Position) -> usize {
+        let Some(&start) = self.starts.get(position.line as usize) else {
+            return text.len();
+        };
+        let mut character = 0;
+        for (index, ch) in text[start..].char_indices() {
+            if character >= position.character as usize || ch == '\n' {
+                return start + index;
+            }
+            character += ch.len_utf16();
+        }
+        text.len()
+    }
+}
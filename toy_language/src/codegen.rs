@@ -0,0 +1,1465 @@
+mod layout;
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+use std::fmt::Write;
+
+use cranelift::codegen::cursor::{Cursor, FuncCursor};
+use cranelift::codegen::ir::{StackSlot, UserFuncName, UserStackMapEntry};
+use cranelift::frontend::Switch;
+use cranelift::prelude::*;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{DataDescription, DataId, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+
+use crate::ast::{Arm, Builtin, Expr, Func, ImplDef, Overflow, Pattern, Program, Stmt, Ty};
+use crate::pre_ast::Operator;
+use crate::runtime;
+use crate::span::Span;
+use layout::Layout;
+
+type EnvLayout = (u32, i64, Vec<(i32, bool)>);
+
+#[derive(Clone, Copy)]
+pub enum Emit {
+    Clif,
+    Asm,
+    Object,
+}
+
+struct Compiler<'s, M: Module> {
+    input: &'s str,
+    overflow: Overflow,
+    emit: Option<Emit>,
+    listing: String,
+    module: M,
+    ptr_ty: Type,
+    func_ids: Vec<FuncId>,
+    func_signatures: Vec<Signature>,
+    func_values: HashMap<usize, DataId>,
+    impls: &'s [ImplDef],
+    vtables: HashMap<usize, DataId>,
+    lambda_ids: Vec<FuncId>,
+    lambda_envs: Vec<EnvLayout>,
+    frame_entries: HashMap<String, FuncId>,
+    compiled: Vec<(FuncId, String, usize, Vec<runtime::StackMap>)>,
+    global_ids: Vec<DataId>,
+    global_tys: Vec<Ty>,
+    struct_fields: Vec<Vec<Ty>>,
+    struct_layouts: Vec<(Layout, Vec<i32>)>,
+    variant_payloads: Vec<Vec<Vec<Ty>>>,
+    variant_layouts: Vec<Vec<(Layout, Vec<i32>)>>,
+}
+
+struct FuncTranslator<'c, 'b, 's, 'f, M: Module> {
+    compiler: &'c mut Compiler<'s, M>,
+    builder: FunctionBuilder<'b>,
+    func: &'f Func,
+    var_offsets: Vec<usize>,
+    ref_slots: Vec<Option<StackSlot>>,
+    overflow: Overflow,
+}
+
+const RUNTIME_PREFIX: &str = "toy_rt_";
+const FLAGS: [(&str, &str); 2] = [
+    ("enable_multi_ret_implicit_sret", "true"),
+    ("preserve_frame_pointers", "true"),
+];
+
+pub fn run(program: &Program, input: &str, overflow: Overflow) -> i32 {
+    let (compiler, init_id) = load(program, input, overflow);
+    let init = compiler.module.get_finalized_function(init_id);
+    let init = unsafe { std::mem::transmute::<*const u8, unsafe extern "C" fn()>(init) };
+    unsafe { init() };
+    match program.main {
+        Some(index) => {
+            let main = compiler
+                .module
+                .get_finalized_function(compiler.func_ids[index]);
+            let main =
+                unsafe { std::mem::transmute::<*const u8, unsafe extern "C" fn() -> i64>(main) };
+            unsafe { main() as i32 }
+        }
+        None => 0,
+    }
+}
+
+// Calls functions without parameters and returns what they evaluate to. The
+// globals of the program are left uninitialized.
+pub fn evaluate(program: &Program, input: &str, roots: &[(Span, usize)]) -> Vec<Expr> {
+    let (compiler, _) = load(program, input, Overflow::Checked);
+    roots
+        .iter()
+        .map(|&(span, index)| {
+            let func = compiler
+                .module
+                .get_finalized_function(compiler.func_ids[index]);
+            runtime::at_compile_time(span, || unsafe {
+                match program.funcs[index].ret_ty {
+                    Ty::Int => Expr::Integer(std::mem::transmute::<
+                        *const u8,
+                        unsafe extern "C" fn() -> i64,
+                    >(func)()),
+                    Ty::Float => Expr::Float(std::mem::transmute::<
+                        *const u8,
+                        unsafe extern "C" fn() -> f64,
+                    >(func)()),
+                    Ty::Bool => Expr::Boolean(
+                        std::mem::transmute::<*const u8, unsafe extern "C" fn() -> i8>(func)() != 0,
+                    ),
+                    Ty::String => {
+                        let value = std::mem::transmute::<
+                            *const u8,
+                            unsafe extern "C" fn() -> *const c_char,
+                        >(func)();
+                        Expr::String(CStr::from_ptr(value).to_string_lossy().into_owned())
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        })
+        .collect()
+}
+
+fn load<'s>(
+    program: &'s Program,
+    input: &'s str,
+    overflow: Overflow,
+) -> (Compiler<'s, JITModule>, FuncId) {
+    let mut jit_builder =
+        JITBuilder::with_flags(&FLAGS, cranelift_module::default_libcall_names()).unwrap();
+    for (name, addr) in runtime::symbols() {
+        jit_builder.symbol(format!("{RUNTIME_PREFIX}{name}"), addr);
+    }
+    for func in program.funcs.iter().filter(|func| func.external) {
+        let addr = runtime::lookup_symbol(&func.name);
+        assert!(
+            !addr.is_null(),
+            "unresolved extern function `{}`",
+            func.name
+        );
+        jit_builder.symbol(&func.name, addr);
+    }
+    let (mut compiler, init_id) =
+        compile(JITModule::new(jit_builder), program, input, overflow, None);
+    compiler.module.finalize_definitions().unwrap();
+    for (func_id, name, size, stack_maps) in std::mem::take(&mut compiler.compiled) {
+        let start = compiler.module.get_finalized_function(func_id) as usize;
+        runtime::register_code(start, size, name, stack_maps);
+    }
+    for (ty, &data_id) in program.globals.iter().zip(&compiler.global_ids) {
+        let addr = compiler.module.get_finalized_data(data_id).0 as usize;
+        for offset in compiler.layout(ty).refs {
+            runtime::register_root(addr + offset as usize);
+        }
+    }
+    (compiler, init_id)
+}
+
+// The runtime functions an object imports are the ones `runtime::symbols`
+// gives the JIT, with their names prefixed by `toy_rt_`. Code linked from an
+// object is not registered with the garbage collector, so a collection made
+// while it runs cannot see the references on its stack.
+pub fn emit(program: &Program, input: &str, overflow: Overflow, emit: Emit) -> Vec<u8> {
+    let mut flag_builder = settings::builder();
+    for (name, value) in FLAGS {
+        flag_builder.set(name, value).unwrap();
+    }
+    flag_builder.set("is_pic", "true").unwrap();
+    let isa = cranelift_native::builder()
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder))
+        .unwrap();
+    let object_builder =
+        ObjectBuilder::new(isa, "toy", cranelift_module::default_libcall_names()).unwrap();
+    let (compiler, _) = compile(
+        ObjectModule::new(object_builder),
+        program,
+        input,
+        overflow,
+        Some(emit),
+    );
+    match compiler.emit {
+        Some(Emit::Object) => compiler.module.finish().emit().unwrap(),
+        _ => compiler.listing.into_bytes(),
+    }
+}
+
+fn compile<'s, M: Module>(
+    module: M,
+    program: &'s Program,
+    input: &'s str,
+    overflow: Overflow,
+    emit: Option<Emit>,
+) -> (Compiler<'s, M>, FuncId) {
+    let ptr_ty = module.target_config().pointer_type();
+    let mut compiler = Compiler {
+        input,
+        overflow,
+        emit,
+        listing: String::new(),
+        module,
+        ptr_ty,
+        func_ids: Vec::new(),
+        func_signatures: Vec::new(),
+        func_values: HashMap::new(),
+        impls: &program.impls,
+        vtables: HashMap::new(),
+        lambda_ids: Vec::new(),
+        lambda_envs: Vec::new(),
+        frame_entries: HashMap::new(),
+        compiled: Vec::new(),
+        global_ids: Vec::new(),
+        global_tys: program.globals.clone(),
+        struct_fields: program
+            .structs
+            .iter()
+            .map(|def| def.fields.iter().map(|(_, ty)| ty.clone()).collect())
+            .collect(),
+        struct_layouts: program
+            .structs
+            .iter()
+            .map(|def| Layout::sequence(def.fields.iter().map(|(_, ty)| ty), ptr_ty))
+            .collect(),
+        variant_payloads: program
+            .enums
+            .iter()
+            .map(|def| def.variants.iter().map(|(_, tys)| tys.clone()).collect())
+            .collect(),
+        variant_layouts: program
+            .enums
+            .iter()
+            .map(|def| {
+                def.variants
+                    .iter()
+                    .map(|(_, tys)| Layout::sequence(tys, ptr_ty))
+                    .collect()
+            })
+            .collect(),
+    };
+    let object = matches!(emit, Some(Emit::Object));
+    for func in &program.funcs {
+        let signature = compiler.signature(func);
+        let linkage = if func.external {
+            Linkage::Import
+        } else {
+            Linkage::Local
+        };
+        // An object's `main` symbol is the entry point defined below.
+        let mut func_id = if object && func.name == "main" {
+            compiler
+                .module
+                .declare_anonymous_function(&signature)
+                .unwrap()
+        } else {
+            compiler
+                .module
+                .declare_function(&func.name, linkage, &signature)
+                .unwrap()
+        };
+        if func.external && func.ret_ty == Ty::String {
+            func_id = compiler.checked_extern(func, func_id, &signature);
+        }
+        compiler.func_ids.push(func_id);
+        compiler.func_signatures.push(signature);
+    }
+    for lambda in &program.lambdas {
+        let signature = compiler.signature(lambda);
+        let lambda_id = compiler
+            .module
+            .declare_anonymous_function(&signature)
+            .unwrap();
+        compiler.lambda_ids.push(lambda_id);
+        let env = compiler.env_layout(lambda);
+        compiler.lambda_envs.push(env);
+    }
+    for ty in &program.globals {
+        let data_id = compiler.module.declare_anonymous_data(true, false).unwrap();
+        let layout = compiler.layout(ty);
+        let mut description = DataDescription::new();
+        description.define_zeroinit(layout.size.max(1) as usize);
+        description.set_align(layout.align.into());
+        compiler.module.define_data(data_id, &description).unwrap();
+        compiler.global_ids.push(data_id);
+    }
+    for (func, &func_id) in program.funcs.iter().zip(&compiler.func_ids.clone()) {
+        if !func.external {
+            compiler.define(func, func_id);
+        }
+    }
+    for (lambda, &lambda_id) in program.lambdas.iter().zip(&compiler.lambda_ids.clone()) {
+        compiler.define(lambda, lambda_id);
+    }
+    let init_signature = compiler.signature(&program.init);
+    let init_id = compiler
+        .module
+        .declare_anonymous_function(&init_signature)
+        .unwrap();
+    compiler.define(&program.init, init_id);
+    if object {
+        let main_id = program.main.map(|index| compiler.func_ids[index]);
+        compiler.entry_point(init_id, main_id);
+    }
+    (compiler, init_id)
+}
+
+impl<M: Module> Compiler<'_, M> {
+    fn layout(&self, ty: &Ty) -> Layout {
+        Layout::of(ty, self.ptr_ty)
+    }
+    fn string_offsets(&self, ty: &Ty) -> Vec<i32> {
+        match ty {
+            Ty::String => vec![0],
+            Ty::Tuple(tys) => {
+                let (_, offsets) = Layout::sequence(tys, self.ptr_ty);
+                tys.iter()
+                    .zip(offsets)
+                    .flat_map(|(ty, offset)| {
+                        self.string_offsets(ty)
+                            .into_iter()
+                            .map(move |string_offset| offset + string_offset)
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+    fn leaf_types(&self, ty: &Ty) -> Vec<Type> {
+        self.layout(ty)
+            .leaves
+            .into_iter()
+            .map(|(_, ty)| ty)
+            .collect()
+    }
+    fn signature(&self, func: &Func) -> Signature {
+        let mut signature = self.module.make_signature();
+        if func.captures.is_some() {
+            signature.params.push(AbiParam::new(self.ptr_ty));
+        }
+        for ty in &func.vars[..func.num_params] {
+            for ty in self.leaf_types(ty) {
+                let param = AbiParam::new(ty);
+                signature.params.push(if func.external && ty == types::I8 {
+                    param.uext()
+                } else {
+                    param
+                });
+            }
+        }
+        for ty in self.leaf_types(&func.ret_ty) {
+            signature.returns.push(AbiParam::new(ty));
+        }
+        signature
+    }
+    fn env_layout(&self, func: &Func) -> EnvLayout {
+        let captures = func.captures.as_ref().unwrap();
+        let layouts = captures.iter().map(|&(index, by_ref)| {
+            if by_ref {
+                Layout::reference(self.ptr_ty)
+            } else {
+                self.layout(&func.vars[index])
+            }
+        });
+        let (layout, offsets) =
+            Layout::concat(std::iter::once(Layout::scalar(self.ptr_ty)).chain(layouts));
+        let captures = offsets[1..]
+            .iter()
+            .zip(captures)
+            .map(|(&offset, &(_, by_ref))| (offset, by_ref))
+            .collect();
+        (layout.size, layout.ref_map(), captures)
+    }
+    fn shim(&mut self, index: usize, receiver_ty: Option<&Ty>) -> FuncId {
+        let mut signature = self.func_signatures[index].clone();
+        let receiver_layout = receiver_ty.map(|ty| self.layout(ty));
+        if let Some(layout) = &receiver_layout {
+            signature.params.drain(..layout.leaves.len());
+        }
+        signature.params.insert(0, AbiParam::new(self.ptr_ty));
+        let shim_id = self.module.declare_anonymous_function(&signature).unwrap();
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = signature;
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+        let entry_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        let params = builder.block_params(entry_block).to_vec();
+        let mut args = match &receiver_layout {
+            Some(layout) => layout.load(&mut builder, params[0], 0),
+            None => Vec::new(),
+        };
+        args.extend_from_slice(&params[1..]);
+        let func_ref = self
+            .module
+            .declare_func_in_func(self.func_ids[index], builder.func);
+        let inst = builder.ins().call(func_ref, &args);
+        let results = builder.inst_results(inst).to_vec();
+        builder.ins().return_(&results);
+        builder.seal_all_blocks();
+        builder.finalize();
+        self.define_function(shim_id, "", &mut ctx);
+        shim_id
+    }
+    // Defines the exported `main` of an object, which runs the initializer
+    // and then the program's `main`, whose result becomes the exit status.
+    fn entry_point(&mut self, init_id: FuncId, main_id: Option<FuncId>) {
+        let mut signature = self.module.make_signature();
+        signature.returns.push(AbiParam::new(types::I32));
+        let entry_id = self
+            .module
+            .declare_function("main", Linkage::Export, &signature)
+            .unwrap();
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = signature;
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+        let entry_block = builder.create_block();
+        builder.switch_to_block(entry_block);
+        let init_ref = self.module.declare_func_in_func(init_id, builder.func);
+        builder.ins().call(init_ref, &[]);
+        let status = match main_id {
+            Some(main_id) => {
+                let main_ref = self.module.declare_func_in_func(main_id, builder.func);
+                let inst = builder.ins().call(main_ref, &[]);
+                let result = builder.inst_results(inst)[0];
+                builder.ins().ireduce(types::I32, result)
+            }
+            None => builder.ins().iconst(types::I32, 0),
+        };
+        builder.ins().return_(&[status]);
+        builder.seal_all_blocks();
+        builder.finalize();
+        self.define_function(entry_id, "", &mut ctx);
+    }
+    // Native code may return a null string, which is reported where the
+    // function is declared.
+    fn checked_extern(&mut self, func: &Func, import_id: FuncId, signature: &Signature) -> FuncId {
+        let func_id = self.module.declare_anonymous_function(signature).unwrap();
+        let (line, column) = func.span.line_column(self.input);
+        let fail = self.frame_entry("null_string", &[types::I64, types::I64], &[]);
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = signature.clone();
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+        let entry_block = builder.create_block();
+        let fail_block = builder.create_block();
+        let ok_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        let args = builder.block_params(entry_block).to_vec();
+        let import = self.module.declare_func_in_func(import_id, builder.func);
+        let inst = builder.ins().call(import, &args);
+        let string = builder.inst_results(inst)[0];
+        builder.ins().brif(string, ok_block, &[], fail_block, &[]);
+        builder.switch_to_block(fail_block);
+        let fail = self.module.declare_func_in_func(fail, builder.func);
+        let line = builder.ins().iconst(types::I64, line as i64);
+        let column = builder.ins().iconst(types::I64, column as i64);
+        builder.ins().call(fail, &[line, column]);
+        builder.ins().trap(TrapCode::unwrap_user(1));
+        builder.switch_to_block(ok_block);
+        builder.ins().return_(&[string]);
+        builder.seal_all_blocks();
+        builder.finalize();
+        self.define_function(func_id, "", &mut ctx);
+        func_id
+    }
+    fn func_table(&mut self, shim_ids: &[FuncId]) -> DataId {
+        let data_id = self.module.declare_anonymous_data(false, false).unwrap();
+        let mut description = DataDescription::new();
+        let ptr_size = self.ptr_ty.bytes();
+        description.define_zeroinit((ptr_size as usize * shim_ids.len()).max(1));
+        for (position, &shim_id) in shim_ids.iter().enumerate() {
+            let func_ref = self.module.declare_func_in_data(shim_id, &mut description);
+            description.write_function_addr(position as u32 * ptr_size, func_ref);
+        }
+        self.module.define_data(data_id, &description).unwrap();
+        data_id
+    }
+    fn func_value(&mut self, index: usize) -> DataId {
+        if let Some(&data_id) = self.func_values.get(&index) {
+            return data_id;
+        }
+        let shim_id = self.shim(index, None);
+        let data_id = self.func_table(&[shim_id]);
+        self.func_values.insert(index, data_id);
+        data_id
+    }
+    fn vtable(&mut self, impl_index: usize) -> DataId {
+        if let Some(&data_id) = self.vtables.get(&impl_index) {
+            return data_id;
+        }
+        let def = &self.impls[impl_index];
+        let shim_ids: Vec<_> = def
+            .methods
+            .iter()
+            .map(|&index| self.shim(index, Some(&def.ty)))
+            .collect();
+        let data_id = self.func_table(&shim_ids);
+        self.vtables.insert(impl_index, data_id);
+        data_id
+    }
+    fn define(&mut self, func: &Func, func_id: FuncId) {
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = self.signature(func);
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+
+        let mut var_offsets = Vec::new();
+        let mut ref_slots = Vec::new();
+        for (ty, &boxed) in func.vars.iter().zip(&func.boxed) {
+            var_offsets.push(ref_slots.len());
+            let layout = if boxed {
+                Layout::reference(self.ptr_ty)
+            } else {
+                self.layout(ty)
+            };
+            for (offset, ty) in layout.leaves {
+                if layout.refs.contains(&offset) {
+                    let slot = builder.create_sized_stack_slot(StackSlotData::new(
+                        StackSlotKind::ExplicitSlot,
+                        ty.bytes(),
+                        3,
+                    ));
+                    ref_slots.push(Some(slot));
+                } else {
+                    builder.declare_var(Variable::new(ref_slots.len()), ty);
+                    ref_slots.push(None);
+                }
+            }
+        }
+        var_offsets.push(ref_slots.len());
+
+        let entry_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        let null = builder.ins().iconst(self.ptr_ty, 0);
+        for &slot in ref_slots.iter().flatten() {
+            builder.ins().stack_store(null, slot, 0);
+        }
+        let mut params = builder.block_params(entry_block).to_vec().into_iter();
+        let env = func.captures.as_ref().map(|_| params.next().unwrap());
+
+        let overflow = self.overflow;
+        let mut translator = FuncTranslator {
+            compiler: self,
+            builder,
+            func,
+            var_offsets,
+            ref_slots,
+            overflow,
+        };
+        for index in 0..func.num_params {
+            let num_leaves = translator.compiler.leaf_types(&func.vars[index]).len();
+            let values = params.by_ref().take(num_leaves).collect();
+            translator.init_var(index, values);
+        }
+        let mut initialized = vec![false; func.vars.len()];
+        initialized[..func.num_params].fill(true);
+        if let (Some(env), Some(captures)) = (env, &func.captures) {
+            let env_layout = translator.compiler.env_layout(func).2;
+            for (&(index, _), (offset, by_ref)) in captures.iter().zip(env_layout) {
+                if by_ref {
+                    let cell = translator.builder.ins().load(
+                        translator.compiler.ptr_ty,
+                        MemFlags::trusted(),
+                        env,
+                        offset,
+                    );
+                    let leaf = translator.var_offsets[index];
+                    translator.def_leaf(leaf, cell);
+                } else {
+                    let layout = translator.compiler.layout(&func.vars[index]);
+                    let values = layout.load(&mut translator.builder, env, offset);
+                    translator.init_var(index, values);
+                }
+                initialized[index] = true;
+            }
+        }
+        for (index, initialized) in initialized.into_iter().enumerate() {
+            if func.boxed[index] && !initialized {
+                let cell = translator.new_cell(&func.vars[index]);
+                let leaf = translator.var_offsets[index];
+                translator.def_leaf(leaf, cell);
+            }
+        }
+        for stmt in &func.body {
+            translator.translate_stmt(stmt);
+        }
+        if func.ret_ty == Ty::unit() {
+            translator.builder.ins().return_(&[]);
+        } else {
+            translator.builder.ins().trap(TrapCode::unwrap_user(1));
+        }
+        translator.builder.seal_all_blocks();
+        translator.builder.finalize();
+        let ref_slots: Vec<_> = translator.ref_slots.into_iter().flatten().collect();
+
+        let mut cursor = FuncCursor::new(&mut ctx.func);
+        while cursor.next_block().is_some() {
+            while let Some(inst) = cursor.next_inst() {
+                if !cursor.func.dfg.insts[inst].opcode().is_safepoint() {
+                    continue;
+                }
+                for &slot in &ref_slots {
+                    let entry = UserStackMapEntry {
+                        ty: self.ptr_ty,
+                        slot,
+                        offset: 0,
+                    };
+                    cursor.func.dfg.append_user_stack_map_entry(inst, entry);
+                }
+            }
+        }
+        self.define_function(func_id, &func.name, &mut ctx);
+    }
+    fn define_function(&mut self, func_id: FuncId, name: &str, ctx: &mut codegen::Context) {
+        ctx.func.name = UserFuncName::user(0, func_id.as_u32());
+        if !name.is_empty() && self.emit.is_some() {
+            writeln!(self.listing, "; {name}").unwrap();
+        }
+        match self.emit {
+            Some(Emit::Clif) => writeln!(self.listing, "{}", ctx.func.display()).unwrap(),
+            Some(Emit::Asm) => ctx.set_disasm(true),
+            _ => {}
+        }
+        self.module.define_function(func_id, ctx).unwrap();
+        let compiled_code = ctx.compiled_code().unwrap();
+        if let (Some(Emit::Asm), Some(vcode)) = (&self.emit, &compiled_code.vcode) {
+            writeln!(self.listing, "{vcode}").unwrap();
+        }
+        let stack_maps = compiled_code
+            .buffer
+            .user_stack_maps()
+            .iter()
+            .map(|(offset, span, stack_map)| {
+                let slots = stack_map.entries().map(|(_, slot)| slot as usize).collect();
+                (*offset as usize, *span as usize, slots)
+            })
+            .collect();
+        let size = compiled_code.code_info().total_size as usize;
+        self.compiled
+            .push((func_id, name.to_owned(), size, stack_maps));
+        self.module.clear_context(ctx);
+    }
+    fn frame_entry(&mut self, func: &str, params: &[Type], returns: &[Type]) -> FuncId {
+        if let Some(&func_id) = self.frame_entries.get(func) {
+            return func_id;
+        }
+        let mut signature = self.module.make_signature();
+        signature
+            .params
+            .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        signature
+            .returns
+            .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        let func_id = self.module.declare_anonymous_function(&signature).unwrap();
+        let mut runtime_signature = signature.clone();
+        runtime_signature.params.push(AbiParam::new(self.ptr_ty));
+        let mut ctx = self.module.make_context();
+        ctx.func.signature = signature;
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+        let entry_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        let mut args = builder.block_params(entry_block).to_vec();
+        args.push(builder.ins().get_frame_pointer(self.ptr_ty));
+        let callee = self.runtime_func(func, &runtime_signature);
+        let callee = self.module.declare_func_in_func(callee, builder.func);
+        let inst = builder.ins().call(callee, &args);
+        let results = builder.inst_results(inst).to_vec();
+        builder.ins().return_(&results);
+        builder.seal_all_blocks();
+        builder.finalize();
+        self.define_function(func_id, "", &mut ctx);
+        self.frame_entries.insert(func.to_owned(), func_id);
+        func_id
+    }
+    fn runtime_func(&mut self, func: &str, signature: &Signature) -> FuncId {
+        self.module
+            .declare_function(
+                &format!("{RUNTIME_PREFIX}{func}"),
+                Linkage::Import,
+                signature,
+            )
+            .unwrap()
+    }
+}
+
+impl<M: Module> FuncTranslator<'_, '_, '_, '_, M> {
+    fn new_cell(&mut self, ty: &Ty) -> Value {
+        let layout = self.compiler.layout(ty);
+        self.alloc(layout.size, layout.ref_map())
+    }
+    fn alloc(&mut self, size: u32, refs: i64) -> Value {
+        let size = self.builder.ins().iconst(types::I64, size as i64);
+        let refs = self.builder.ins().iconst(types::I64, refs);
+        self.call_allocator("alloc", &[size, refs])
+    }
+    fn call_allocator(&mut self, func: &str, args: &[Value]) -> Value {
+        let value = self.call_framed_runtime(func, args, &[self.compiler.ptr_ty])[0];
+        self.builder.declare_value_needs_stack_map(value);
+        value
+    }
+    fn call_framed_runtime(&mut self, func: &str, args: &[Value], returns: &[Type]) -> Vec<Value> {
+        let params: Vec<_> = args
+            .iter()
+            .map(|&arg| self.builder.func.dfg.value_type(arg))
+            .collect();
+        let func_id = self.compiler.frame_entry(func, &params, returns);
+        let func_ref = self
+            .compiler
+            .module
+            .declare_func_in_func(func_id, self.builder.func);
+        let inst = self.builder.ins().call(func_ref, args);
+        self.builder.inst_results(inst).to_vec()
+    }
+    fn declare_refs(&mut self, ty: &Ty, values: &[Value]) {
+        let layout = self.compiler.layout(ty);
+        for (&(offset, _), &value) in layout.leaves.iter().zip(values) {
+            if layout.refs.contains(&offset) {
+                self.builder.declare_value_needs_stack_map(value);
+            }
+        }
+    }
+    fn use_leaf(&mut self, leaf: usize) -> Value {
+        let Some(slot) = self.ref_slots[leaf] else {
+            return self.builder.use_var(Variable::new(leaf));
+        };
+        let value = self.builder.ins().stack_load(self.compiler.ptr_ty, slot, 0);
+        self.builder.declare_value_needs_stack_map(value);
+        value
+    }
+    fn def_leaf(&mut self, leaf: usize, value: Value) {
+        match self.ref_slots[leaf] {
+            Some(slot) => {
+                self.builder.ins().stack_store(value, slot, 0);
+            }
+            None => self.builder.def_var(Variable::new(leaf), value),
+        }
+    }
+    fn init_var(&mut self, index: usize, values: Vec<Value>) {
+        if self.func.boxed[index] {
+            let cell = self.new_cell(&self.func.vars[index]);
+            let layout = self.compiler.layout(&self.func.vars[index]);
+            layout.store(&mut self.builder, values, cell, 0);
+            self.def_leaf(self.var_offsets[index], cell);
+        } else {
+            for (leaf, value) in (self.var_offsets[index]..).zip(values) {
+                self.def_leaf(leaf, value);
+            }
+        }
+    }
+    fn translate_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.translate_expr(expr);
+            }
+            Stmt::Assign(pattern, value) => {
+                let values = self.translate_expr(value);
+                self.assign(pattern, &mut values.into_iter());
+            }
+            Stmt::Block(stmts) => {
+                for stmt in stmts {
+                    self.translate_stmt(stmt);
+                }
+            }
+            Stmt::If(condition, then_stmts, else_stmts, _) => {
+                let condition = self.translate_expr(condition)[0];
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                self.builder
+                    .ins()
+                    .brif(condition, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                for stmt in then_stmts {
+                    self.translate_stmt(stmt);
+                }
+                self.builder.ins().jump(merge_block, &[]);
+                self.builder.switch_to_block(else_block);
+                for stmt in else_stmts {
+                    self.translate_stmt(stmt);
+                }
+                self.builder.ins().jump(merge_block, &[]);
+                self.builder.switch_to_block(merge_block);
+            }
+            Stmt::While(condition, stmts, _) => {
+                let header_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+                self.builder.ins().jump(header_block, &[]);
+                self.builder.switch_to_block(header_block);
+                let condition = self.translate_expr(condition)[0];
+                self.builder
+                    .ins()
+                    .brif(condition, body_block, &[], exit_block, &[]);
+                self.builder.switch_to_block(body_block);
+                for stmt in stmts {
+                    self.translate_stmt(stmt);
+                }
+                self.builder.ins().jump(header_block, &[]);
+                self.builder.switch_to_block(exit_block);
+            }
+            Stmt::Return(value, _) => {
+                let values = self.translate_expr(value);
+                self.builder.ins().return_(&values);
+                let unreachable_block = self.builder.create_block();
+                self.builder.switch_to_block(unreachable_block);
+            }
+        }
+    }
+    fn assign(&mut self, pattern: &Pattern, values: &mut impl Iterator<Item = Value>) {
+        match *pattern {
+            Pattern::Variable(index) if self.func.boxed[index] => {
+                let cell = self.use_leaf(self.var_offsets[index]);
+                let layout = self.compiler.layout(&self.func.vars[index]);
+                let var_values: Vec<_> = values.take(layout.leaves.len()).collect();
+                layout.store(&mut self.builder, var_values, cell, 0);
+            }
+            Pattern::Variable(index) => {
+                for leaf in self.var_offsets[index]..self.var_offsets[index + 1] {
+                    self.def_leaf(leaf, values.next().unwrap());
+                }
+            }
+            Pattern::Global(index) => {
+                let addr = self.global_addr(index);
+                let layout = self.compiler.layout(&self.compiler.global_tys[index]);
+                let global_values: Vec<_> = values.take(layout.leaves.len()).collect();
+                layout.store(&mut self.builder, global_values, addr, 0);
+            }
+            Pattern::Tuple(ref patterns) => {
+                for pattern in patterns {
+                    self.assign(pattern, values);
+                }
+            }
+            Pattern::Index(ref elem_ty, ref array, ref index, span) => {
+                let layout = self.compiler.layout(elem_ty);
+                let elem_values: Vec<_> = values.take(layout.leaves.len()).collect();
+                let array = self.translate_expr(array)[0];
+                let index = self.translate_expr(index)[0];
+                let addr = self.elem_addr(&layout, array, index, span);
+                layout.store(&mut self.builder, elem_values, addr, 8);
+            }
+            Pattern::Field(struct_index, field_index, ref record) => {
+                let offset = self.compiler.struct_layouts[struct_index].1[field_index];
+                let layout = self
+                    .compiler
+                    .layout(&self.compiler.struct_fields[struct_index][field_index]);
+                let field_values: Vec<_> = values.take(layout.leaves.len()).collect();
+                let record = self.translate_expr(record)[0];
+                layout.store(&mut self.builder, field_values, record, offset);
+            }
+            Pattern::Coerce(impl_index, ref pattern) => {
+                let layout = self.compiler.layout(&self.compiler.impls[impl_index].ty);
+                let impl_values: Vec<_> = values.take(layout.leaves.len()).collect();
+                let object = self.trait_object(impl_index, impl_values);
+                self.assign(pattern, &mut object.into_iter());
+            }
+            Pattern::Discard => {
+                values.next();
+            }
+        }
+    }
+    fn trait_object(&mut self, impl_index: usize, values: Vec<Value>) -> Vec<Value> {
+        let layout = self.compiler.layout(&self.compiler.impls[impl_index].ty);
+        let cell = self.alloc(layout.size, layout.ref_map());
+        layout.store(&mut self.builder, values, cell, 0);
+        let data_id = self.compiler.vtable(impl_index);
+        let global_value = self
+            .compiler
+            .module
+            .declare_data_in_func(data_id, self.builder.func);
+        let vtable = self
+            .builder
+            .ins()
+            .global_value(self.compiler.ptr_ty, global_value);
+        vec![cell, vtable]
+    }
+    fn call_runtime(&mut self, func: &str, args: &[Value], returns: &[Type]) -> Vec<Value> {
+        let mut signature = self.compiler.module.make_signature();
+        for &arg in args {
+            let ty = self.builder.func.dfg.value_type(arg);
+            signature.params.push(AbiParam::new(ty));
+        }
+        for &ty in returns {
+            signature.returns.push(AbiParam::new(ty));
+        }
+        let callee = self.compiler.runtime_func(func, &signature);
+        let callee = self
+            .compiler
+            .module
+            .declare_func_in_func(callee, self.builder.func);
+        let inst = self.builder.ins().call(callee, args);
+        self.builder.inst_results(inst).to_vec()
+    }
+    fn location(&mut self, span: Span) -> [Value; 2] {
+        let (line, column) = span.line_column(self.compiler.input);
+        [
+            self.builder.ins().iconst(types::I64, line as i64),
+            self.builder.ins().iconst(types::I64, column as i64),
+        ]
+    }
+    fn new_array(&mut self, layout: &Layout, len: Value, location: [Value; 2]) -> Value {
+        let elem_size = self.builder.ins().iconst(types::I64, layout.size as i64);
+        let elem_refs = self.builder.ins().iconst(types::I64, layout.ref_map());
+        self.call_allocator(
+            "new_array",
+            &[len, elem_size, elem_refs, location[0], location[1]],
+        )
+    }
+    // Strings are never null, so the strings in new arrays are empty.
+    fn fill_strings(&mut self, elem_ty: &Ty, layout: &Layout, array: Value, len: Value) {
+        let offsets = self.compiler.string_offsets(elem_ty);
+        if offsets.is_empty() {
+            return;
+        }
+        let empty = self.translate_expr(&Expr::String(String::new()))[0];
+        let header_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit_block = self.builder.create_block();
+        self.builder.append_block_param(header_block, types::I64);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.ins().jump(header_block, &[zero]);
+        self.builder.switch_to_block(header_block);
+        let index = self.builder.block_params(header_block)[0];
+        let done = self
+            .builder
+            .ins()
+            .icmp(IntCC::SignedGreaterThanOrEqual, index, len);
+        self.builder
+            .ins()
+            .brif(done, exit_block, &[], body_block, &[]);
+        self.builder.switch_to_block(body_block);
+        let offset = self.builder.ins().imul_imm(index, layout.size as i64);
+        let addr = self.builder.ins().iadd(array, offset);
+        for offset in offsets {
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), empty, addr, 8 + offset);
+        }
+        let next = self.builder.ins().iadd_imm(index, 1);
+        self.builder.ins().jump(header_block, &[next]);
+        self.builder.switch_to_block(exit_block);
+    }
+    fn elem_addr(&mut self, layout: &Layout, array: Value, index: Value, span: Span) -> Value {
+        let len = self
+            .builder
+            .ins()
+            .load(types::I64, MemFlags::trusted(), array, 0);
+        let out_of_bounds = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, index, len);
+        self.fail_if(out_of_bounds, "index_out_of_bounds", &[index, len], span);
+        let offset = self.builder.ins().imul_imm(index, layout.size as i64);
+        self.builder.ins().iadd(array, offset)
+    }
+    fn global_addr(&mut self, index: usize) -> Value {
+        let global_value = self
+            .compiler
+            .module
+            .declare_data_in_func(self.compiler.global_ids[index], self.builder.func);
+        self.builder
+            .ins()
+            .global_value(self.compiler.ptr_ty, global_value)
+    }
+    fn translate_expr(&mut self, expr: &Expr) -> Vec<Value> {
+        match *expr {
+            Expr::Variable(index) if self.func.boxed[index] => {
+                let cell = self.use_leaf(self.var_offsets[index]);
+                let layout = self.compiler.layout(&self.func.vars[index]);
+                layout.load(&mut self.builder, cell, 0)
+            }
+            Expr::Variable(index) => (self.var_offsets[index]..self.var_offsets[index + 1])
+                .map(|leaf| self.use_leaf(leaf))
+                .collect(),
+            Expr::Global(index, span) => {
+                let addr = self.global_addr(index);
+                let layout = self.compiler.layout(&self.compiler.global_tys[index]);
+                let values = layout.load(&mut self.builder, addr, 0);
+                for (&(offset, _), &value) in layout.leaves.iter().zip(&values) {
+                    if layout.refs.contains(&offset) {
+                        let is_null = self.builder.ins().icmp_imm(IntCC::Equal, value, 0);
+                        self.fail_if(is_null, "null_dereference", &[], span);
+                    }
+                }
+                values
+            }
+            Expr::Func(index) => {
+                let data_id = self.compiler.func_value(index);
+                let global_value = self
+                    .compiler
+                    .module
+                    .declare_data_in_func(data_id, self.builder.func);
+                vec![self
+                    .builder
+                    .ins()
+                    .global_value(self.compiler.ptr_ty, global_value)]
+            }
+            Expr::Closure(lambda, ref captured) => {
+                let (size, refs, captures) = self.compiler.lambda_envs[lambda].clone();
+                let env = self.alloc(size, refs);
+                let func_ref = self
+                    .compiler
+                    .module
+                    .declare_func_in_func(self.compiler.lambda_ids[lambda], self.builder.func);
+                let code = self.builder.ins().func_addr(self.compiler.ptr_ty, func_ref);
+                self.builder.ins().store(MemFlags::trusted(), code, env, 0);
+                for (&index, (offset, by_ref)) in captured.iter().zip(captures) {
+                    if by_ref {
+                        let cell = self.use_leaf(self.var_offsets[index]);
+                        self.builder
+                            .ins()
+                            .store(MemFlags::trusted(), cell, env, offset);
+                    } else {
+                        let values = self.translate_expr(&Expr::Variable(index));
+                        let layout = self.compiler.layout(&self.func.vars[index]);
+                        layout.store(&mut self.builder, values, env, offset);
+                    }
+                }
+                vec![env]
+            }
+            Expr::Integer(value) => vec![self.builder.ins().iconst(types::I64, value)],
+            Expr::Float(value) => vec![self.builder.ins().f64const(value)],
+            Expr::Boolean(value) => vec![self.builder.ins().iconst(types::I8, value as i64)],
+            Expr::String(ref string) => {
+                let data_id = self
+                    .compiler
+                    .module
+                    .declare_anonymous_data(false, false)
+                    .unwrap();
+                let mut description = DataDescription::new();
+                description.define(
+                    string
+                        .bytes()
+                        .chain(std::iter::once(0))
+                        .collect::<Vec<_>>()
+                        .into_boxed_slice(),
+                );
+                self.compiler
+                    .module
+                    .define_data(data_id, &description)
+                    .unwrap();
+                let global_value = self
+                    .compiler
+                    .module
+                    .declare_data_in_func(data_id, self.builder.func);
+                vec![self
+                    .builder
+                    .ins()
+                    .global_value(self.compiler.ptr_ty, global_value)]
+            }
+            Expr::Tuple(ref exprs) => exprs
+                .iter()
+                .flat_map(|expr| self.translate_expr(expr))
+                .collect(),
+            Expr::Array(ref elem_ty, ref elems) => {
+                let layout = self.compiler.layout(elem_ty);
+                let len = self.builder.ins().iconst(types::I64, elems.len() as i64);
+                let zero = self.builder.ins().iconst(types::I64, 0);
+                let array = self.new_array(&layout, len, [zero, zero]);
+                for (index, elem) in elems.iter().enumerate() {
+                    let values = self.translate_expr(elem);
+                    let offset = 8 + (index as u32 * layout.size) as i32;
+                    layout.store(&mut self.builder, values, array, offset);
+                }
+                vec![array]
+            }
+            Expr::NewArray(ref elem_ty, ref len, span) => {
+                let layout = self.compiler.layout(elem_ty);
+                let len = self.translate_expr(len)[0];
+                let location = self.location(span);
+                let array = self.new_array(&layout, len, location);
+                self.fill_strings(elem_ty, &layout, array, len);
+                vec![array]
+            }
+            Expr::Index(ref elem_ty, ref array, ref index, span) => {
+                let layout = self.compiler.layout(elem_ty);
+                let array = self.translate_expr(array)[0];
+                let index = self.translate_expr(index)[0];
+                let addr = self.elem_addr(&layout, array, index, span);
+                layout.load(&mut self.builder, addr, 8)
+            }
+            Expr::Struct(struct_index, ref fields) => {
+                let values: Vec<_> = fields
+                    .iter()
+                    .flat_map(|field| self.translate_expr(field))
+                    .collect();
+                let layout = &self.compiler.struct_layouts[struct_index].0;
+                let (size, refs) = (layout.size, layout.ref_map());
+                let record = self.alloc(size, refs);
+                let layout = &self.compiler.struct_layouts[struct_index].0;
+                layout.store(&mut self.builder, values, record, 0);
+                vec![record]
+            }
+            Expr::Field(struct_index, field_index, ref record) => {
+                let record = self.translate_expr(record)[0];
+                let offset = self.compiler.struct_layouts[struct_index].1[field_index];
+                let layout = self
+                    .compiler
+                    .layout(&self.compiler.struct_fields[struct_index][field_index]);
+                layout.load(&mut self.builder, record, offset)
+            }
+            Expr::Variant(enum_index, variant, ref payload) => {
+                let values: Vec<_> = payload
+                    .iter()
+                    .flat_map(|value| self.translate_expr(value))
+                    .collect();
+                // The map covers the tag too, so that it is never cut short.
+                let tys = &self.compiler.variant_payloads[enum_index][variant];
+                let (cell_layout, _) =
+                    Layout::sequence(std::iter::once(&Ty::Int).chain(tys), self.compiler.ptr_ty);
+                let cell = self.alloc(cell_layout.size, cell_layout.ref_map());
+                let tag = self.builder.ins().iconst(types::I64, variant as i64);
+                self.builder.ins().store(MemFlags::trusted(), tag, cell, 0);
+                let layout = &self.compiler.variant_layouts[enum_index][variant].0;
+                layout.store(&mut self.builder, values, cell, 8);
+                vec![cell]
+            }
+            Expr::Match(enum_index, ref scrutinee, ref arms, ref ty) => {
+                self.translate_match(enum_index, scrutinee, arms, ty)
+            }
+            Expr::TraitObject(impl_index, ref value) => {
+                let values = self.translate_expr(value);
+                self.trait_object(impl_index, values)
+            }
+            Expr::DynamicCall(method, ref receiver, ref args, ref ret_ty) => {
+                let receiver = self.translate_expr(receiver);
+                let [cell, vtable] = receiver[..] else {
+                    unreachable!()
+                };
+                let args: Vec<_> = args
+                    .iter()
+                    .flat_map(|arg| self.translate_expr(arg))
+                    .collect();
+                let offset = (method as u32 * self.compiler.ptr_ty.bytes()) as i32;
+                let code = self.builder.ins().load(
+                    self.compiler.ptr_ty,
+                    MemFlags::trusted(),
+                    vtable,
+                    offset,
+                );
+                self.call_with_env(code, cell, args, ret_ty)
+            }
+            Expr::Length(ref array) => {
+                let array = self.translate_expr(array)[0];
+                vec![self
+                    .builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), array, 0)]
+            }
+            Expr::Builtin(ref builtin, ref args, span) => {
+                let args: Vec<_> = args
+                    .iter()
+                    .flat_map(|arg| self.translate_expr(arg))
+                    .collect();
+                self.translate_builtin(builtin, &args, span)
+            }
+            Expr::Negation(ref operand, span) => {
+                let operand = self.translate_expr(operand)[0];
+                if self.builder.func.dfg.value_type(operand) == types::F64 {
+                    vec![self.builder.ins().fneg(operand)]
+                } else {
+                    let zero = self.builder.ins().iconst(types::I64, 0);
+                    vec![self.arithmetic(&Operator::Sub, zero, operand, span)]
+                }
+            }
+            Expr::BinaryOperation(ref operator, ref left_operand, ref right_operand, span) => {
+                let left = self.translate_expr(left_operand)[0];
+                let right = self.translate_expr(right_operand)[0];
+                let is_int = self.builder.func.dfg.value_type(left) == types::I64;
+                let is_arithmetic = matches!(
+                    operator,
+                    Operator::Add | Operator::Sub | Operator::Mul | Operator::Div | Operator::Rem
+                );
+                if is_int && is_arithmetic {
+                    vec![self.arithmetic(operator, left, right, span)]
+                } else {
+                    vec![self.binary_operation(operator, left, right)]
+                }
+            }
+            Expr::Overflow(overflow, ref expr) => {
+                let outer = std::mem::replace(&mut self.overflow, overflow);
+                let values = self.translate_expr(expr);
+                self.overflow = outer;
+                values
+            }
+            Expr::Call(ref func, ref args, ref ret_ty) => {
+                let closure = match **func {
+                    Expr::Func(_) => None,
+                    _ => Some(self.translate_expr(func)[0]),
+                };
+                let args: Vec<_> = args
+                    .iter()
+                    .flat_map(|arg| self.translate_expr(arg))
+                    .collect();
+                if let Some(closure) = closure {
+                    let code = self.builder.ins().load(
+                        self.compiler.ptr_ty,
+                        MemFlags::trusted(),
+                        closure,
+                        0,
+                    );
+                    return self.call_with_env(code, closure, args, ret_ty);
+                }
+                let Expr::Func(index) = **func else {
+                    unreachable!()
+                };
+                let func_ref = self
+                    .compiler
+                    .module
+                    .declare_func_in_func(self.compiler.func_ids[index], self.builder.func);
+                let inst = self.builder.ins().call(func_ref, &args);
+                let results = self.builder.inst_results(inst).to_vec();
+                self.declare_refs(ret_ty, &results);
+                results
+            }
+        }
+    }
+    fn translate_builtin(&mut self, builtin: &Builtin, args: &[Value], span: Span) -> Vec<Value> {
+        let is_float =
+            |builder: &FunctionBuilder| builder.func.dfg.value_type(args[0]) == types::F64;
+        match *builtin {
+            Builtin::Print(ref ty, newline) => {
+                let print = match ty {
+                    Some(Ty::Int) => Some("print_int"),
+                    Some(Ty::Float) => Some("print_float"),
+                    Some(Ty::Bool) => Some("print_bool"),
+                    Some(Ty::String) => Some("print_string"),
+                    None => None,
+                    Some(_) => unreachable!(),
+                };
+                if let Some(print) = print {
+                    self.call_runtime(print, args, &[]);
+                }
+                if newline {
+                    self.call_runtime("print_newline", &[], &[]);
+                }
+                Vec::new()
+            }
+            Builtin::Format(ref tys) => {
+                let mut string = None;
+                for (&arg, ty) in args.iter().zip(tys) {
+                    let piece = match ty {
+                        Ty::Int => self.call_allocator("int_to_string", &[arg]),
+                        Ty::Float => self.call_allocator("float_to_string", &[arg]),
+                        Ty::Bool => self.call_allocator("bool_to_string", &[arg]),
+                        Ty::String => arg,
+                        _ => unreachable!(),
+                    };
+                    string = Some(match string {
+                        Some(string) => self.call_allocator("concat", &[string, piece]),
+                        None => piece,
+                    });
+                }
+                vec![string.unwrap()]
+            }
+            Builtin::ReadLine => vec![self.call_allocator("read_line", &[])],
+            Builtin::Args => vec![self.call_allocator("args", &[])],
+            Builtin::ParseInt => {
+                let [line, column] = self.location(span);
+                self.call_framed_runtime("parse_int", &[args[0], line, column], &[types::I64])
+            }
+            Builtin::Abs if is_float(&self.builder) => vec![self.builder.ins().fabs(args[0])],
+            Builtin::Abs => vec![self.builder.ins().iabs(args[0])],
+            Builtin::Min if is_float(&self.builder) => {
+                vec![self.builder.ins().fmin(args[0], args[1])]
+            }
+            Builtin::Min => vec![self.builder.ins().smin(args[0], args[1])],
+            Builtin::Max if is_float(&self.builder) => {
+                vec![self.builder.ins().fmax(args[0], args[1])]
+            }
+            Builtin::Max => vec![self.builder.ins().smax(args[0], args[1])],
+            Builtin::Sqrt => vec![self.builder.ins().sqrt(args[0])],
+            Builtin::Sin => self.call_runtime("sin", args, &[types::F64]),
+            Builtin::Pow => self.call_runtime("pow", args, &[types::F64]),
+            Builtin::Exit => {
+                self.call_runtime("exit", args, &[]);
+                Vec::new()
+            }
+        }
+    }
+    fn call_with_env(
+        &mut self,
+        code: Value,
+        env: Value,
+        args: Vec<Value>,
+        ret_ty: &Ty,
+    ) -> Vec<Value> {
+        let mut signature = self.compiler.module.make_signature();
+        signature.params.push(AbiParam::new(self.compiler.ptr_ty));
+        for &arg in &args {
+            let ty = self.builder.func.dfg.value_type(arg);
+            signature.params.push(AbiParam::new(ty));
+        }
+        for ty in self.compiler.leaf_types(ret_ty) {
+            signature.returns.push(AbiParam::new(ty));
+        }
+        let signature = self.builder.import_signature(signature);
+        let args: Vec<_> = std::iter::once(env).chain(args).collect();
+        let inst = self.builder.ins().call_indirect(signature, code, &args);
+        let results = self.builder.inst_results(inst).to_vec();
+        self.declare_refs(ret_ty, &results);
+        results
+    }
+    fn translate_match(
+        &mut self,
+        enum_index: usize,
+        scrutinee: &Expr,
+        arms: &[Arm],
+        ty: &Ty,
+    ) -> Vec<Value> {
+        let cell = self.translate_expr(scrutinee)[0];
+        let tag = self
+            .builder
+            .ins()
+            .load(types::I64, MemFlags::trusted(), cell, 0);
+        let merge_block = self.builder.create_block();
+        for ty in self.compiler.leaf_types(ty) {
+            self.builder.append_block_param(merge_block, ty);
+        }
+        let mut switch = Switch::new();
+        let mut default_block = None;
+        let arm_blocks: Vec<_> = arms
+            .iter()
+            .map(|arm| {
+                let block = self.builder.create_block();
+                match arm.variant {
+                    Some(variant) => switch.set_entry(variant as u128, block),
+                    None => default_block = Some(block),
+                }
+                block
+            })
+            .collect();
+        let trap_block = default_block.is_none().then(|| self.builder.create_block());
+        switch.emit(
+            &mut self.builder,
+            tag,
+            default_block.or(trap_block).unwrap(),
+        );
+        if let Some(trap_block) = trap_block {
+            self.builder.switch_to_block(trap_block);
+            self.builder.ins().trap(TrapCode::unwrap_user(1));
+        }
+        for (arm, block) in arms.iter().zip(arm_blocks) {
+            self.builder.switch_to_block(block);
+            if let Some(variant) = arm.variant {
+                let offsets = self.compiler.variant_layouts[enum_index][variant].1.clone();
+                for (binding_index, binding) in arm.bindings.iter().enumerate() {
+                    let layout = self.compiler.layout(
+                        &self.compiler.variant_payloads[enum_index][variant][binding_index],
+                    );
+                    let values = layout.load(&mut self.builder, cell, 8 + offsets[binding_index]);
+                    self.assign(binding, &mut values.into_iter());
+                }
+            }
+            for stmt in &arm.body {
+                self.translate_stmt(stmt);
+            }
+            let values = self.translate_expr(&arm.value);
+            self.builder.ins().jump(merge_block, &values);
+        }
+        self.builder.switch_to_block(merge_block);
+        let results = self.builder.block_params(merge_block).to_vec();
+        self.declare_refs(ty, &results);
+        results
+    }
+    fn fail_if(&mut self, condition: Value, func: &str, args: &[Value], span: Span) {
+        let ok_block = self.builder.create_block();
+        let fail_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(condition, fail_block, &[], ok_block, &[]);
+        self.builder.switch_to_block(fail_block);
+        let mut args = args.to_vec();
+        args.extend(self.location(span));
+        self.call_framed_runtime(func, &args, &[]);
+        self.builder.ins().trap(TrapCode::unwrap_user(1));
+        self.builder.switch_to_block(ok_block);
+    }
+    fn division(&mut self, operator: &Operator, left: Value, right: Value, span: Span) -> Value {
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, right, 0);
+        self.fail_if(is_zero, "division_by_zero", &[], span);
+        let is_min = self.builder.ins().icmp_imm(IntCC::Equal, left, i64::MIN);
+        let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, right, -1);
+        let overflowed = self.builder.ins().band(is_min, is_minus_one);
+        let is_div = matches!(operator, Operator::Div);
+        if is_div && matches!(self.overflow, Overflow::Checked) {
+            self.fail_if(overflowed, "integer_overflow", &[], span);
+        }
+        let one = self.builder.ins().iconst(types::I64, 1);
+        let right = self.builder.ins().select(overflowed, one, right);
+        let value = self.binary_operation(operator, left, right);
+        if is_div && matches!(self.overflow, Overflow::Saturating) {
+            let max = self.builder.ins().iconst(types::I64, i64::MAX);
+            return self.builder.ins().select(overflowed, max, value);
+        }
+        value
+    }
+    fn arithmetic(&mut self, operator: &Operator, left: Value, right: Value, span: Span) -> Value {
+        if matches!(operator, Operator::Div | Operator::Rem) {
+            return self.division(operator, left, right, span);
+        }
+        let ins = self.builder.ins();
+        let (value, overflowed) = match (self.overflow, operator) {
+            (Overflow::Wrapping, _) => return self.binary_operation(operator, left, right),
+            (_, Operator::Add) => ins.sadd_overflow(left, right),
+            (_, Operator::Sub) => ins.ssub_overflow(left, right),
+            (_, Operator::Mul) => ins.smul_overflow(left, right),
+            _ => unreachable!(),
+        };
+        match self.overflow {
+            Overflow::Checked => {
+                self.fail_if(overflowed, "integer_overflow", &[], span);
+                value
+            }
+            Overflow::Saturating => {
+                let sign = match operator {
+                    Operator::Mul => self.builder.ins().bxor(left, right),
+                    _ => left,
+                };
+                let sign = self.builder.ins().sshr_imm(sign, 63);
+                let saturated = self.builder.ins().bxor_imm(sign, i64::MAX);
+                self.builder.ins().select(overflowed, saturated, value)
+            }
+            Overflow::Wrapping => unreachable!(),
+        }
+    }
+    fn binary_operation(&mut self, operator: &Operator, left: Value, right: Value) -> Value {
+        let is_float = self.builder.func.dfg.value_type(left) == types::F64;
+        let ins = self.builder.ins();
+        if is_float {
+            match operator {
+                Operator::Add => ins.fadd(left, right),
+                Operator::Sub => ins.fsub(left, right),
+                Operator::Mul => ins.fmul(left, right),
+                Operator::Div => ins.fdiv(left, right),
+                Operator::Equal => ins.fcmp(FloatCC::Equal, left, right),
+                Operator::NotEqual => ins.fcmp(FloatCC::NotEqual, left, right),
+                Operator::Greater => ins.fcmp(FloatCC::GreaterThan, left, right),
+                Operator::GreaterEqual => ins.fcmp(FloatCC::GreaterThanOrEqual, left, right),
+                Operator::Less => ins.fcmp(FloatCC::LessThan, left, right),
+                Operator::LessEqual => ins.fcmp(FloatCC::LessThanOrEqual, left, right),
+                Operator::Rem | Operator::Assign => unreachable!(),
+            }
+        } else {
+            match operator {
+                Operator::Add => ins.iadd(left, right),
+                Operator::Sub => ins.isub(left, right),
+                Operator::Mul => ins.imul(left, right),
+                Operator::Div => ins.sdiv(left, right),
+                Operator::Rem => ins.srem(left, right),
+                Operator::Equal => ins.icmp(IntCC::Equal, left, right),
+                Operator::NotEqual => ins.icmp(IntCC::NotEqual, left, right),
+                Operator::Greater => ins.icmp(IntCC::SignedGreaterThan, left, right),
+                Operator::GreaterEqual => ins.icmp(IntCC::SignedGreaterThanOrEqual, left, right),
+                Operator::Less => ins.icmp(IntCC::SignedLessThan, left, right),
+                Operator::LessEqual => ins.icmp(IntCC::SignedLessThanOrEqual, left, right),
+                Operator::Assign => unreachable!(),
+            }
+        }
+    }
+}
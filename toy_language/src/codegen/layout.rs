@@ -0,0 +1,105 @@
+use cranelift::prelude::*;
+
+use crate::ast::Ty;
+
+pub struct Layout {
+    pub size: u32,
+    pub align: u32,
+    pub leaves: Vec<(i32, Type)>,
+    pub refs: Vec<i32>,
+}
+
+impl Layout {
+    pub fn of(ty: &Ty, ptr_ty: Type) -> Layout {
+        match ty {
+            Ty::Int => Layout::scalar(types::I64),
+            Ty::Float => Layout::scalar(types::F64),
+            Ty::Bool => Layout::scalar(types::I8),
+            Ty::Ptr => Layout::scalar(ptr_ty),
+            Ty::String | Ty::Array(_) | Ty::Struct(_) | Ty::Enum(_) | Ty::Func(..) => {
+                Layout::reference(ptr_ty)
+            }
+            Ty::Tuple(tys) => Layout::sequence(tys, ptr_ty).0,
+            Ty::Trait(_) => Layout::concat([Layout::reference(ptr_ty), Layout::scalar(ptr_ty)]).0,
+        }
+    }
+    pub fn sequence<'a>(tys: impl IntoIterator<Item = &'a Ty>, ptr_ty: Type) -> (Layout, Vec<i32>) {
+        Layout::concat(tys.into_iter().map(|ty| Layout::of(ty, ptr_ty)))
+    }
+    pub fn concat(layouts: impl IntoIterator<Item = Layout>) -> (Layout, Vec<i32>) {
+        let mut size = 0u32;
+        let mut align = 1;
+        let mut leaves = Vec::new();
+        let mut refs = Vec::new();
+        let mut offsets = Vec::new();
+        for layout in layouts {
+            size = size.next_multiple_of(layout.align);
+            offsets.push(size as i32);
+            leaves.extend(
+                layout
+                    .leaves
+                    .into_iter()
+                    .map(|(offset, ty)| (size as i32 + offset, ty)),
+            );
+            refs.extend(layout.refs.into_iter().map(|offset| size as i32 + offset));
+            size += layout.size;
+            align = align.max(layout.align);
+        }
+        let layout = Layout {
+            size: size.next_multiple_of(align),
+            align,
+            leaves,
+            refs,
+        };
+        (layout, offsets)
+    }
+    pub fn scalar(ty: Type) -> Layout {
+        Layout {
+            size: ty.bytes(),
+            align: ty.bytes(),
+            leaves: vec![(0, ty)],
+            refs: Vec::new(),
+        }
+    }
+    pub fn reference(ptr_ty: Type) -> Layout {
+        Layout {
+            refs: vec![0],
+            ..Layout::scalar(ptr_ty)
+        }
+    }
+    pub fn ref_map(&self) -> i64 {
+        if self.size > 64 * 8 {
+            return -1;
+        }
+        self.refs
+            .iter()
+            .fold(0, |map, &offset| map | 1 << (offset / 8))
+    }
+    pub fn load(&self, builder: &mut FunctionBuilder, addr: Value, offset: i32) -> Vec<Value> {
+        self.leaves
+            .iter()
+            .map(|&(leaf_offset, ty)| {
+                let value = builder
+                    .ins()
+                    .load(ty, MemFlags::trusted(), addr, offset + leaf_offset);
+                if self.refs.contains(&leaf_offset) {
+                    builder.declare_value_needs_stack_map(value);
+                }
+                value
+            })
+            .collect()
+    }
+    pub fn store(
+        &self,
+        builder: &mut FunctionBuilder,
+        values: impl IntoIterator<Item = Value>,
+        addr: Value,
+        offset: i32,
+    ) {
+        for (&(leaf_offset, _), value) in self.leaves.iter().zip(values) {
+            builder
+                .ins()
+                .store(MemFlags::trusted(), value, addr, offset + leaf_offset);
+        }
+    }
+}
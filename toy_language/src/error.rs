@@ -0,0 +1,58 @@
+use std::any::Any;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    Usage,
+    Lexical,
+    Syntax,
+    Type,
+    Runtime,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Lexical => "lexical",
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Type => "type",
+            ErrorKind::Runtime => "runtime",
+        }
+    }
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 2,
+            ErrorKind::Lexical => 10,
+            ErrorKind::Syntax => 11,
+            ErrorKind::Type => 12,
+            ErrorKind::Runtime => 13,
+        }
+    }
+}
+
+// The lexer panics with this rather than with a string, so that its errors can
+// be told apart from the syntax errors found while the same input is parsed.
+pub struct LexicalError(pub String);
+
+pub fn lexical_error(message: impl Into<String>) -> ! {
+    std::panic::panic_any(LexicalError(message.into()))
+}
+
+// The kind of error a panic reports, given the stage it happened in.
+pub fn kind(payload: &(dyn Any + Send), stage: ErrorKind) -> ErrorKind {
+    if payload.is::<LexicalError>() {
+        ErrorKind::Lexical
+    } else {
+        stage
+    }
+}
+
+pub fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(LexicalError(message)) = payload.downcast_ref() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else {
+        payload.downcast_ref::<String>().map_or("", String::as_str)
+    }
+}
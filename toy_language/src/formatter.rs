@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+
+use crate::parser::{self, Lexeme, Token, Trivia};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Gap {
+    None,
+    Space,
+    Newline,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+enum Open {
+    Brace { indent: usize },
+    Bracket { commas: usize },
+}
+
+struct Formatter {
+    roles: Vec<Option<Role>>,
+    output: String,
+    open: Vec<Open>,
+    line_indent: usize,
+    prev: Option<usize>,
+    pending_newline: bool,
+}
+
+pub fn format(source: &str) -> String {
+    let lexemes = parser::tokens(source);
+    let mut formatter = Formatter {
+        roles: roles(&lexemes),
+        output: String::new(),
+        open: Vec::new(),
+        line_indent: 0,
+        prev: None,
+        pending_newline: false,
+    };
+    for index in 0..lexemes.len() {
+        formatter.lexeme(&lexemes, index);
+    }
+    let mut output = formatter.output;
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+fn roles(lexemes: &[Lexeme]) -> Vec<Option<Role>> {
+    let postfix: HashSet<&str> = lexemes
+        .windows(4)
+        .filter_map(|window| match window {
+            [Lexeme {
+                token: Some(Token::Identifier(keyword)),
+                ..
+            }, Lexeme {
+                token: Some(Token::Identifier(fixity)),
+                ..
+            }, Lexeme {
+                token: Some(Token::Integer(_)),
+                ..
+            }, Lexeme {
+                token: Some(Token::Symbol(symbol)),
+                ..
+            }] if *keyword == "operator" && *fixity == "postfix" => Some(*symbol),
+            _ => None,
+        })
+        .collect();
+    let mut roles = Vec::new();
+    let mut after_operand = false;
+    for (index, lexeme) in lexemes.iter().enumerate() {
+        let role = match &lexeme.token {
+            Some(Token::Symbol(symbol)) if *symbol == "=" => Some(Role::Infix),
+            Some(Token::Symbol(_)) if !after_operand => Some(Role::Prefix),
+            Some(Token::Symbol(symbol)) => {
+                let next = lexemes.get(index + 1).and_then(|next| next.token.as_ref());
+                if postfix.contains(symbol) && !next.is_some_and(starts_operand) {
+                    Some(Role::Postfix)
+                } else {
+                    Some(Role::Infix)
+                }
+            }
+            _ => None,
+        };
+        after_operand = match &lexeme.token {
+            Some(Token::Symbol(_)) => role == Some(Role::Postfix),
+            Some(token) => ends_operand(token),
+            None => false,
+        };
+        roles.push(role);
+    }
+    roles
+}
+
+fn starts_operand(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::StringLiteral(_)
+            | Token::OpeningBracket(_)
+    )
+}
+
+fn ends_operand(token: &Token) -> bool {
+    match token {
+        Token::Identifier(name) => !is_keyword(name),
+        Token::Integer(_)
+        | Token::Float(_)
+        | Token::StringLiteral(_)
+        | Token::ClosingBracket(_) => true,
+        _ => false,
+    }
+}
+
+fn is_keyword(name: &str) -> bool {
+    ["if", "while", "match"].contains(&name)
+}
+
+impl Formatter {
+    fn lexeme(&mut self, lexemes: &[Lexeme], index: usize) {
+        let lexeme = &lexemes[index];
+        let mut newlines = 0;
+        for &(kind, text) in &lexeme.trivia {
+            if kind == Trivia::Whitespace {
+                newlines += text.matches('\n').count();
+                continue;
+            }
+            if newlines > 0 || self.output.is_empty() {
+                self.newline(newlines > 1);
+                self.pending_newline = true;
+            } else if !self.output.ends_with(['(', '[']) {
+                self.output.push(' ');
+            }
+            self.output.push_str(text.trim_end());
+            self.pending_newline |= kind == Trivia::LineComment;
+            newlines = 0;
+        }
+        let Some(token) = &lexeme.token else {
+            return;
+        };
+        let next = lexemes.get(index + 1);
+        if let (Token::Comma, Some(Open::Bracket { commas }), Some(next)) =
+            (token, self.open.last(), next)
+        {
+            let has_comments = next
+                .trivia
+                .iter()
+                .any(|(kind, _)| *kind != Trivia::Whitespace);
+            if *commas > 0 && matches!(next.token, Some(Token::ClosingBracket(_))) && !has_comments
+            {
+                return;
+            }
+        }
+        let closed = match token {
+            Token::ClosingBrace | Token::ClosingBracket(_) => self.open.pop(),
+            _ => None,
+        };
+        let gap = if self.pending_newline {
+            Gap::Newline
+        } else {
+            self.gap(lexemes, index)
+        };
+        match gap {
+            Gap::None
+                if self.output.ends_with("*/")
+                    && !matches!(
+                        token,
+                        Token::Comma | Token::Semicolon | Token::ClosingBracket(_)
+                    ) =>
+            {
+                self.output.push(' ')
+            }
+            Gap::None => {}
+            Gap::Space => self.output.push(' '),
+            Gap::Newline => match closed {
+                Some(Open::Brace { indent }) => {
+                    self.output.push('\n');
+                    self.indent(indent);
+                }
+                _ => self.newline(newlines > 1),
+            },
+        }
+        self.output.push_str(lexeme.text);
+        match token {
+            Token::OpeningBrace => self.open.push(Open::Brace {
+                indent: self.line_indent,
+            }),
+            Token::OpeningBracket(_) => self.open.push(Open::Bracket { commas: 0 }),
+            Token::Comma => {
+                if let Some(Open::Bracket { commas }) = self.open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => {}
+        }
+        self.prev = Some(index);
+        self.pending_newline = false;
+    }
+    fn gap(&self, lexemes: &[Lexeme], index: usize) -> Gap {
+        let Some(prev) = self.prev else {
+            return Gap::None;
+        };
+        let (Some(prev_token), Some(token)) = (&lexemes[prev].token, &lexemes[index].token) else {
+            return Gap::None;
+        };
+        let next = lexemes.get(index + 1).and_then(|next| next.token.as_ref());
+        match (prev_token, token) {
+            (Token::OpeningBrace, Token::ClosingBrace) => return Gap::None,
+            (Token::OpeningBrace, _) | (_, Token::ClosingBrace) => return Gap::Newline,
+            (Token::ClosingBrace, Token::Identifier(name)) if *name == "else" => {
+                return Gap::Space;
+            }
+            (
+                Token::ClosingBrace,
+                Token::Semicolon | Token::Comma | Token::Dot | Token::ClosingBracket(_),
+            ) => return Gap::None,
+            (Token::ClosingBrace, _) => return Gap::Newline,
+            (Token::Semicolon, _) => {
+                return match self.open.last() {
+                    Some(Open::Bracket { .. }) => Gap::Space,
+                    _ => Gap::Newline,
+                };
+            }
+            (_, Token::Comma | Token::Semicolon | Token::ClosingBracket(_)) => return Gap::None,
+            (Token::Integer(_), Token::Dot) => return Gap::Space,
+            (_, Token::Dot) | (Token::Dot, _) | (Token::OpeningBracket(_), _) => return Gap::None,
+            (_, Token::Colon) => {
+                return match next {
+                    Some(Token::Symbol(symbol)) if *symbol == "=" => Gap::Space,
+                    _ => Gap::None,
+                };
+            }
+            (Token::Colon, Token::Symbol(symbol)) if *symbol == "=" => return Gap::None,
+            _ => {}
+        }
+        match self.roles[index] {
+            Some(Role::Infix) => return Gap::Space,
+            Some(Role::Postfix) if !matches!(prev_token, Token::Symbol(_)) => return Gap::None,
+            _ => {}
+        }
+        match (self.roles[prev], token) {
+            (Some(Role::Infix), _) | (Some(Role::Prefix), Token::Symbol(_)) => Gap::Space,
+            (Some(Role::Prefix), _) => Gap::None,
+            (_, Token::OpeningBracket(_)) if ends_operand(prev_token) => Gap::None,
+            (Some(Role::Postfix), Token::OpeningBracket(_)) => Gap::None,
+            _ => Gap::Space,
+        }
+    }
+    fn newline(&mut self, blank: bool) {
+        if self.output.is_empty() {
+            return;
+        }
+        self.output.push('\n');
+        if blank && !self.output.ends_with("{\n") {
+            self.output.push('\n');
+        }
+        let indent = match self
+            .open
+            .iter()
+            .rposition(|open| matches!(open, Open::Brace { .. }))
+        {
+            Some(position) => {
+                let Open::Brace { indent } = self.open[position] else {
+                    unreachable!()
+                };
+                indent + 1 + usize::from(position + 1 < self.open.len())
+            }
+            None => usize::from(!self.open.is_empty()),
+        };
+        self.indent(indent);
+    }
+    fn indent(&mut self, indent: usize) {
+        self.line_indent = indent;
+        self.output.push_str(&"    ".repeat(indent));
+    }
+}
@@ -0,0 +1,16 @@
+pub mod ast;
+pub mod codegen;
+pub mod error;
+pub mod formatter;
+pub mod macros;
+pub mod module;
+pub mod parser;
+pub mod pre_ast;
+pub mod resolver;
+mod runtime;
+pub mod simplify;
+pub mod source_map;
+pub mod span;
+pub mod symbol;
+
+pub use runtime::{enable_gc_stress, set_args};
@@ -0,0 +1,335 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::module::{self, Module};
+use crate::pre_ast::{Ast, BracketKind, NodeId, Stmt, Term, TermKind};
+use crate::source_map;
+use crate::span::{self, Span};
+use crate::symbol::Symbol;
+
+const MAX_DEPTH: usize = 32;
+
+// A macro stands for its body with its parameters replaced by the terms it is
+// called with. The variables the body declares are renamed at each expansion,
+// so they cannot clash with those of the code around it, and the other names
+// it uses refer to what they do where the macro is defined.
+struct Macro {
+    name: Symbol,
+    span: Span,
+    module: usize,
+    params: Vec<Symbol>,
+    body: Vec<Stmt>,
+    locals: HashSet<Symbol>,
+    // The text of the definition, from the start of its first line.
+    origin: Span,
+}
+
+struct Expander {
+    macros: HashMap<Symbol, Rc<Macro>>,
+    expansions: usize,
+    depth: usize,
+    // The call that the expansions being made started from.
+    outermost: Span,
+    macro_names: HashMap<Span, usize>,
+}
+
+// Replaces the calls to macros by their expansions, before names are
+// resolved. Modules see their own macros and the `pub` macros of the modules
+// they import.
+pub fn expand(ast: &mut Ast, modules: &mut [Module]) {
+    let mut exported: Vec<HashMap<Symbol, Rc<Macro>>> = Vec::new();
+    let mut expansions = 0;
+    for (index, module) in modules.iter_mut().enumerate() {
+        let mut expander = Expander {
+            macros: HashMap::new(),
+            expansions,
+            depth: 0,
+            outermost: Span::new(0, 0),
+            macro_names: HashMap::new(),
+        };
+        for &import in &module.imports {
+            for (&name, definition) in &exported[import] {
+                let prev = expander.macros.insert(name, definition.clone());
+                assert!(
+                    prev.is_none_or(|prev| Rc::ptr_eq(&prev, definition)),
+                    "`{name}` is imported from more than one module"
+                );
+            }
+        }
+        let mut own = HashSet::new();
+        let mut public = HashMap::new();
+        let mut items = Vec::new();
+        let stmts = std::mem::take(&mut module.stmts);
+        for (stmt, is_public) in stmts.into_iter().zip(std::mem::take(&mut module.public)) {
+            let Some(definition) = definition(ast, &stmt, index) else {
+                items.push((stmt, is_public));
+                continue;
+            };
+            let definition = Rc::new(definition);
+            let name = definition.name;
+            if is_public {
+                public.insert(name, definition.clone());
+            }
+            assert!(own.insert(name), "duplicate macro `{name}`");
+            expander.macros.insert(name, definition);
+        }
+        for (stmt, is_public) in items {
+            let mut expanded = Vec::new();
+            expander.expand_stmt(ast, stmt, true, &mut expanded);
+            for stmt in expanded {
+                let (marked, stmt) = module::visibility(ast, stmt);
+                module.stmts.push(stmt);
+                module.public.push(is_public || marked);
+            }
+        }
+        expansions = expander.expansions;
+        module.macro_names = expander.macro_names;
+        exported.push(public);
+    }
+}
+
+impl Expander {
+    fn expand_stmts(&mut self, ast: &mut Ast, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let mut expanded = Vec::new();
+        for stmt in stmts {
+            self.expand_stmt(ast, stmt, false, &mut expanded);
+        }
+        expanded
+    }
+    // At the top level, only the initializers of globals are expanded in the
+    // heads of blocks, which are otherwise definitions.
+    fn expand_stmt(&mut self, ast: &mut Ast, stmt: Stmt, top_level: bool, out: &mut Vec<Stmt>) {
+        match stmt {
+            Stmt::Term(Some(id)) => {
+                let Some((definition, args, call)) = self.call(ast, id) else {
+                    out.push(Stmt::Term(Some(self.expand_term(ast, id))));
+                    return;
+                };
+                let stmts = self.instantiate(ast, &definition, &args, call);
+                self.depth += 1;
+                for stmt in stmts {
+                    self.expand_stmt(ast, stmt, top_level, out);
+                }
+                self.depth -= 1;
+            }
+            Stmt::Block { antecedent, stmts } => {
+                let antecedent = match antecedent {
+                    Some(id)
+                        if !top_level || matches!(ast[id].kind, TermKind::Assignment { .. }) =>
+                    {
+                        Some(self.expand_term(ast, id))
+                    }
+                    antecedent => antecedent,
+                };
+                let stmts = self.expand_stmts(ast, stmts);
+                out.push(Stmt::Block { antecedent, stmts });
+            }
+            stmt => out.push(stmt),
+        }
+    }
+    // Terms are rebuilt, rather than changed in place, when something below
+    // them is expanded, so that children keep smaller IDs than their parents.
+    fn expand_term(&mut self, ast: &mut Ast, id: NodeId) -> NodeId {
+        if let Some((definition, args, call)) = self.call(ast, id) {
+            let stmts = self.instantiate(ast, &definition, &args, call);
+            let [Stmt::Term(Some(term))] = stmts[..] else {
+                span::at(call);
+                panic!(
+                    "`{}` does not expand to an expression (defined at {})",
+                    definition.name,
+                    source_map::location(definition.span)
+                );
+            };
+            self.depth += 1;
+            let term = self.expand_term(ast, term);
+            self.depth -= 1;
+            return term;
+        }
+        let mut term = ast[id].clone();
+        let changed = match &mut term.kind {
+            TermKind::Block { antecedent, stmts } => {
+                let expanded_antecedent = antecedent.map(|id| self.expand_term(ast, id));
+                let expanded = self.expand_stmts(ast, stmts.clone());
+                let changed = expanded_antecedent != *antecedent || expanded != *stmts;
+                (*antecedent, *stmts) = (expanded_antecedent, expanded);
+                changed
+            }
+            kind => {
+                let mut changed = false;
+                for child in kind.children_mut() {
+                    let expanded = self.expand_term(ast, *child);
+                    changed |= expanded != *child;
+                    *child = expanded;
+                }
+                changed
+            }
+        };
+        if changed {
+            ast.push(term)
+        } else {
+            id
+        }
+    }
+    fn call(&self, ast: &Ast, id: NodeId) -> Option<(Rc<Macro>, Vec<NodeId>, Span)> {
+        let TermKind::Bracketed {
+            antecedent: Some(name),
+            bracket_kind: BracketKind::Round,
+            elements,
+            ..
+        } = &ast[id].kind
+        else {
+            return None;
+        };
+        let TermKind::Identifier(name) = ast[*name].kind else {
+            return None;
+        };
+        let definition = self.macros.get(&name)?.clone();
+        let call = ast[id].span;
+        span::at(call);
+        let args: Vec<_> = elements
+            .iter()
+            .map(|arg| arg.expect("empty macro argument"))
+            .collect();
+        assert!(
+            args.len() == definition.params.len(),
+            "`{name}` takes {} arguments but {} were given (defined at {})",
+            definition.params.len(),
+            args.len(),
+            source_map::location(definition.span)
+        );
+        Some((definition, args, call))
+    }
+    fn instantiate(
+        &mut self,
+        ast: &mut Ast,
+        definition: &Macro,
+        args: &[NodeId],
+        call: Span,
+    ) -> Vec<Stmt> {
+        if self.depth == 0 {
+            self.outermost = call;
+        }
+        span::at(self.outermost);
+        assert!(
+            self.depth < MAX_DEPTH,
+            "too many nested expansions of `{}` (defined at {})",
+            definition.name,
+            source_map::location(definition.span)
+        );
+        span::at(call);
+        self.expansions += 1;
+        let file = source_map::add_expansion(definition.origin, definition.name.as_str(), call);
+        let shift = source_map::start(file) - definition.origin.start;
+        let args: HashMap<_, _> = definition
+            .params
+            .iter()
+            .copied()
+            .zip(args.iter().copied())
+            .collect();
+        let renamed: HashMap<_, _> = definition
+            .locals
+            .iter()
+            .map(|&name| (name, Symbol::intern(&format!("{name}#{}", self.expansions))))
+            .collect();
+        let mut body = definition.body.clone();
+        for id in body.iter_mut().flat_map(Stmt::terms_mut) {
+            *id = self.copy(ast, *id, &args, &renamed, shift, definition.module);
+        }
+        body
+    }
+    // Copies a term of a macro body. The names it doesn't declare are recorded
+    // with the module of the macro, by the spans of their copies.
+    fn copy(
+        &mut self,
+        ast: &mut Ast,
+        id: NodeId,
+        args: &HashMap<Symbol, NodeId>,
+        renamed: &HashMap<Symbol, Symbol>,
+        shift: usize,
+        module: usize,
+    ) -> NodeId {
+        let Term { mut kind, span } = ast[id].clone();
+        let span = Span::new(span.start + shift, span.end + shift);
+        if let TermKind::Identifier(name) = kind {
+            if let Some(&arg) = args.get(&name) {
+                return arg;
+            }
+            match renamed.get(&name) {
+                Some(&name) => kind = TermKind::Identifier(name),
+                None => {
+                    self.macro_names.insert(span, module);
+                }
+            }
+        }
+        for child in kind.children_mut() {
+            *child = self.copy(ast, *child, args, renamed, shift, module);
+        }
+        ast.push(Term { kind, span })
+    }
+}
+
+fn definition(ast: &Ast, stmt: &Stmt, module: usize) -> Option<Macro> {
+    let Stmt::Block {
+        antecedent: Some(antecedent),
+        stmts,
+    } = stmt
+    else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*antecedent].kind else {
+        return None;
+    };
+    if prefix != "macro" {
+        return None;
+    }
+    let span = ast[*antecedent].span;
+    span::at(span);
+    let TermKind::Bracketed {
+        antecedent: Some(name),
+        bracket_kind: BracketKind::Round,
+        elements,
+        ..
+    } = &ast[*term].kind
+    else {
+        panic!("expected `macro NAME(PARAMS) {{ ... }}`");
+    };
+    let TermKind::Identifier(name) = ast[*name].kind else {
+        panic!("expected `macro NAME(PARAMS) {{ ... }}`");
+    };
+    let params: Vec<_> = elements
+        .iter()
+        .map(|param| match param.map(|param| &ast[param].kind) {
+            Some(&TermKind::Identifier(param)) => param,
+            _ => panic!("invalid parameter of macro `{name}`"),
+        })
+        .collect();
+    let mut locals = HashSet::new();
+    let mut end = span.end;
+    let mut pending: Vec<_> = stmts.iter().flat_map(Stmt::terms).collect();
+    while let Some(id) = pending.pop() {
+        end = end.max(ast[id].span.end);
+        if let TermKind::Declaration {
+            term: Some(declared),
+            ..
+        } = ast[id].kind
+        {
+            if let TermKind::Identifier(local) = ast[declared].kind {
+                if !params.contains(&local) {
+                    locals.insert(local);
+                }
+            }
+        }
+        pending.extend(ast[id].kind.children());
+    }
+    let start =
+        source_map::with_text(|text| text[..span.start].rfind('\n').map_or(0, |index| index + 1));
+    Some(Macro {
+        name,
+        span,
+        module,
+        params,
+        body: stmts.clone(),
+        locals,
+        origin: Span::new(start, end),
+    })
+}
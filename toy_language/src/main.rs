@@ -1,14 +1,222 @@
-mod ast;
-mod parser;
-mod pre_ast;
-
+use std::cell::Cell;
 use std::io::Read;
+use std::panic::AssertUnwindSafe;
+
+use toy_language::ast::Overflow;
+use toy_language::codegen::{self, Emit};
+use toy_language::error::{self, ErrorKind};
+use toy_language::parser::{self, Token};
+use toy_language::span::{self, Span};
+use toy_language::{macros, module, pre_ast, resolver, simplify, source_map};
+
+const USAGE: &str = "\
+usage: toy_language [OPTION...] [FILE...] [-- ARG...]
+Reads the main module from the given files, in order, or from stdin.
+Modules it imports are found relative to the importing file.
+Arguments after `--` are available to the program through `args()`.
+
+options:
+  --emit=STAGE      tokens, ast, ast-json, ast-sexpr, source, typed-ast,
+                    clif, asm, obj or run (the default)
+  -o FILE           output file for --emit=obj
+  --overflow=MODE   checked (the default), wrapping or saturating
+  --gc-stress       collect garbage on every allocation
+
+object files:
+  The object's exported `main` initializes the globals and returns the value
+  of the program's `main`. It imports the extern functions by name and the
+  runtime as `toy_rt_*` functions, which only this driver defines, so linking
+  it needs a library that defines the runtime functions the program calls.
+
+exit codes:
+  2 usage error, 10 lexical error, 11 syntax error, 12 type error,
+  13 runtime error; otherwise the value returned by `main`";
+
+const STAGES: &[&str] = &[
+    "tokens",
+    "ast",
+    "ast-json",
+    "ast-sexpr",
+    "source",
+    "typed-ast",
+    "clif",
+    "asm",
+    "obj",
+    "run",
+];
+
+thread_local! {
+    static STAGE: Cell<Option<ErrorKind>> = const { Cell::new(None) };
+}
 
 fn main() {
-    let mut input = String::new();
-    std::io::stdin().read_to_string(&mut input).unwrap();
-    let stmts = parser::parse(&input);
-    for stmt in &stmts {
-        stmt._debug_print(0);
+    let mut args = std::env::args().skip(1);
+    let mut files = Vec::new();
+    let mut emit = "run".to_owned();
+    let mut output = None;
+    let mut overflow = Overflow::Checked;
+    let mut program_args = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            program_args.extend(args.by_ref());
+        } else if let Some(stage) = arg.strip_prefix("--emit=") {
+            if !STAGES.contains(&stage) {
+                usage_error(&format!("unknown stage `{stage}`"));
+            }
+            emit = stage.to_owned();
+        } else if arg == "-o" {
+            output = Some(
+                args.next()
+                    .unwrap_or_else(|| usage_error("`-o` expects a file name")),
+            );
+        } else if let Some(mode) = arg.strip_prefix("--overflow=") {
+            overflow = match mode {
+                "checked" => Overflow::Checked,
+                "wrapping" => Overflow::Wrapping,
+                "saturating" => Overflow::Saturating,
+                _ => usage_error(&format!("unknown overflow mode `{mode}`")),
+            };
+        } else if arg == "--gc-stress" {
+            toy_language::enable_gc_stress();
+        } else if arg == "--help" {
+            println!("{USAGE}");
+            return;
+        } else if arg.starts_with('-') {
+            usage_error(&format!("unknown option `{arg}`"));
+        } else {
+            files.push(arg);
+        }
+    }
+    let input = read_sources(&files);
+    toy_language::set_args(program_args);
+    std::panic::set_hook(Box::new(|info| {
+        let message = error::message(info.payload());
+        let stage = STAGE.get().map(|stage| error::kind(info.payload(), stage));
+        match (stage, span::current()) {
+            (Some(kind), Some(span)) => eprintln!(
+                "{} error at {}: {message}",
+                kind.name(),
+                source_map::location(span)
+            ),
+            (Some(kind), None) => eprintln!("{} error: {message}", kind.name()),
+            (None, _) => eprintln!("error: {message}"),
+        }
+    }));
+
+    if emit == "tokens" {
+        let lexemes = stage(ErrorKind::Syntax, || parser::tokens(&input));
+        let mut offset = 0;
+        for lexeme in &lexemes {
+            offset += lexeme
+                .trivia
+                .iter()
+                .map(|(_, text)| text.len())
+                .sum::<usize>();
+            if let Some(token) = &lexeme.token {
+                let location = source_map::location(Span::new(offset, offset));
+                match token {
+                    Token::StringLiteral(_) => {
+                        println!("{location} StringLiteral({})", lexeme.text)
+                    }
+                    token => println!("{location} {token:?}"),
+                }
+            }
+            offset += lexeme.text.len();
+        }
+        return;
+    }
+    let mut ast = stage(ErrorKind::Syntax, || parser::parse(&input));
+    match emit.as_str() {
+        "ast" => {
+            for stmt in &ast.stmts {
+                stmt._debug_print(&ast, 0);
+            }
+            return;
+        }
+        "ast-json" => return print!("{}", pre_ast::json(&ast)),
+        "ast-sexpr" => return print!("{}", pre_ast::sexpr(&ast)),
+        "source" => {
+            let source = pre_ast::print(&ast);
+            assert!(
+                parser::parse(&source) == ast,
+                "printed source does not parse back to the same tree"
+            );
+            return print!("{source}");
+        }
+        _ => {}
     }
+    let mut modules = stage(ErrorKind::Syntax, || {
+        let mut modules = module::load(&mut ast);
+        macros::expand(&mut ast, &mut modules);
+        modules
+    });
+    let mut program = stage(ErrorKind::Type, || {
+        resolver::generate(&mut ast, &mut modules);
+        resolver::resolve(&ast, &modules)
+    });
+    for (span, message) in simplify::simplify(&mut program) {
+        eprintln!("warning at {}: {message}", source_map::location(span));
+    }
+    // Generated code has been added to the source map too.
+    let input = source_map::with_text(str::to_owned);
+    let emit = match emit.as_str() {
+        "typed-ast" => return println!("{program:#?}"),
+        "clif" => Emit::Clif,
+        "asm" => Emit::Asm,
+        "obj" => Emit::Object,
+        _ => {
+            let code = stage(ErrorKind::Type, || codegen::run(&program, &input, overflow));
+            std::process::exit(code);
+        }
+    };
+    let bytes = stage(ErrorKind::Type, || {
+        codegen::emit(&program, &input, overflow, emit)
+    });
+    if output.is_none() && matches!(emit, Emit::Clif | Emit::Asm) {
+        return print!("{}", String::from_utf8(bytes).unwrap());
+    }
+    let output = output.unwrap_or_else(|| match files.first() {
+        Some(file) => std::path::Path::new(file)
+            .with_extension("o")
+            .to_string_lossy()
+            .into_owned(),
+        None => "a.o".to_owned(),
+    });
+    std::fs::write(&output, bytes)
+        .unwrap_or_else(|error| fatal(&format!("cannot write `{output}`: {error}")));
+}
+
+// The files make up the main module; the files it imports are added to the
+// source map once it is parsed.
+fn read_sources(files: &[String]) -> String {
+    if files.is_empty() {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input).unwrap();
+        source_map::add("<stdin>", &input);
+        return input;
+    }
+    for file in files {
+        let source = std::fs::read_to_string(file)
+            .unwrap_or_else(|error| fatal(&format!("cannot read `{file}`: {error}")));
+        source_map::add(file, &source);
+    }
+    source_map::with_text(str::to_owned)
+}
+
+fn stage<T>(kind: ErrorKind, f: impl FnOnce() -> T) -> T {
+    STAGE.set(Some(kind));
+    span::clear();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    STAGE.set(None);
+    result.unwrap_or_else(|payload| std::process::exit(error::kind(&*payload, kind).exit_code()))
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{USAGE}");
+    fatal(message);
+}
+
+fn fatal(message: &str) -> ! {
+    eprintln!("error: {message}");
+    std::process::exit(ErrorKind::Usage.exit_code());
 }
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::pre_ast::{Ast, NodeId, Stmt, StringLiteralComponent, TermKind};
+use crate::span::{self, Span};
+use crate::{parser, source_map};
+
+// A file of a program, with its own namespace. Other modules only see the
+// items it marks `pub`, and only if they import it.
+#[derive(Clone)]
+pub struct Module {
+    // Prefixes the names of the module's functions in the compiled code. The
+    // main module has none.
+    pub name: String,
+    pub stmts: Vec<Stmt>,
+    // Whether each statement was marked `pub`.
+    pub public: Vec<bool>,
+    pub imports: Vec<usize>,
+    // The names that the macros expanded into the module use without declaring
+    // them, by span, with the modules the macros are defined in. They are
+    // looked up there, rather than where the macros are used.
+    pub macro_names: HashMap<Span, usize>,
+}
+
+struct Loader {
+    modules: Vec<Module>,
+    loaded: HashMap<PathBuf, usize>,
+    // The files being loaded, each imported by the one before.
+    importing: Vec<(PathBuf, String)>,
+}
+
+// Loads the files that `ast` imports, directly or not, adding them to the
+// source map and their terms to `ast`. Every module comes after the modules it
+// imports; the last one is the main module, made of the statements of `ast`.
+pub fn load(ast: &mut Ast) -> Vec<Module> {
+    let mut loader = Loader {
+        modules: Vec::new(),
+        loaded: HashMap::new(),
+        importing: Vec::new(),
+    };
+    for file in source_map::files() {
+        let path = source_map::path(file);
+        if let Ok(canonical) = std::fs::canonicalize(&path) {
+            loader.importing.push((canonical, path));
+        }
+    }
+    let main = loader.module(ast, String::new(), ast.stmts.clone());
+    loader.modules.push(main);
+    loader.modules
+}
+
+impl Loader {
+    fn module(&mut self, ast: &mut Ast, name: String, stmts: Vec<Stmt>) -> Module {
+        let mut module = Module {
+            name,
+            stmts: Vec::new(),
+            public: Vec::new(),
+            imports: Vec::new(),
+            macro_names: HashMap::new(),
+        };
+        for stmt in stmts {
+            if let Some((path, span)) = import_path(ast, &stmt) {
+                let index = self.import(ast, &path, span);
+                if !module.imports.contains(&index) {
+                    module.imports.push(index);
+                }
+                continue;
+            }
+            let (public, stmt) = visibility(ast, stmt);
+            module.stmts.push(stmt);
+            module.public.push(public);
+        }
+        module
+    }
+
+    // Paths are relative to the directory of the importing file.
+    fn import(&mut self, ast: &mut Ast, path: &str, span: Span) -> usize {
+        let importer = source_map::path(source_map::file(span.start));
+        let path = Path::new(&importer)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path);
+        let display = path.to_string_lossy().into_owned();
+        let canonical = std::fs::canonicalize(&path)
+            .unwrap_or_else(|error| panic!("cannot read `{display}`: {error}"));
+        if let Some(&index) = self.loaded.get(&canonical) {
+            return index;
+        }
+        if let Some(first) = self
+            .importing
+            .iter()
+            .position(|(importing, _)| *importing == canonical)
+        {
+            let cycle: Vec<_> = self.importing[first..]
+                .iter()
+                .map(|(_, path)| path.as_str())
+                .chain([display.as_str()])
+                .collect();
+            panic!("import cycle: {}", cycle.join(" -> "));
+        }
+        let source = std::fs::read_to_string(&path)
+            .unwrap_or_else(|error| panic!("cannot read `{display}`: {error}"));
+        let file = source_map::add(&display, &source);
+        let start = source_map::start(file);
+        let stmts = source_map::with_text(|text| parser::parse_into(ast, text, start));
+        // The name keeps the extension, so that `util.toy` and `util.tpl` differ.
+        self.importing.push((canonical.clone(), display.clone()));
+        let module = self.module(ast, display, stmts);
+        self.importing.pop();
+        self.modules.push(module);
+        self.loaded.insert(canonical, self.modules.len() - 1);
+        self.modules.len() - 1
+    }
+}
+
+impl Module {
+    // Puts the statements of generated code in place of the one at `position`.
+    pub fn splice(&mut self, ast: &mut Ast, position: usize, stmts: Vec<Stmt>) {
+        let (public, stmts): (Vec<_>, Vec<_>) = stmts
+            .into_iter()
+            .map(|stmt| {
+                assert!(
+                    import_path(ast, &stmt).is_none(),
+                    "generated code cannot import modules"
+                );
+                visibility(ast, stmt)
+            })
+            .unzip();
+        self.stmts.splice(position..=position, stmts);
+        self.public.splice(position..=position, public);
+    }
+}
+
+fn import_path(ast: &Ast, stmt: &Stmt) -> Option<(String, Span)> {
+    let Stmt::Term(Some(term)) = stmt else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*term].kind else {
+        return None;
+    };
+    if prefix != "import" {
+        return None;
+    }
+    let term = &ast[*term];
+    span::at(term.span);
+    match &term.kind {
+        TermKind::StringLiteral(components) => match &components[..] {
+            [StringLiteralComponent::String(path)] => Some((path.clone(), term.span)),
+            _ => panic!("expected `import \"path\"`"),
+        },
+        _ => panic!("expected `import \"path\"`"),
+    }
+}
+
+// The term marked `pub` by `id`, if it is a `pub` prefix.
+pub fn public(ast: &Ast, id: NodeId) -> Option<NodeId> {
+    match &ast[id].kind {
+        TermKind::Prefixed { prefix, term } if prefix == "pub" => Some(*term),
+        _ => None,
+    }
+}
+
+// Takes the `pub` off a definition.
+pub fn visibility(ast: &mut Ast, stmt: Stmt) -> (bool, Stmt) {
+    match stmt {
+        Stmt::Block {
+            antecedent: Some(antecedent),
+            stmts,
+        } => match public(ast, antecedent) {
+            Some(term) => (
+                true,
+                Stmt::Block {
+                    antecedent: Some(term),
+                    stmts,
+                },
+            ),
+            None => (
+                public_assignment(ast, antecedent),
+                Stmt::Block {
+                    antecedent: Some(antecedent),
+                    stmts,
+                },
+            ),
+        },
+        Stmt::Term(Some(id)) => match public(ast, id) {
+            Some(term) => (true, Stmt::Term(Some(term))),
+            None => (public_assignment(ast, id), Stmt::Term(Some(id))),
+        },
+        stmt => (false, stmt),
+    }
+}
+
+// Takes the `pub` off the left-hand side of an assignment, in place.
+fn public_assignment(ast: &mut Ast, id: NodeId) -> bool {
+    let TermKind::Assignment {
+        left_hand_side: Some(left_hand_side),
+        ..
+    } = ast[id].kind
+    else {
+        return false;
+    };
+    let Some(term) = public(ast, left_hand_side) else {
+        return false;
+    };
+    if let TermKind::Assignment { left_hand_side, .. } = &mut ast[id].kind {
+        *left_hand_side = Some(term);
+    }
+    true
+}
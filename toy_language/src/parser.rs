@@ -1,124 +1,305 @@
 mod chars_peekable;
+mod incremental;
 mod operators;
 mod token;
 
-use crate::pre_ast::{Stmt, Term};
+use crate::pre_ast::{
+    Associativity, Ast, BracketKind, Fixity, NodeId, OperatorDecl, Stmt, Term, TermKind,
+};
+use crate::span::Span;
+use crate::symbol::Symbol;
 use chars_peekable::CharsPeekable;
-use enum_iterator::Sequence;
-use token::Token;
+pub use incremental::Incremental;
+pub use token::{is_symbol, Lexeme, Token, Trivia};
 
-pub fn parse(input: &str) -> Vec<Stmt> {
+pub fn parse(input: &str) -> Ast {
+    let mut ast = Ast::default();
+    ast.stmts = parse_into(&mut ast, input, 0);
+    ast
+}
+
+// Parses `input` from `start` on, adding its terms to an existing arena. Spans
+// count from the beginning of `input`.
+pub fn parse_into(ast: &mut Ast, input: &str, start: usize) -> Vec<Stmt> {
+    let mut chars = CharsPeekable::starting_at(input, start);
+    parse_stmts(&mut chars, ast)
+}
+
+// Lexes the whole input. Operators are declared as the statements that
+// declare them are parsed, so the input has to parse too.
+pub fn tokens(input: &str) -> Vec<Lexeme<'_>> {
     let mut chars = CharsPeekable::new(input);
-    let mut peeked = token::next(&mut chars);
-    let ret = std::iter::from_fn(|| parse_stmt(&mut chars, &mut peeked)).collect();
+    chars.keep_lexemes();
+    // Interpolated expressions get parsed too, but nothing looks at them.
+    parse_stmts(&mut chars, &mut Ast::default());
+    let mut lexemes = chars.take_lexemes().unwrap();
+    let end = lexemes.iter().position(|lexeme| lexeme.token.is_none());
+    lexemes.truncate(end.unwrap() + 1);
+    lexemes
+}
+
+fn parse_stmts(chars: &mut CharsPeekable, ast: &mut Ast) -> Vec<Stmt> {
+    let mut operators = operators::Table::new();
+    let mut stmts = Vec::new();
+    let mut peeked = token::next(chars, &mut operators, ast);
+    while let Some(stmt) = parse_stmt(chars, &mut peeked, &mut operators, ast) {
+        stmts.push(stmt);
+    }
     assert!(peeked.is_none());
-    ret
+    stmts
+}
+
+fn fixity(name: &str) -> Option<Fixity> {
+    match name {
+        "prefix" => Some(Fixity::Prefix),
+        "postfix" => Some(Fixity::Postfix),
+        "infixl" => Some(Fixity::Infix(Associativity::Left)),
+        "infixr" => Some(Fixity::Infix(Associativity::Right)),
+        "infix" => Some(Fixity::Infix(Associativity::None)),
+        _ => None,
+    }
 }
 
-fn parse_stmt(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option<Stmt> {
-    let term = parse_term(chars, peeked);
+fn parse_stmt<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Option<Stmt> {
+    if let Some(Token::Identifier(keyword)) = peeked {
+        if *keyword == "operator" {
+            return Some(parse_operator_decl(chars, peeked, operators, ast));
+        }
+    }
+    let term = parse_term(chars, peeked, operators, ast);
     match peeked {
         Some(Token::Semicolon) => {
-            *peeked = token::next(chars);
+            *peeked = token::next(chars, operators, ast);
             Some(Stmt::Term(term))
         }
-        Some(Token::OpeningBrace) => {
-            *peeked = token::next(chars);
-            let mut stmts = Vec::new();
-            loop {
-                if let Some(Token::ClosingBrace) = peeked {
-                    *peeked = token::next(chars);
-                    break;
-                } else if let Some(stmt) = parse_stmt(chars, peeked) {
-                    stmts.push(stmt);
-                } else {
-                    panic!();
-                }
-            }
-            Some(Stmt::Block {
-                antecedent: term,
-                stmts,
-            })
-        }
-        Some(_) => panic!(),
+        Some(Token::OpeningBrace) => Some(Stmt::Block {
+            antecedent: term,
+            stmts: parse_block(chars, peeked, operators, ast),
+        }),
+        Some(_) => panic!("expected `;` or `{{`"),
         None => None,
     }
 }
-fn parse_term(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option<Term> {
-    parse_assign(chars, peeked)
+fn parse_operator_decl<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Stmt {
+    *peeked = token::next(chars, operators, ast);
+    let fixity = match peeked {
+        Some(Token::Identifier(name)) => {
+            fixity(name).unwrap_or_else(|| panic!("unknown fixity `{name}`"))
+        }
+        _ => panic!("expected a fixity"),
+    };
+    *peeked = token::next(chars, operators, ast);
+    let Some(Token::Integer(precedence)) = *peeked else {
+        panic!("expected a precedence");
+    };
+    let precedence = u32::try_from(precedence)
+        .ok()
+        .filter(|&precedence| precedence < 1000)
+        .expect("precedence out of range");
+    *peeked = token::next_declared(chars, operators, ast);
+    let Some(Token::Symbol(symbol)) = peeked.take() else {
+        panic!("expected an operator symbol");
+    };
+    *peeked = token::next(chars, operators, ast);
+    let Some(Token::Identifier(function)) = peeked.take() else {
+        panic!("expected the name of a function");
+    };
+    *peeked = token::next(chars, operators, ast);
+    assert!(matches!(peeked, Some(Token::Semicolon)), "expected `;`");
+    let decl = OperatorDecl {
+        symbol: symbol.to_owned(),
+        fixity,
+        precedence,
+        function: function.to_owned(),
+    };
+    operators.declare(&decl);
+    *peeked = token::next(chars, operators, ast);
+    Stmt::Operator(decl)
+}
+fn parse_block<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Vec<Stmt> {
+    *peeked = token::next(chars, operators, ast);
+    let mut stmts = Vec::new();
+    loop {
+        if let Some(Token::ClosingBrace) = peeked {
+            *peeked = token::next(chars, operators, ast);
+            return stmts;
+        } else if let Some(stmt) = parse_stmt(chars, peeked, operators, ast) {
+            stmts.push(stmt);
+        } else {
+            panic!("expected `}}`");
+        }
+    }
+}
+fn parse_term<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Option<NodeId> {
+    parse_assign(chars, peeked, operators, ast)
 }
-fn parse_assign(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option<Term> {
-    let left_hand_side = parse_binary_operation(chars, peeked);
+fn parse_assign<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Option<NodeId> {
+    let start = chars.token_start();
+    let left_hand_side = parse_binary_operation(chars, peeked, operators, ast);
     if let Some(operator) = peeked.as_ref().and_then(operators::assignment) {
-        *peeked = token::next(chars);
-        let right_hand_side = parse_assign(chars, peeked);
-        Some(Term::Assignment {
-            left_hand_side: left_hand_side.map(Box::new),
-            operator,
-            right_hand_side: right_hand_side.map(Box::new),
-        })
+        *peeked = token::next(chars, operators, ast);
+        let right_hand_side = parse_assign(chars, peeked, operators, ast);
+        Some(term(
+            ast,
+            chars,
+            start,
+            TermKind::Assignment {
+                left_hand_side,
+                operator,
+                right_hand_side,
+            },
+        ))
     } else {
         left_hand_side
     }
 }
-fn parse_binary_operation(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option<Term> {
-    parse_binary_operation_rec(chars, peeked, operators::Precedence::first())
+fn parse_binary_operation<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Option<NodeId> {
+    parse_operation(chars, peeked, operators, ast, 0)
 }
-fn parse_binary_operation_rec(
-    chars: &mut CharsPeekable,
-    peeked: &mut Option<Token>,
-    precedence: Option<operators::Precedence>,
-) -> Option<Term> {
-    let Some(precedence) = precedence else {
-            return parse_factor(chars, peeked);
+fn parse_operation<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+    min_power: u32,
+) -> Option<NodeId> {
+    let start = chars.token_start();
+    let prefix = peeked.as_ref().and_then(|token| operators.prefix(token));
+    let mut left_operand = if let Some(entry) = prefix.cloned() {
+        *peeked = token::next(chars, operators, ast);
+        let operand = parse_operation(chars, peeked, operators, ast, entry.power());
+        Some(term(ast, chars, start, entry.apply(None, operand)))
+    } else {
+        parse_factor(chars, peeked, operators, ast)
+    };
+    while let Some(token) = peeked.as_ref() {
+        if let Some(entry) = operators.postfix(token).cloned() {
+            if entry.power() < min_power {
+                break;
+            }
+            *peeked = token::next(chars, operators, ast);
+            left_operand = Some(term(ast, chars, start, entry.apply(left_operand, None)));
+            continue;
+        }
+        let Some(entry) = operators.infix(token).cloned() else {
+            break;
         };
-    let mut left_operand = parse_binary_operation_rec(chars, peeked, precedence.next());
-    while let Some(operator) = peeked
-        .as_ref()
-        .and_then(|token| operators::infix(token, precedence))
-    {
-        *peeked = token::next(chars);
-        let right_operand = parse_binary_operation_rec(chars, peeked, precedence.next());
-        left_operand = Some(Term::BinaryOperation {
-            left_operand: left_operand.map(Box::new),
-            operator,
-            right_operand: right_operand.map(Box::new),
-        });
+        let (left_power, right_power) = entry.infix_powers();
+        if left_power < min_power {
+            break;
+        }
+        *peeked = token::next(chars, operators, ast);
+        let right_operand = parse_operation(chars, peeked, operators, ast, right_power);
+        left_operand = Some(term(
+            ast,
+            chars,
+            start,
+            entry.apply(left_operand, right_operand),
+        ));
+        if entry.is_non_associative() {
+            let next = peeked.as_ref().and_then(|token| operators.infix(token));
+            assert!(
+                next.is_none_or(|next| next.power() != entry.power()),
+                "chained non-associative operator"
+            );
+        }
     }
     left_operand
 }
-fn parse_factor(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option<Term> {
+fn parse_factor<'s>(
+    chars: &mut CharsPeekable<'s>,
+    peeked: &mut Option<Token<'s>>,
+    operators: &mut operators::Table,
+    ast: &mut Ast,
+) -> Option<NodeId> {
     let Some(first_token) = peeked else {
-            return None;
-        };
+        return None;
+    };
+    let start = chars.token_start();
     let mut antecedent = match first_token {
         Token::Identifier(name) => {
-            let ret = Term::Identifier(name.clone());
-            *peeked = token::next(chars);
-            Some(ret)
+            let name = Symbol::intern(name);
+            *peeked = token::next(chars, operators, ast);
+            if let Some(Token::Identifier(_) | Token::StringLiteral(_)) = peeked {
+                let rest = parse_factor(chars, peeked, operators, ast).unwrap();
+                let kind = TermKind::Prefixed {
+                    prefix: name,
+                    term: rest,
+                };
+                return Some(term(ast, chars, start, kind));
+            }
+            Some(term(ast, chars, start, TermKind::Identifier(name)))
         }
         Token::Integer(value) => {
-            let ret = Term::Integer(*value);
-            *peeked = token::next(chars);
-            Some(ret)
+            let kind = TermKind::Integer(*value);
+            *peeked = token::next(chars, operators, ast);
+            Some(term(ast, chars, start, kind))
+        }
+        Token::Float(value) => {
+            let kind = TermKind::Float(*value);
+            *peeked = token::next(chars, operators, ast);
+            Some(term(ast, chars, start, kind))
         }
         Token::StringLiteral(components) => {
-            let ret = Term::StringLiteral(components.clone());
-            *peeked = token::next(chars);
-            Some(ret)
+            let kind = TermKind::StringLiteral(std::mem::take(components));
+            *peeked = token::next(chars, operators, ast);
+            Some(term(ast, chars, start, kind))
         }
         _ => None,
     };
     loop {
         match *peeked {
             Some(Token::OpeningBracket(bracket_kind)) => {
-                *peeked = token::next(chars);
+                *peeked = token::next(chars, operators, ast);
                 let mut elements = Vec::new();
                 let has_trailing_comma;
                 loop {
-                    let element = parse_assign(chars, peeked);
+                    let element_start = chars.token_start();
+                    let mut element = parse_assign(chars, peeked, operators, ast);
+                    if let Some(Token::OpeningBrace) = peeked {
+                        let stmts = parse_block(chars, peeked, operators, ast);
+                        element = Some(term(
+                            ast,
+                            chars,
+                            element_start,
+                            TermKind::Block {
+                                antecedent: element,
+                                stmts,
+                            },
+                        ));
+                    }
                     if let Some(Token::Comma) = peeked {
-                        *peeked = token::next(chars);
+                        *peeked = token::next(chars, operators, ast);
                         elements.push(element);
                     } else {
                         if let Some(element) = element {
@@ -130,26 +311,63 @@ fn parse_factor(chars: &mut CharsPeekable, peeked: &mut Option<Token>) -> Option
                         break;
                     }
                 }
-                assert!(matches!(*peeked,
-                    Some(Token::ClosingBracket(closing_bracket_kind)) if closing_bracket_kind == bracket_kind
+                let closing = match bracket_kind {
+                    BracketKind::Round => ')',
+                    BracketKind::Square => ']',
+                };
+                assert!(
+                    matches!(*peeked,
+                        Some(Token::ClosingBracket(closing_bracket_kind)) if closing_bracket_kind == bracket_kind
+                    ),
+                    "expected `,` or `{closing}`"
+                );
+                *peeked = token::next(chars, operators, ast);
+                antecedent = Some(term(
+                    ast,
+                    chars,
+                    start,
+                    TermKind::Bracketed {
+                        antecedent,
+                        bracket_kind,
+                        elements,
+                        has_trailing_comma,
+                    },
                 ));
-                *peeked = token::next(chars);
-                antecedent = Some(Term::Bracketed {
-                    antecedent: antecedent.map(Box::new),
-                    bracket_kind,
-                    elements,
-                    has_trailing_comma,
-                });
             }
             Some(Token::Colon) => {
-                *peeked = token::next(chars);
-                let ty = parse_factor(chars, peeked);
-                antecedent = Some(Term::Declaration {
-                    term: antecedent.map(Box::new),
-                    ty: ty.map(Box::new),
-                })
+                *peeked = token::next(chars, operators, ast);
+                let ty = parse_factor(chars, peeked, operators, ast);
+                antecedent = Some(term(
+                    ast,
+                    chars,
+                    start,
+                    TermKind::Declaration {
+                        term: antecedent,
+                        ty,
+                    },
+                ))
+            }
+            Some(Token::Dot) => {
+                *peeked = token::next(chars, operators, ast);
+                let Some(Token::Identifier(member)) = peeked else {
+                    panic!("expected a name after `.`");
+                };
+                let member = Symbol::intern(member);
+                *peeked = token::next(chars, operators, ast);
+                antecedent = Some(term(
+                    ast,
+                    chars,
+                    start,
+                    TermKind::MemberAccess { antecedent, member },
+                ))
             }
             _ => return antecedent,
         }
     }
 }
+fn term(ast: &mut Ast, chars: &CharsPeekable, start: usize, kind: TermKind) -> NodeId {
+    ast.push(Term {
+        kind,
+        span: Span::new(start, chars.prev_token_end()),
+    })
+}
@@ -1,22 +1,42 @@
 use std::str::CharIndices;
 
+use super::Lexeme;
+
 pub struct CharsPeekable<'s> {
     input: &'s str,
     chars: CharIndices<'s>,
+    base: usize,
     next_char_index: Option<(usize, char)>,
+    token_start: usize,
+    prev_token_end: usize,
+    // The lexemes read so far, if they are being kept.
+    lexemes: Option<Vec<Lexeme<'s>>>,
 }
 impl<'s> CharsPeekable<'s> {
-    pub fn new(input: &str) -> CharsPeekable {
-        let mut chars = input.char_indices();
-        let next_char_index = chars.next();
+    pub fn new(input: &str) -> CharsPeekable<'_> {
+        CharsPeekable::starting_at(input, 0)
+    }
+    pub fn starting_at(input: &str, offset: usize) -> CharsPeekable<'_> {
+        let mut chars = input[offset..].char_indices();
+        let next_char_index = chars.next().map(|(index, ch)| (offset + index, ch));
         CharsPeekable {
             input,
             chars,
+            base: offset,
             next_char_index,
+            token_start: offset,
+            prev_token_end: offset,
+            lexemes: None,
         }
     }
-    pub unsafe fn get_substring_unchecked(&self, from: usize, to: usize) -> &str {
-        self.input.get_unchecked(from..to)
+    pub fn slice(&self, from: usize, to: usize) -> &'s str {
+        &self.input[from..to]
+    }
+    pub fn rest(&self) -> &str {
+        match self.next_char_index {
+            Some((index, _)) => &self.input[index..],
+            None => "",
+        }
     }
     pub fn next(&mut self) -> Option<char> {
         self.next_if(|_| true)
@@ -24,7 +44,7 @@ impl<'s> CharsPeekable<'s> {
     pub fn next_if(&mut self, pred: impl FnOnce(char) -> bool) -> Option<char> {
         match self.next_char_index {
             Some((_, ch)) if pred(ch) => {
-                self.next_char_index = self.chars.next();
+                self.next_char_index = self.chars.next().map(|(index, ch)| (self.base + index, ch));
                 Some(ch)
             }
             _ => None,
@@ -45,4 +65,27 @@ impl<'s> CharsPeekable<'s> {
             None => self.input.len(),
         }
     }
+    pub fn set_token_bounds(&mut self, token_start: usize, prev_token_end: usize) {
+        self.token_start = token_start;
+        self.prev_token_end = prev_token_end;
+    }
+    pub fn token_start(&self) -> usize {
+        self.token_start
+    }
+    pub fn prev_token_end(&self) -> usize {
+        self.prev_token_end
+    }
+    pub fn keep_lexemes(&mut self) {
+        self.lexemes = Some(Vec::new());
+    }
+    pub fn lexemes(&mut self) -> Option<&mut Vec<Lexeme<'s>>> {
+        self.lexemes.as_mut()
+    }
+    // Stops keeping lexemes until they are given back.
+    pub fn take_lexemes(&mut self) -> Option<Vec<Lexeme<'s>>> {
+        self.lexemes.take()
+    }
+    pub fn restore_lexemes(&mut self, lexemes: Option<Vec<Lexeme<'s>>>) {
+        self.lexemes = lexemes;
+    }
 }
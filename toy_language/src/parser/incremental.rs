@@ -0,0 +1,179 @@
+use super::chars_peekable::CharsPeekable;
+use super::operators::Table;
+use super::{parse_stmt, token};
+use crate::pre_ast::{Ast, OperatorDecl, Stmt};
+use crate::span::Span;
+
+// Keeps the source of a file together with its parsed statements, so that an
+// edit only has to relex and reparse the top-level statements it touches.
+pub struct Incremental {
+    text: String,
+    parsed: Option<Parsed>,
+}
+
+#[derive(Default)]
+struct Parsed {
+    // Edits leave the terms of replaced statements behind in the arena, so it
+    // is compacted whenever it has doubled in size.
+    ast: Ast,
+    compacted: usize,
+    extents: Vec<Span>,
+    // The number of operators declared by the end of each statement.
+    declared: Vec<usize>,
+    operators: Vec<OperatorDecl>,
+}
+
+impl Incremental {
+    pub fn new(text: String) -> Incremental {
+        Incremental { text, parsed: None }
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn ast(&mut self) -> &Ast {
+        if self.parsed.is_none() {
+            let mut parsed = Parsed::default();
+            parse_from(
+                &self.text,
+                0,
+                Table::new(),
+                &mut parsed,
+                Old::default(),
+                0,
+                0,
+            );
+            parsed.compacted = parsed.ast.len();
+            self.parsed = Some(parsed);
+        }
+        &self.parsed.as_ref().unwrap().ast
+    }
+    pub fn edit(&mut self, span: Span, replacement: &str) {
+        self.text.replace_range(span.start..span.end, replacement);
+        let Some(mut parsed) = self.parsed.take() else {
+            return;
+        };
+        let delta = replacement.len() as isize - (span.end - span.start) as isize;
+        let kept = parsed
+            .extents
+            .iter()
+            .take_while(|extent| extent.end <= span.start)
+            .count();
+        let (restart, declared) = match kept.checked_sub(1) {
+            Some(last) => (parsed.extents[last].end, parsed.declared[last]),
+            None => (0, 0),
+        };
+        let mut operators = Table::new();
+        for decl in &parsed.operators[..declared] {
+            operators.declare(decl);
+        }
+        let old = Old {
+            stmts: parsed.ast.stmts.split_off(kept),
+            extents: parsed.extents.split_off(kept),
+            declared: parsed.declared.split_off(kept),
+            operators: std::mem::take(&mut parsed.operators),
+        };
+        parse_from(
+            &self.text,
+            restart,
+            operators,
+            &mut parsed,
+            old,
+            span.end,
+            delta,
+        );
+        if parsed.ast.len() > 2 * parsed.compacted {
+            parsed.ast.compact();
+            parsed.compacted = parsed.ast.len();
+        }
+        self.parsed = Some(parsed);
+    }
+}
+
+// The statements after the first one an edit touches.
+#[derive(Default)]
+struct Old {
+    stmts: Vec<Stmt>,
+    extents: Vec<Span>,
+    declared: Vec<usize>,
+    operators: Vec<OperatorDecl>,
+}
+
+// Parses the statements of `text` from `start`, appending them to `parsed`.
+// Once a statement starts where one of the `old` statements after the edit
+// used to start, shifted by `delta`, the rest of the old statements are
+// reused, unless the operators declared before it have changed, which can
+// change how everything after them is lexed and parsed.
+fn parse_from(
+    text: &str,
+    start: usize,
+    mut operators: Table,
+    parsed: &mut Parsed,
+    old: Old,
+    edit_end: usize,
+    delta: isize,
+) {
+    // The number of operators declared before each old statement.
+    let mut declared_before = vec![operators.declared().len()];
+    declared_before.extend(&old.declared);
+    let mut reusable = old
+        .stmts
+        .into_iter()
+        .zip(old.extents)
+        .zip(declared_before)
+        .zip(old.declared)
+        .map(|(((stmt, extent), before), count)| (stmt, extent, before, count))
+        .peekable();
+    let mut chars = CharsPeekable::starting_at(text, start);
+    let mut peeked = token::next(&mut chars, &mut operators, &mut parsed.ast);
+    while peeked.is_some() {
+        let stmt_start = chars.token_start();
+        while reusable
+            .next_if(|(_, extent, ..)| {
+                extent.start < edit_end || shift(extent.start, delta) < stmt_start
+            })
+            .is_some()
+        {}
+        if reusable.peek().is_some_and(|(_, extent, before, _)| {
+            shift(extent.start, delta) == stmt_start
+                && operators.declared() == &old.operators[..*before]
+        }) {
+            for (stmt, extent, _, count) in reusable {
+                shift_stmt(&mut parsed.ast, &stmt, delta);
+                parsed.ast.stmts.push(stmt);
+                parsed.extents.push(shift_span(extent, delta));
+                parsed.declared.push(count);
+            }
+            parsed.operators = old.operators;
+            return;
+        }
+        let Some(stmt) = parse_stmt(&mut chars, &mut peeked, &mut operators, &mut parsed.ast)
+        else {
+            break;
+        };
+        parsed.ast.stmts.push(stmt);
+        parsed
+            .extents
+            .push(Span::new(stmt_start, chars.prev_token_end()));
+        parsed.declared.push(operators.declared().len());
+    }
+    assert!(peeked.is_none());
+    parsed.operators = operators.declared().to_vec();
+}
+
+fn shift(offset: usize, delta: isize) -> usize {
+    offset.checked_add_signed(delta).unwrap()
+}
+
+fn shift_span(span: Span, delta: isize) -> Span {
+    Span::new(shift(span.start, delta), shift(span.end, delta))
+}
+
+// Shifts the spans of every term of `stmt`.
+fn shift_stmt(ast: &mut Ast, stmt: &Stmt, delta: isize) {
+    let mut pending = stmt.terms();
+    while let Some(id) = pending.pop() {
+        let term = &mut ast[id];
+        term.span = shift_span(term.span, delta);
+        pending.extend(term.kind.children());
+    }
+}
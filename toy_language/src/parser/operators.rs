@@ -1,33 +1,133 @@
+use std::collections::HashMap;
+
 use super::Token;
-use crate::pre_ast::Operator;
-use enum_iterator::Sequence;
+use crate::pre_ast::{Associativity, Fixity, NodeId, Operator, OperatorDecl, TermKind};
+use crate::symbol::Symbol;
 
-#[derive(Clone, Copy, Sequence)]
-pub enum Precedence {
-    Equality,
-    Inequality,
-    AddSub,
-    MulDivRem,
+#[derive(Clone)]
+pub struct Entry {
+    symbol: Symbol,
+    fixity: Fixity,
+    precedence: u32,
+    builtin: Option<Operator>,
 }
-pub fn infix(token: &Token, precedence: Precedence) -> Option<Operator> {
-    match (token, precedence) {
-        (Token::Plus, Precedence::AddSub) => Some(Operator::Add),
-        (Token::Hyphen, Precedence::AddSub) => Some(Operator::Sub),
-        (Token::Asterisk, Precedence::MulDivRem) => Some(Operator::Mul),
-        (Token::Slash, Precedence::MulDivRem) => Some(Operator::Div),
-        (Token::Percent, Precedence::MulDivRem) => Some(Operator::Rem),
-        (Token::DoubleEqual, Precedence::Equality) => Some(Operator::Equal),
-        (Token::ExclamationEqual, Precedence::Equality) => Some(Operator::NotEqual),
-        (Token::Greater, Precedence::Inequality) => Some(Operator::Greater),
-        (Token::GreaterEqual, Precedence::Inequality) => Some(Operator::GreaterEqual),
-        (Token::Less, Precedence::Inequality) => Some(Operator::Less),
-        (Token::LessEqual, Precedence::Inequality) => Some(Operator::LessEqual),
-        _ => None,
+pub struct Table {
+    unary: HashMap<String, Entry>,
+    infix: HashMap<String, Entry>,
+    declared: Vec<OperatorDecl>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        let mut table = Table {
+            unary: HashMap::new(),
+            infix: HashMap::new(),
+            declared: Vec::new(),
+        };
+        let builtins = [
+            ("==", 10, Operator::Equal),
+            ("!=", 10, Operator::NotEqual),
+            (">", 20, Operator::Greater),
+            (">=", 20, Operator::GreaterEqual),
+            ("<", 20, Operator::Less),
+            ("<=", 20, Operator::LessEqual),
+            ("+", 30, Operator::Add),
+            ("-", 30, Operator::Sub),
+            ("*", 40, Operator::Mul),
+            ("/", 40, Operator::Div),
+            ("%", 40, Operator::Rem),
+        ];
+        for (symbol, precedence, operator) in builtins {
+            table.insert(
+                symbol,
+                Fixity::Infix(Associativity::Left),
+                precedence,
+                Some(operator),
+            );
+        }
+        table.insert("-", Fixity::Prefix, 50, Some(Operator::Sub));
+        table
+    }
+    pub fn declare(&mut self, decl: &OperatorDecl) {
+        assert!(decl.symbol != "=", "cannot redefine `=`");
+        self.insert(&decl.symbol, decl.fixity, decl.precedence, None);
+        self.declared.push(decl.clone());
+    }
+    pub fn declared(&self) -> &[OperatorDecl] {
+        &self.declared
+    }
+    fn insert(&mut self, symbol: &str, fixity: Fixity, precedence: u32, builtin: Option<Operator>) {
+        let entries = match fixity {
+            Fixity::Prefix | Fixity::Postfix => &mut self.unary,
+            Fixity::Infix(_) => &mut self.infix,
+        };
+        let entry = Entry {
+            symbol: Symbol::intern(symbol),
+            fixity,
+            precedence,
+            builtin,
+        };
+        let prev = entries.insert(symbol.to_owned(), entry);
+        assert!(prev.is_none(), "duplicate operator `{symbol}`");
+    }
+    pub fn is_operator(&self, symbol: &str) -> bool {
+        symbol == "=" || self.unary.contains_key(symbol) || self.infix.contains_key(symbol)
+    }
+    pub fn prefix(&self, token: &Token) -> Option<&Entry> {
+        self.unary(token)
+            .filter(|entry| entry.fixity == Fixity::Prefix)
+    }
+    pub fn postfix(&self, token: &Token) -> Option<&Entry> {
+        self.unary(token)
+            .filter(|entry| entry.fixity == Fixity::Postfix)
+    }
+    fn unary(&self, token: &Token) -> Option<&Entry> {
+        let Token::Symbol(symbol) = token else {
+            return None;
+        };
+        self.unary.get(*symbol)
+    }
+    pub fn infix(&self, token: &Token) -> Option<&Entry> {
+        let Token::Symbol(symbol) = token else {
+            return None;
+        };
+        self.infix.get(*symbol)
     }
 }
+
+impl Entry {
+    pub fn power(&self) -> u32 {
+        self.precedence * 2
+    }
+    pub fn infix_powers(&self) -> (u32, u32) {
+        match self.fixity {
+            Fixity::Infix(Associativity::Right) => (self.power() + 1, self.power()),
+            _ => (self.power(), self.power() + 1),
+        }
+    }
+    pub fn is_non_associative(&self) -> bool {
+        self.fixity == Fixity::Infix(Associativity::None)
+    }
+    pub fn apply(&self, left_operand: Option<NodeId>, right_operand: Option<NodeId>) -> TermKind {
+        match self.builtin {
+            Some(operator) => TermKind::BinaryOperation {
+                left_operand,
+                operator,
+                right_operand,
+            },
+            None => TermKind::CustomOperation {
+                left_operand,
+                operator: self.symbol,
+                fixity: self.fixity,
+                right_operand,
+            },
+        }
+    }
+}
+
 pub fn assignment(token: &Token) -> Option<Operator> {
     match token {
-        Token::Equal => Some(Operator::Assign),
+        Token::Symbol(symbol) if *symbol == "=" => Some(Operator::Assign),
         _ => None,
     }
 }
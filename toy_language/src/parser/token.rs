@@ -1,61 +1,167 @@
+use super::operators::Table;
 use super::parse_term;
 use super::CharsPeekable;
-use crate::pre_ast::{BracketKind, StringLiteralComponent};
+use crate::error::lexical_error;
+use crate::pre_ast::{Ast, BracketKind, StringLiteralComponent};
+use crate::span::{self, Span};
 
-#[derive(Debug)]
-pub enum Token {
-    Identifier(String),
+#[derive(Clone, Debug)]
+pub enum Token<'s> {
+    Identifier(&'s str),
     Integer(i64),
+    Float(f64),
     StringLiteral(Vec<StringLiteralComponent>),
-    Plus,
-    Hyphen,
-    Asterisk,
-    Slash,
-    Percent,
-    Greater,
-    GreaterEqual,
-    Less,
-    LessEqual,
-    Equal,
-    DoubleEqual,
-    Exclamation,
-    ExclamationEqual,
+    Symbol(&'s str),
     Comma,
     Semicolon,
     Colon,
+    Dot,
     OpeningBracket(BracketKind),
     ClosingBracket(BracketKind),
     OpeningBrace,
     ClosingBrace,
 }
 
-pub fn next(chars: &mut CharsPeekable) -> Option<Token> {
-    chars.consume_while(|ch| ch.is_ascii_whitespace());
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Trivia {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+pub struct Lexeme<'s> {
+    pub trivia: Vec<(Trivia, &'s str)>,
+    pub token: Option<Token<'s>>,
+    pub text: &'s str,
+}
+
+pub fn next<'s>(
+    chars: &mut CharsPeekable<'s>,
+    operators: &mut Table,
+    ast: &mut Ast,
+) -> Option<Token<'s>> {
+    next_token(chars, operators, ast, false)
+}
+
+// Like `next`, but takes all of a run of symbol characters as one symbol,
+// for the operator a declaration introduces.
+pub fn next_declared<'s>(
+    chars: &mut CharsPeekable<'s>,
+    operators: &mut Table,
+    ast: &mut Ast,
+) -> Option<Token<'s>> {
+    next_token(chars, operators, ast, true)
+}
+
+fn next_token<'s>(
+    chars: &mut CharsPeekable<'s>,
+    operators: &mut Table,
+    ast: &mut Ast,
+    whole_symbol: bool,
+) -> Option<Token<'s>> {
+    let prev_token_end = chars.offset();
+    let keep = chars.lexemes().is_some();
+    let mut trivia = Vec::new();
+    loop {
+        let start = chars.offset();
+        let Some(kind) = skip_trivia(chars) else {
+            break;
+        };
+        if keep {
+            let end = chars.offset();
+            trivia.push((kind, chars.slice(start, end)));
+        }
+    }
+    let token_start = chars.offset();
+    span::at(Span::new(token_start, token_start));
+    let token = lex(chars, operators, ast, whole_symbol);
+    let end = chars.offset();
+    span::at(Span::new(token_start, end));
+    chars.set_token_bounds(token_start, prev_token_end);
+    let text = chars.slice(token_start, end);
+    if let Some(lexemes) = chars.lexemes() {
+        lexemes.push(Lexeme {
+            trivia,
+            token: token.clone(),
+            text,
+        });
+    }
+    token
+}
+
+fn skip_trivia(chars: &mut CharsPeekable) -> Option<Trivia> {
+    if chars.consume_if(|ch| ch.is_ascii_whitespace()) {
+        chars.consume_while(|ch| ch.is_ascii_whitespace());
+        Some(Trivia::Whitespace)
+    } else if chars.rest().starts_with("/*") {
+        let start = chars.offset();
+        span::at(Span::new(start, start));
+        chars.next();
+        chars.next();
+        let mut comment_depth = 1;
+        while comment_depth > 0 {
+            match chars
+                .next()
+                .unwrap_or_else(|| lexical_error("unterminated comment"))
+            {
+                '*' if chars.consume_if_eq('/') => comment_depth -= 1,
+                '/' if chars.consume_if_eq('*') => comment_depth += 1,
+                _ => {}
+            }
+        }
+        Some(Trivia::BlockComment)
+    } else if chars.rest().starts_with("//") {
+        chars.consume_while(|ch| ch != '\n');
+        Some(Trivia::LineComment)
+    } else {
+        None
+    }
+}
+
+fn lex<'s>(
+    chars: &mut CharsPeekable<'s>,
+    operators: &mut Table,
+    ast: &mut Ast,
+    whole_symbol: bool,
+) -> Option<Token<'s>> {
     let start = chars.offset();
     let first_ch = chars.next()?;
     match first_ch {
         'a'..='z' | 'A'..='Z' | '_' => {
             chars.consume_while(|ch| matches!(ch, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_'));
             let end = chars.offset();
-            let name = unsafe { chars.get_substring_unchecked(start, end) };
-            Some(Token::Identifier(name.to_owned()))
+            Some(Token::Identifier(chars.slice(start, end)))
         }
         '0'..='9' => {
-            let mut value: i64 = unsafe { first_ch.to_digit(10).unwrap_unchecked() }.into();
-            while let Some(ch) = chars.next_if(|ch| ch.is_ascii_digit()) {
-                value = value
-                    .checked_mul(10)
-                    .unwrap()
-                    .checked_add(unsafe { ch.to_digit(10).unwrap_unchecked() } as i64)
-                    .unwrap();
+            chars.consume_while(|ch| ch.is_ascii_digit());
+            // A float's digits are never read as an integer, which could overflow.
+            let float = chars.consume_if_eq('.');
+            if float {
+                chars.consume_while(|ch| ch.is_ascii_digit());
+            }
+            let end = chars.offset();
+            let literal = chars.slice(start, end);
+            if float {
+                let value: f64 = literal.parse().unwrap();
+                if value.is_infinite() {
+                    lexical_error(format!("float literal `{literal}` is too large"));
+                }
+                Some(Token::Float(value))
+            } else {
+                let value = literal.parse().unwrap_or_else(|_| {
+                    lexical_error(format!("integer literal `{literal}` is too large"))
+                });
+                Some(Token::Integer(value))
             }
-            Some(Token::Integer(value))
         }
         '"' => {
             let mut components = Vec::<StringLiteralComponent>::new();
             let mut string = String::new();
             loop {
-                match chars.next().unwrap() {
+                match chars
+                    .next()
+                    .unwrap_or_else(|| lexical_error("unterminated string literal"))
+                {
                     '"' => {
                         if !string.is_empty() {
                             components.push(StringLiteralComponent::String(string));
@@ -67,13 +173,25 @@ pub fn next(chars: &mut CharsPeekable) -> Option<Token> {
                             components
                                 .push(StringLiteralComponent::String(std::mem::take(&mut string)));
                         }
-                        let mut peeked = next(chars);
-                        let expr = parse_term(chars, &mut peeked);
-                        assert!(matches!(peeked, Some(Token::ClosingBrace)));
-                        components.push(StringLiteralComponent::Expr(expr.unwrap()));
+                        // The literal is a single lexeme.
+                        let lexemes = chars.take_lexemes();
+                        let mut peeked = next(chars, operators, ast);
+                        let expr = parse_term(chars, &mut peeked, operators, ast);
+                        let expr = expr.unwrap_or_else(|| {
+                            lexical_error("expected an expression to interpolate")
+                        });
+                        if !matches!(peeked, Some(Token::ClosingBrace)) {
+                            lexical_error("expected `}` after an interpolated expression");
+                        }
+                        chars.restore_lexemes(lexemes);
+                        components.push(StringLiteralComponent::Expr(expr));
                     }
                     '\\' => {
-                        string.push(chars.next().unwrap());
+                        string.push(
+                            chars
+                                .next()
+                                .unwrap_or_else(|| lexical_error("unterminated string literal")),
+                        );
                     }
                     ch => {
                         string.push(ch);
@@ -81,65 +199,48 @@ pub fn next(chars: &mut CharsPeekable) -> Option<Token> {
                 }
             }
         }
-        '+' => Some(Token::Plus),
-        '-' => Some(Token::Hyphen),
-        '*' => Some(Token::Asterisk),
-        '%' => Some(Token::Percent),
-        '/' => {
-            if chars.consume_if_eq('*') {
-                let mut comment_depth = 1;
-                while comment_depth > 0 {
-                    match chars.next().unwrap() {
-                        '*' if chars.consume_if_eq('/') => comment_depth -= 1,
-                        '/' if chars.consume_if_eq('*') => comment_depth += 1,
-                        _ => {}
-                    }
-                }
-                next(chars)
-            } else if chars.consume_if_eq('/') {
-                chars.consume_while(|ch| ch != '\n');
-                next(chars)
-            } else {
-                Some(Token::Slash)
+        ch if is_symbol(ch) => {
+            let rest = chars.rest();
+            let mut run_end = 0;
+            while rest[run_end..].starts_with(is_symbol)
+                && !rest[run_end..].starts_with("//")
+                && !rest[run_end..].starts_with("/*")
+            {
+                run_end += 1;
             }
-        }
-        '>' => {
-            if chars.consume_if_eq('=') {
-                Some(Token::GreaterEqual)
+            // Symbols that aren't operators, like the `=-` in `x=-1`, are
+            // split after the longest operator they start with.
+            let run = chars.slice(start, start + 1 + run_end);
+            let len = if whole_symbol {
+                run.len()
             } else {
-                Some(Token::Greater)
-            }
-        }
-        '<' => {
-            if chars.consume_if_eq('=') {
-                Some(Token::LessEqual)
-            } else {
-                Some(Token::Less)
-            }
-        }
-        '=' => {
-            if chars.consume_if_eq('=') {
-                Some(Token::DoubleEqual)
-            } else {
-                Some(Token::Equal)
-            }
-        }
-        '!' => {
-            if chars.consume_if_eq('=') {
-                Some(Token::ExclamationEqual)
-            } else {
-                Some(Token::Exclamation)
+                (2..=run.len())
+                    .rev()
+                    .find(|&len| operators.is_operator(&run[..len]))
+                    .unwrap_or(1)
+            };
+            for _ in 1..len {
+                chars.next();
             }
+            Some(Token::Symbol(&run[..len]))
         }
         ',' => Some(Token::Comma),
         ';' => Some(Token::Semicolon),
         ':' => Some(Token::Colon),
+        '.' => Some(Token::Dot),
         '(' => Some(Token::OpeningBracket(BracketKind::Round)),
         ')' => Some(Token::ClosingBracket(BracketKind::Round)),
         '[' => Some(Token::OpeningBracket(BracketKind::Square)),
         ']' => Some(Token::ClosingBracket(BracketKind::Square)),
         '{' => Some(Token::OpeningBrace),
         '}' => Some(Token::ClosingBrace),
-        _ => todo!(),
+        ch => lexical_error(format!("unexpected character {ch:?}")),
     }
 }
+
+pub fn is_symbol(ch: char) -> bool {
+    matches!(
+        ch,
+        '+' | '-' | '*' | '/' | '%' | '<' | '>' | '=' | '!' | '&' | '|' | '^' | '~' | '?'
+    )
+}
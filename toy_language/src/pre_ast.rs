@@ -1,35 +1,82 @@
 mod debug_print;
+mod dump;
+mod print;
+
+use std::ops::{Index, IndexMut};
+
+pub use dump::{json, sexpr};
+pub use print::print;
+
+use crate::span::Span;
+use crate::symbol::Symbol;
+
+// All the terms of a program live in one arena and refer to each other by
+// `NodeId`. A term is only added once its children are, so children always
+// have smaller IDs than their parents. Analyses can keep data about terms in
+// a `Vec` indexed by `NodeId::index`.
+#[derive(Clone, Default, Debug)]
+pub struct Ast {
+    terms: Vec<Term>,
+    pub stmts: Vec<Stmt>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId(u32);
 
 #[derive(Clone, Debug)]
-pub enum Term {
-    Identifier(String),
+pub struct Term {
+    pub kind: TermKind,
+    pub span: Span,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub enum TermKind {
+    Identifier(Symbol),
     Integer(i64),
+    Float(f64),
     StringLiteral(Vec<StringLiteralComponent>),
     Declaration {
-        term: Option<Box<Term>>,
-        ty: Option<Box<Term>>,
+        term: Option<NodeId>,
+        ty: Option<NodeId>,
     },
     BinaryOperation {
-        left_operand: Option<Box<Term>>,
+        left_operand: Option<NodeId>,
         operator: Operator,
-        right_operand: Option<Box<Term>>,
+        right_operand: Option<NodeId>,
+    },
+    CustomOperation {
+        left_operand: Option<NodeId>,
+        operator: Symbol,
+        fixity: Fixity,
+        right_operand: Option<NodeId>,
     },
     Assignment {
-        left_hand_side: Option<Box<Term>>,
+        left_hand_side: Option<NodeId>,
         operator: Operator,
-        right_hand_side: Option<Box<Term>>,
+        right_hand_side: Option<NodeId>,
     },
     Bracketed {
-        antecedent: Option<Box<Term>>,
+        antecedent: Option<NodeId>,
         bracket_kind: BracketKind,
-        elements: Vec<Option<Term>>,
+        elements: Vec<Option<NodeId>>,
         has_trailing_comma: bool,
     },
+    MemberAccess {
+        antecedent: Option<NodeId>,
+        member: Symbol,
+    },
+    Prefixed {
+        prefix: Symbol,
+        term: NodeId,
+    },
+    Block {
+        antecedent: Option<NodeId>,
+        stmts: Vec<Stmt>,
+    },
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum StringLiteralComponent {
     String(String),
-    Expr(Term),
+    Expr(NodeId),
 }
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BracketKind {
@@ -37,7 +84,7 @@ pub enum BracketKind {
     Square,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Operator {
     Add,
     Sub,
@@ -52,12 +99,274 @@ pub enum Operator {
     LessEqual,
     Assign,
 }
+impl Operator {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Rem => "%",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Assign => "=",
+        }
+    }
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Fixity {
+    Prefix,
+    Postfix,
+    Infix(Associativity),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct OperatorDecl {
+    pub symbol: String,
+    pub fixity: Fixity,
+    pub precedence: u32,
+    pub function: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum Stmt {
-    Term(Option<Term>),
+    Term(Option<NodeId>),
     Block {
-        antecedent: Option<Term>,
+        antecedent: Option<NodeId>,
         stmts: Vec<Stmt>,
     },
+    Operator(OperatorDecl),
+}
+
+impl Ast {
+    pub fn push(&mut self, term: Term) -> NodeId {
+        let id = NodeId(self.terms.len().try_into().unwrap());
+        self.terms.push(term);
+        id
+    }
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+    // Drops the terms that can no longer be reached from `stmts`, renumbering
+    // the rest.
+    pub fn compact(&mut self) {
+        let mut reachable = vec![false; self.terms.len()];
+        let mut pending: Vec<_> = self.stmts.iter().flat_map(Stmt::terms).collect();
+        while let Some(id) = pending.pop() {
+            if !reachable[id.index()] {
+                reachable[id.index()] = true;
+                pending.extend(self[id].kind.children());
+            }
+        }
+        let mut new_ids = Vec::with_capacity(self.terms.len());
+        let mut terms = Vec::new();
+        for (mut term, reachable) in std::mem::take(&mut self.terms).into_iter().zip(reachable) {
+            if !reachable {
+                new_ids.push(None);
+                continue;
+            }
+            for child in term.kind.children_mut() {
+                *child = new_ids[child.index()].unwrap();
+            }
+            new_ids.push(Some(NodeId(terms.len() as u32)));
+            terms.push(term);
+        }
+        self.terms = terms;
+        for stmt in &mut self.stmts {
+            for id in stmt.terms_mut() {
+                *id = new_ids[id.index()].unwrap();
+            }
+        }
+    }
+}
+
+impl Index<NodeId> for Ast {
+    type Output = Term;
+    fn index(&self, id: NodeId) -> &Term {
+        &self.terms[id.index()]
+    }
+}
+
+impl IndexMut<NodeId> for Ast {
+    fn index_mut(&mut self, id: NodeId) -> &mut Term {
+        &mut self.terms[id.index()]
+    }
+}
+
+// Two trees are equal if they have the same shape, wherever their terms are
+// in the arena and whatever their spans.
+impl PartialEq for Ast {
+    fn eq(&self, other: &Ast) -> bool {
+        let erase = |stmts: &[Stmt]| {
+            let mut stmts = stmts.to_vec();
+            for id in stmts.iter_mut().flat_map(Stmt::terms_mut) {
+                *id = NodeId(0);
+            }
+            stmts
+        };
+        if erase(&self.stmts) != erase(&other.stmts) {
+            return false;
+        }
+        let terms = |ast: &Ast| ast.stmts.iter().flat_map(Stmt::terms).collect::<Vec<_>>();
+        let mut pending: Vec<_> = terms(self).into_iter().zip(terms(other)).collect();
+        while let Some((id, other_id)) = pending.pop() {
+            let (kind, other_kind) = (&self[id].kind, &other[other_id].kind);
+            let (mut erased, mut other_erased) = (kind.clone(), other_kind.clone());
+            for id in erased
+                .children_mut()
+                .into_iter()
+                .chain(other_erased.children_mut())
+            {
+                *id = NodeId(0);
+            }
+            if erased != other_erased {
+                return false;
+            }
+            pending.extend(kind.children().into_iter().zip(other_kind.children()));
+        }
+        true
+    }
+}
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TermKind {
+    // The terms directly below this one, including those in the statements of
+    // a block.
+    pub fn children(&self) -> Vec<NodeId> {
+        match self {
+            TermKind::Identifier(_) | TermKind::Integer(_) | TermKind::Float(_) => Vec::new(),
+            TermKind::StringLiteral(components) => components
+                .iter()
+                .filter_map(|component| match component {
+                    StringLiteralComponent::Expr(term) => Some(*term),
+                    StringLiteralComponent::String(_) => None,
+                })
+                .collect(),
+            TermKind::Declaration {
+                term: left,
+                ty: right,
+            }
+            | TermKind::BinaryOperation {
+                left_operand: left,
+                right_operand: right,
+                ..
+            }
+            | TermKind::CustomOperation {
+                left_operand: left,
+                right_operand: right,
+                ..
+            }
+            | TermKind::Assignment {
+                left_hand_side: left,
+                right_hand_side: right,
+                ..
+            } => left.iter().chain(right).copied().collect(),
+            TermKind::Bracketed {
+                antecedent,
+                elements,
+                ..
+            } => antecedent
+                .iter()
+                .chain(elements.iter().flatten())
+                .copied()
+                .collect(),
+            TermKind::MemberAccess { antecedent, .. } => antecedent.iter().copied().collect(),
+            TermKind::Prefixed { term, .. } => vec![*term],
+            TermKind::Block { antecedent, stmts } => antecedent
+                .iter()
+                .copied()
+                .chain(stmts.iter().flat_map(Stmt::terms))
+                .collect(),
+        }
+    }
+    pub fn children_mut(&mut self) -> Vec<&mut NodeId> {
+        match self {
+            TermKind::Identifier(_) | TermKind::Integer(_) | TermKind::Float(_) => Vec::new(),
+            TermKind::StringLiteral(components) => components
+                .iter_mut()
+                .filter_map(|component| match component {
+                    StringLiteralComponent::Expr(term) => Some(term),
+                    StringLiteralComponent::String(_) => None,
+                })
+                .collect(),
+            TermKind::Declaration {
+                term: left,
+                ty: right,
+            }
+            | TermKind::BinaryOperation {
+                left_operand: left,
+                right_operand: right,
+                ..
+            }
+            | TermKind::CustomOperation {
+                left_operand: left,
+                right_operand: right,
+                ..
+            }
+            | TermKind::Assignment {
+                left_hand_side: left,
+                right_hand_side: right,
+                ..
+            } => left.iter_mut().chain(right).collect(),
+            TermKind::Bracketed {
+                antecedent,
+                elements,
+                ..
+            } => antecedent
+                .iter_mut()
+                .chain(elements.iter_mut().flatten())
+                .collect(),
+            TermKind::MemberAccess { antecedent, .. } => antecedent.iter_mut().collect(),
+            TermKind::Prefixed { term, .. } => vec![term],
+            TermKind::Block { antecedent, stmts } => antecedent
+                .iter_mut()
+                .chain(stmts.iter_mut().flat_map(Stmt::terms_mut))
+                .collect(),
+        }
+    }
+}
+
+impl Stmt {
+    // The terms of this statement, including those in nested blocks.
+    pub fn terms(&self) -> Vec<NodeId> {
+        match self {
+            Stmt::Term(term) => term.iter().copied().collect(),
+            Stmt::Block { antecedent, stmts } => antecedent
+                .iter()
+                .copied()
+                .chain(stmts.iter().flat_map(Stmt::terms))
+                .collect(),
+            Stmt::Operator(_) => Vec::new(),
+        }
+    }
+    pub fn terms_mut(&mut self) -> Vec<&mut NodeId> {
+        match self {
+            Stmt::Term(term) => term.iter_mut().collect(),
+            Stmt::Block { antecedent, stmts } => antecedent
+                .iter_mut()
+                .chain(stmts.iter_mut().flat_map(Stmt::terms_mut))
+                .collect(),
+            Stmt::Operator(_) => Vec::new(),
+        }
+    }
 }
@@ -1,90 +1,126 @@
-use super::{Stmt, StringLiteralComponent, Term};
+use super::{Ast, Stmt, StringLiteralComponent, Term, TermKind};
 
 impl Term {
-    pub fn _debug_print(&self, depth: usize) {
+    pub fn _debug_print(&self, ast: &Ast, depth: usize) {
         let indent = "  ".repeat(depth);
-        match self {
-            Term::Identifier(name) => {
+        match &self.kind {
+            TermKind::Identifier(name) => {
                 println!("{indent}Identifier({name})");
             }
-            Term::Integer(value) => {
+            TermKind::Integer(value) => {
                 println!("{indent}Integer({value})");
             }
-            Term::StringLiteral(components) => {
+            TermKind::Float(value) => {
+                println!("{indent}Float({value})");
+            }
+            TermKind::StringLiteral(components) => {
                 println!("{indent}String literal");
                 for component in components {
-                    component._debug_print(depth + 1);
+                    component._debug_print(ast, depth + 1);
                 }
             }
-            Term::Declaration { term, ty } => {
+            TermKind::Declaration { term, ty } => {
                 println!("{indent}Declaration");
                 if let Some(term) = term {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
                 if let Some(term) = ty {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
             }
-            Term::Assignment {
+            TermKind::Assignment {
                 left_hand_side,
                 operator,
                 right_hand_side,
             } => {
                 println!("{indent}Assignment({operator:?})");
                 if let Some(term) = left_hand_side {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
                 if let Some(term) = right_hand_side {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
             }
-            Term::BinaryOperation {
+            TermKind::BinaryOperation {
                 left_operand,
                 operator,
                 right_operand,
             } => {
                 println!("{indent}Binary operation({operator:?})");
                 if let Some(term) = left_operand {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
+                }
+                if let Some(term) = right_operand {
+                    ast[*term]._debug_print(ast, depth + 1);
+                }
+            }
+            TermKind::CustomOperation {
+                left_operand,
+                operator,
+                fixity,
+                right_operand,
+            } => {
+                println!("{indent}Custom operation({operator}, {fixity:?})");
+                if let Some(term) = left_operand {
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
                 if let Some(term) = right_operand {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
             }
-            Term::Bracketed {
+            TermKind::Bracketed {
                 antecedent,
                 bracket_kind,
                 elements,
                 has_trailing_comma,
             } => {
-                eprintln!("{indent}Bracketed({bracket_kind:?})");
+                println!("{indent}Bracketed({bracket_kind:?})");
                 match antecedent {
-                    Some(term) => term._debug_print(depth + 1),
-                    None => eprintln!("{indent}  (no antecedent)"),
+                    Some(term) => ast[*term]._debug_print(ast, depth + 1),
+                    None => println!("{indent}  (no antecedent)"),
                 }
-                eprintln!(
+                println!(
                     "{indent}{} elements (trailing comma: {has_trailing_comma})",
                     elements.len()
                 );
                 for elem in elements {
                     match elem {
-                        Some(term) => term._debug_print(depth + 1),
+                        Some(term) => ast[*term]._debug_print(ast, depth + 1),
                         None => {
-                            eprintln!("{indent}  (empty)")
+                            println!("{indent}  (empty)")
                         }
                     }
                 }
             }
+            TermKind::MemberAccess { antecedent, member } => {
+                println!("{indent}Member access({member})");
+                if let Some(term) = antecedent {
+                    ast[*term]._debug_print(ast, depth + 1);
+                }
+            }
+            TermKind::Prefixed { prefix, term } => {
+                println!("{indent}Prefixed({prefix})");
+                ast[*term]._debug_print(ast, depth + 1);
+            }
+            TermKind::Block { antecedent, stmts } => {
+                println!("{indent}Block");
+                if let Some(term) = antecedent {
+                    ast[*term]._debug_print(ast, depth + 1);
+                }
+                for stmt in stmts {
+                    stmt._debug_print(ast, depth + 1);
+                }
+            }
         }
     }
 }
 
 impl StringLiteralComponent {
-    pub fn _debug_print(&self, depth: usize) {
+    pub fn _debug_print(&self, ast: &Ast, depth: usize) {
         let indent = "  ".repeat(depth);
         match self {
             StringLiteralComponent::Expr(expr) => {
-                expr._debug_print(depth);
+                ast[*expr]._debug_print(ast, depth);
             }
             StringLiteralComponent::String(string) => {
                 println!("{indent}{string}");
@@ -94,24 +130,30 @@ impl StringLiteralComponent {
 }
 
 impl Stmt {
-    pub fn _debug_print(&self, depth: usize) {
+    pub fn _debug_print(&self, ast: &Ast, depth: usize) {
         let indent = "  ".repeat(depth);
         match self {
             Stmt::Term(term) => {
                 println!("{indent}Expression statement");
                 if let Some(term) = term {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
             }
             Stmt::Block { antecedent, stmts } => {
                 println!("{indent}Block");
                 if let Some(term) = antecedent {
-                    term._debug_print(depth + 1);
+                    ast[*term]._debug_print(ast, depth + 1);
                 }
                 for stmt in stmts {
-                    stmt._debug_print(depth + 1);
+                    stmt._debug_print(ast, depth + 1);
                 }
             }
+            Stmt::Operator(decl) => {
+                println!(
+                    "{indent}Operator({}, {:?}, {}, {})",
+                    decl.symbol, decl.fixity, decl.precedence, decl.function
+                );
+            }
         }
     }
 }
@@ -0,0 +1,347 @@
+//! Machine-readable dumps of the pre-AST, selected with `--emit=ast-json`
+//! and `--emit=ast-sexpr`.
+//!
+//! Both formats describe the same tree. A program is a list of statement
+//! nodes; every node has a `kind` and, for terms, a `span` holding the start
+//! and end byte offsets of the term in the source. Absent operands are
+//! `null` (JSON) or `nil` (S-expressions). The schema version is bumped on
+//! any incompatible change.
+//!
+//! Statements:
+//! - `term_stmt`: `term` (a term or null), for `term;`
+//! - `block_stmt`: `antecedent`, `stmts`, for `antecedent { stmts }`
+//! - `operator_decl`: `symbol`, `fixity`, `precedence`, `function`
+//!
+//! Terms:
+//! - `identifier`: `name`
+//! - `integer`, `float`: `value`
+//! - `string`: `components`, each a `text` node with a `value` or a term
+//! - `declaration`: `term`, `type`
+//! - `binary_operation`: `operator`, `left`, `right`
+//! - `custom_operation`: `operator`, `fixity`, `left`, `right`
+//! - `assignment`: `operator`, `left`, `right`
+//! - `bracketed`: `antecedent`, `bracket` (`round` or `square`),
+//!   `elements`, `trailing_comma`
+//! - `member_access`: `antecedent`, `member`
+//! - `prefixed`: `prefix`, `term`
+//! - `block`: `antecedent`, `stmts`
+//!
+//! Operators are written as their source symbols and fixities as in
+//! operator declarations (`prefix`, `postfix`, `infixl`, `infixr`, `infix`).
+//!
+//! JSON: `{"version": 1, "stmts": [...]}` with each node an object whose
+//! first keys are `kind` and `span` (`[start, end]`).
+//!
+//! S-expressions: `(program (version 1) node...)` with each node written as
+//! `(kind (span start end) (field value)...)`; list fields are written as
+//! `(field item...)`.
+
+use std::fmt::Write;
+
+use super::{
+    Associativity, Ast, BracketKind, Fixity, NodeId, OperatorDecl, Stmt, StringLiteralComponent,
+    TermKind,
+};
+use crate::span::Span;
+
+const VERSION: i64 = 1;
+
+enum Value {
+    Node {
+        kind: &'static str,
+        span: Option<Span>,
+        fields: Vec<(&'static str, Value)>,
+    },
+    List(Vec<Value>),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+pub fn json(ast: &Ast) -> String {
+    let mut output = String::new();
+    write!(output, "{{\"version\":{VERSION},\"stmts\":").unwrap();
+    let stmts = ast.stmts.iter().map(|stmt| self::stmt(ast, stmt)).collect();
+    write_json(&mut output, &Value::List(stmts));
+    output.push_str("}\n");
+    output
+}
+
+pub fn sexpr(ast: &Ast) -> String {
+    let mut output = format!("(program (version {VERSION})");
+    for stmt in &ast.stmts {
+        output.push_str("\n  ");
+        write_sexpr(&mut output, &self::stmt(ast, stmt));
+    }
+    output.push_str(")\n");
+    output
+}
+
+fn stmt(ast: &Ast, stmt: &Stmt) -> Value {
+    match stmt {
+        Stmt::Term(term) => node("term_stmt", None, vec![("term", optional(ast, *term))]),
+        Stmt::Block { antecedent, stmts } => node(
+            "block_stmt",
+            None,
+            vec![
+                ("antecedent", optional(ast, *antecedent)),
+                ("stmts", stmt_list(ast, stmts)),
+            ],
+        ),
+        Stmt::Operator(OperatorDecl {
+            symbol,
+            fixity: decl_fixity,
+            precedence,
+            function,
+        }) => node(
+            "operator_decl",
+            None,
+            vec![
+                ("symbol", Value::String(symbol.clone())),
+                ("fixity", fixity(*decl_fixity)),
+                ("precedence", Value::Integer((*precedence).into())),
+                ("function", Value::String(function.clone())),
+            ],
+        ),
+    }
+}
+
+fn term(ast: &Ast, term: NodeId) -> Value {
+    let term = &ast[term];
+    let span = Some(term.span);
+    match &term.kind {
+        TermKind::Identifier(name) => node(
+            "identifier",
+            span,
+            vec![("name", Value::String(name.to_string()))],
+        ),
+        TermKind::Integer(value) => node("integer", span, vec![("value", Value::Integer(*value))]),
+        TermKind::Float(value) => node("float", span, vec![("value", Value::Float(*value))]),
+        TermKind::StringLiteral(components) => {
+            let components = components
+                .iter()
+                .map(|component| match component {
+                    StringLiteralComponent::String(string) => {
+                        node("text", None, vec![("value", Value::String(string.clone()))])
+                    }
+                    StringLiteralComponent::Expr(term) => self::term(ast, *term),
+                })
+                .collect();
+            node(
+                "string",
+                span,
+                vec![("components", Value::List(components))],
+            )
+        }
+        TermKind::Declaration { term, ty } => node(
+            "declaration",
+            span,
+            vec![("term", optional(ast, *term)), ("type", optional(ast, *ty))],
+        ),
+        TermKind::BinaryOperation {
+            left_operand,
+            operator,
+            right_operand,
+        } => node(
+            "binary_operation",
+            span,
+            vec![
+                ("operator", Value::String(operator.symbol().to_owned())),
+                ("left", optional(ast, *left_operand)),
+                ("right", optional(ast, *right_operand)),
+            ],
+        ),
+        TermKind::CustomOperation {
+            left_operand,
+            operator,
+            fixity: operator_fixity,
+            right_operand,
+        } => node(
+            "custom_operation",
+            span,
+            vec![
+                ("operator", Value::String(operator.to_string())),
+                ("fixity", fixity(*operator_fixity)),
+                ("left", optional(ast, *left_operand)),
+                ("right", optional(ast, *right_operand)),
+            ],
+        ),
+        TermKind::Assignment {
+            left_hand_side,
+            operator,
+            right_hand_side,
+        } => node(
+            "assignment",
+            span,
+            vec![
+                ("operator", Value::String(operator.symbol().to_owned())),
+                ("left", optional(ast, *left_hand_side)),
+                ("right", optional(ast, *right_hand_side)),
+            ],
+        ),
+        TermKind::Bracketed {
+            antecedent,
+            bracket_kind,
+            elements,
+            has_trailing_comma,
+        } => {
+            let bracket = match bracket_kind {
+                BracketKind::Round => "round",
+                BracketKind::Square => "square",
+            };
+            let elements = elements
+                .iter()
+                .map(|element| optional(ast, *element))
+                .collect();
+            node(
+                "bracketed",
+                span,
+                vec![
+                    ("antecedent", optional(ast, *antecedent)),
+                    ("bracket", Value::String(bracket.to_owned())),
+                    ("elements", Value::List(elements)),
+                    ("trailing_comma", Value::Bool(*has_trailing_comma)),
+                ],
+            )
+        }
+        TermKind::MemberAccess { antecedent, member } => node(
+            "member_access",
+            span,
+            vec![
+                ("antecedent", optional(ast, *antecedent)),
+                ("member", Value::String(member.to_string())),
+            ],
+        ),
+        TermKind::Prefixed { prefix, term } => node(
+            "prefixed",
+            span,
+            vec![
+                ("prefix", Value::String(prefix.to_string())),
+                ("term", self::term(ast, *term)),
+            ],
+        ),
+        TermKind::Block { antecedent, stmts } => node(
+            "block",
+            span,
+            vec![
+                ("antecedent", optional(ast, *antecedent)),
+                ("stmts", stmt_list(ast, stmts)),
+            ],
+        ),
+    }
+}
+
+fn node(kind: &'static str, span: Option<Span>, fields: Vec<(&'static str, Value)>) -> Value {
+    Value::Node { kind, span, fields }
+}
+
+fn optional(ast: &Ast, term: Option<NodeId>) -> Value {
+    term.map_or(Value::Null, |term| self::term(ast, term))
+}
+
+fn stmt_list(ast: &Ast, stmts: &[Stmt]) -> Value {
+    Value::List(stmts.iter().map(|stmt| self::stmt(ast, stmt)).collect())
+}
+
+fn fixity(fixity: Fixity) -> Value {
+    let name = match fixity {
+        Fixity::Prefix => "prefix",
+        Fixity::Postfix => "postfix",
+        Fixity::Infix(Associativity::Left) => "infixl",
+        Fixity::Infix(Associativity::Right) => "infixr",
+        Fixity::Infix(Associativity::None) => "infix",
+    };
+    Value::String(name.to_owned())
+}
+
+fn write_json(output: &mut String, value: &Value) {
+    match value {
+        Value::Node { kind, span, fields } => {
+            write!(output, "{{\"kind\":\"{kind}\"").unwrap();
+            if let Some(span) = span {
+                write!(output, ",\"span\":[{},{}]", span.start, span.end).unwrap();
+            }
+            for (name, value) in fields {
+                write!(output, ",\"{name}\":").unwrap();
+                write_json(output, value);
+            }
+            output.push('}');
+        }
+        Value::List(items) => {
+            output.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_json(output, item);
+            }
+            output.push(']');
+        }
+        Value::String(string) => write_string(output, string),
+        Value::Integer(value) => write!(output, "{value}").unwrap(),
+        Value::Float(value) => write!(output, "{value:?}").unwrap(),
+        Value::Bool(value) => write!(output, "{value}").unwrap(),
+        Value::Null => output.push_str("null"),
+    }
+}
+
+fn write_sexpr(output: &mut String, value: &Value) {
+    match value {
+        Value::Node { kind, span, fields } => {
+            write!(output, "({kind}").unwrap();
+            if let Some(span) = span {
+                write!(output, " (span {} {})", span.start, span.end).unwrap();
+            }
+            for (name, value) in fields {
+                write!(output, " ({name}").unwrap();
+                match value {
+                    Value::List(items) => {
+                        for item in items {
+                            output.push(' ');
+                            write_sexpr(output, item);
+                        }
+                    }
+                    value => {
+                        output.push(' ');
+                        write_sexpr(output, value);
+                    }
+                }
+                output.push(')');
+            }
+            output.push(')');
+        }
+        Value::List(items) => {
+            output.push('(');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    output.push(' ');
+                }
+                write_sexpr(output, item);
+            }
+            output.push(')');
+        }
+        Value::String(string) => write_string(output, string),
+        Value::Integer(value) => write!(output, "{value}").unwrap(),
+        Value::Float(value) => write!(output, "{value:?}").unwrap(),
+        Value::Bool(value) => write!(output, "{value}").unwrap(),
+        Value::Null => output.push_str("nil"),
+    }
+}
+
+fn write_string(output: &mut String, string: &str) {
+    output.push('"');
+    for ch in string.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\r' => output.push_str("\\r"),
+            ch if ch.is_control() => write!(output, "\\u{:04x}", ch as u32).unwrap(),
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+}
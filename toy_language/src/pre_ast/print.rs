@@ -0,0 +1,307 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::parser::is_symbol;
+
+use super::{
+    Associativity, Ast, BracketKind, Fixity, NodeId, OperatorDecl, Stmt, StringLiteralComponent,
+    TermKind,
+};
+
+impl Display for OperatorDecl {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let fixity = match self.fixity {
+            Fixity::Prefix => "prefix",
+            Fixity::Postfix => "postfix",
+            Fixity::Infix(Associativity::Left) => "infixl",
+            Fixity::Infix(Associativity::Right) => "infixr",
+            Fixity::Infix(Associativity::None) => "infix",
+        };
+        write!(
+            f,
+            "operator {fixity} {} {} {};",
+            self.precedence, self.symbol, self.function
+        )
+    }
+}
+
+pub fn print(ast: &Ast) -> String {
+    struct Program<'a>(&'a Ast);
+    impl Display for Program<'_> {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            write_stmts(f, self.0, &self.0.stmts, 0)
+        }
+    }
+    Program(ast).to_string()
+}
+
+fn write_stmts(f: &mut Formatter, ast: &Ast, stmts: &[Stmt], depth: usize) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+    let mut stmts = stmts.iter().peekable();
+    while let Some(stmt) = stmts.next() {
+        f.write_str(&indent)?;
+        match stmt {
+            Stmt::Term(term) => {
+                if let Some(term) = term {
+                    write_term(f, ast, *term, depth)?;
+                }
+                f.write_str(";")?;
+            }
+            Stmt::Block {
+                antecedent,
+                stmts: body,
+            } => {
+                write_block(f, ast, *antecedent, body, depth)?;
+                if let Some(Stmt::Block {
+                    antecedent: Some(antecedent),
+                    stmts: body,
+                }) = stmts.next_if(|stmt| is_else(ast, stmt))
+                {
+                    f.write_char(' ')?;
+                    write_block(f, ast, Some(*antecedent), body, depth)?;
+                }
+                if stmts
+                    .next_if(|stmt| matches!(stmt, Stmt::Term(None)))
+                    .is_some()
+                {
+                    f.write_char(';')?;
+                }
+            }
+            Stmt::Operator(decl) => write!(f, "{decl}")?,
+        }
+        f.write_char('\n')?;
+    }
+    Ok(())
+}
+
+fn is_else(ast: &Ast, stmt: &Stmt) -> bool {
+    matches!(
+        stmt,
+        Stmt::Block {
+            antecedent: Some(antecedent),
+            ..
+        } if matches!(&ast[*antecedent].kind, TermKind::Identifier(name) if name == "else")
+    )
+}
+
+fn write_block(
+    f: &mut Formatter,
+    ast: &Ast,
+    antecedent: Option<NodeId>,
+    stmts: &[Stmt],
+    depth: usize,
+) -> fmt::Result {
+    if let Some(antecedent) = antecedent {
+        write_term(f, ast, antecedent, depth)?;
+        f.write_char(' ')?;
+    }
+    if stmts.is_empty() {
+        return f.write_str("{}");
+    }
+    f.write_str("{\n")?;
+    write_stmts(f, ast, stmts, depth + 1)?;
+    write!(f, "{}}}", "    ".repeat(depth))
+}
+
+fn write_term(f: &mut Formatter, ast: &Ast, term: NodeId, depth: usize) -> fmt::Result {
+    match &ast[term].kind {
+        TermKind::Identifier(name) => f.write_str(name.as_str()),
+        TermKind::Integer(value) => write!(f, "{value}"),
+        TermKind::Float(value) => {
+            let literal = value.to_string();
+            if literal.contains('.') {
+                f.write_str(&literal)
+            } else {
+                write!(f, "{literal}.0")
+            }
+        }
+        TermKind::StringLiteral(components) => {
+            f.write_char('"')?;
+            for component in components {
+                match component {
+                    StringLiteralComponent::String(string) => {
+                        for ch in string.chars() {
+                            if matches!(ch, '"' | '\\' | '{') {
+                                f.write_char('\\')?;
+                            }
+                            f.write_char(ch)?;
+                        }
+                    }
+                    StringLiteralComponent::Expr(term) => {
+                        f.write_char('{')?;
+                        write_term(f, ast, *term, depth)?;
+                        f.write_char('}')?;
+                    }
+                }
+            }
+            f.write_char('"')
+        }
+        TermKind::Declaration { term, ty } => {
+            write_operand(f, ast, *term, depth)?;
+            f.write_char(':')?;
+            if let Some(ty) = ty {
+                f.write_char(' ')?;
+                write_term(f, ast, *ty, depth)?;
+            }
+            Ok(())
+        }
+        TermKind::BinaryOperation {
+            left_operand: None,
+            operator,
+            right_operand,
+        } => write_prefix(f, ast, operator.symbol(), *right_operand, depth),
+        TermKind::BinaryOperation {
+            left_operand: Some(left_operand),
+            operator,
+            right_operand,
+        } => write_infix(
+            f,
+            ast,
+            *left_operand,
+            operator.symbol(),
+            *right_operand,
+            depth,
+        ),
+        TermKind::CustomOperation {
+            left_operand,
+            operator,
+            fixity,
+            right_operand,
+        } => match (fixity, left_operand) {
+            (Fixity::Prefix, _) | (Fixity::Infix(_), None) => {
+                write_prefix(f, ast, operator.as_str(), *right_operand, depth)
+            }
+            (Fixity::Postfix, left_operand) => {
+                let left_operand = left_operand.map(|term| to_string(ast, term, depth));
+                let left_operand = left_operand.unwrap_or_default();
+                f.write_str(&left_operand)?;
+                if left_operand.ends_with(is_symbol) {
+                    f.write_char(' ')?;
+                }
+                f.write_str(operator.as_str())
+            }
+            (Fixity::Infix(_), Some(left_operand)) => write_infix(
+                f,
+                ast,
+                *left_operand,
+                operator.as_str(),
+                *right_operand,
+                depth,
+            ),
+        },
+        TermKind::Assignment {
+            left_hand_side,
+            operator: _,
+            right_hand_side,
+        } => {
+            match left_hand_side.map(|term| &ast[term].kind) {
+                Some(TermKind::Declaration { term, ty: None }) => {
+                    write_operand(f, ast, *term, depth)?;
+                    f.write_str(" :=")?;
+                }
+                _ => {
+                    write_operand(f, ast, *left_hand_side, depth)?;
+                    f.write_str(" =")?;
+                }
+            }
+            if let Some(right_hand_side) = right_hand_side {
+                f.write_char(' ')?;
+                write_term(f, ast, *right_hand_side, depth)?;
+            }
+            Ok(())
+        }
+        TermKind::Bracketed {
+            antecedent,
+            bracket_kind,
+            elements,
+            has_trailing_comma,
+        } => {
+            write_operand(f, ast, *antecedent, depth)?;
+            if let Some(TermKind::Identifier(keyword)) = antecedent.map(|term| &ast[term].kind) {
+                if ["if", "while", "match"].contains(&keyword.as_str()) {
+                    f.write_char(' ')?;
+                }
+            }
+            let (opening, closing) = match bracket_kind {
+                BracketKind::Round => ('(', ')'),
+                BracketKind::Square => ('[', ']'),
+            };
+            f.write_char(opening)?;
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    f.write_char(',')?;
+                    if element.is_some() {
+                        f.write_char(' ')?;
+                    }
+                }
+                write_operand(f, ast, *element, depth)?;
+            }
+            if *has_trailing_comma && !elements.is_empty() {
+                f.write_char(',')?;
+            }
+            f.write_char(closing)
+        }
+        TermKind::MemberAccess { antecedent, member } => {
+            write_operand(f, ast, *antecedent, depth)?;
+            if let Some(TermKind::Integer(_)) = antecedent.map(|term| &ast[term].kind) {
+                f.write_char(' ')?;
+            }
+            write!(f, ".{member}")
+        }
+        TermKind::Prefixed { prefix, term } => {
+            write!(f, "{prefix} ")?;
+            write_term(f, ast, *term, depth)
+        }
+        TermKind::Block { antecedent, stmts } => write_block(f, ast, *antecedent, stmts, depth),
+    }
+}
+
+fn write_operand(f: &mut Formatter, ast: &Ast, term: Option<NodeId>, depth: usize) -> fmt::Result {
+    match term {
+        Some(term) => write_term(f, ast, term, depth),
+        None => Ok(()),
+    }
+}
+
+fn write_prefix(
+    f: &mut Formatter,
+    ast: &Ast,
+    operator: &str,
+    operand: Option<NodeId>,
+    depth: usize,
+) -> fmt::Result {
+    f.write_str(operator)?;
+    let operand = operand
+        .map(|term| to_string(ast, term, depth))
+        .unwrap_or_default();
+    if operand.starts_with(is_symbol) {
+        f.write_char(' ')?;
+    }
+    f.write_str(&operand)
+}
+
+fn write_infix(
+    f: &mut Formatter,
+    ast: &Ast,
+    left_operand: NodeId,
+    operator: &str,
+    right_operand: Option<NodeId>,
+    depth: usize,
+) -> fmt::Result {
+    write_term(f, ast, left_operand, depth)?;
+    write!(f, " {operator}")?;
+    if let Some(right_operand) = right_operand {
+        f.write_char(' ')?;
+        write_term(f, ast, right_operand, depth)?;
+    }
+    Ok(())
+}
+
+fn to_string(ast: &Ast, term: NodeId, depth: usize) -> String {
+    struct Indented<'a>(&'a Ast, NodeId, usize);
+    impl Display for Indented<'_> {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            write_term(f, self.0, self.1, self.2)
+        }
+    }
+    Indented(ast, term, depth).to_string()
+}
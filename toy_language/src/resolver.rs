@@ -0,0 +1,3167 @@
+mod comptime;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    Arm, Builtin, EnumDef, Expr, Func, ImplDef, Overflow, Pattern, Program, Stmt, StructDef, Ty,
+};
+use crate::module::{self, Module};
+use crate::pre_ast::{
+    self, Ast, BracketKind, Fixity, NodeId, Operator, StringLiteralComponent, Term, TermKind,
+};
+use crate::span::{self, Span};
+use crate::{simplify, symbol};
+pub use comptime::generate;
+
+const KEYWORDS: &[&str] = &["if", "else", "while", "return", "match"];
+const BUILTINS: &[&str] = &[
+    "len",
+    "print",
+    "println",
+    "read_line",
+    "parse_int",
+    "args",
+    "abs",
+    "min",
+    "max",
+    "sqrt",
+    "sin",
+    "pow",
+    "exit",
+    "checked",
+    "wrapping",
+    "saturating",
+];
+
+#[derive(Default, Debug)]
+pub struct Index {
+    pub references: Vec<Reference>,
+}
+
+#[derive(Debug)]
+pub struct Reference {
+    pub name: String,
+    pub kind: ReferenceKind,
+    pub span: Span,
+    pub definition: Span,
+    pub ty: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReferenceKind {
+    Variable,
+    Global,
+    Func,
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolKind {
+    Func,
+    Global,
+    Struct,
+    Enum,
+    Trait,
+    Const,
+}
+
+#[derive(PartialEq)]
+struct Signature {
+    params: Vec<Ty>,
+    ret_ty: Ty,
+}
+
+#[derive(Clone)]
+struct Definition<'a> {
+    name: &'a str,
+    span: Span,
+    type_params: Vec<&'a str>,
+    params: &'a [Option<NodeId>],
+    ret_ty: Option<&'a Term>,
+    body: &'a [pre_ast::Stmt],
+    module: usize,
+}
+
+#[derive(Clone)]
+struct GenericStruct<'a> {
+    name: &'a str,
+    module: usize,
+    type_params: Vec<&'a str>,
+    fields: Vec<(&'a str, &'a Term)>,
+}
+
+#[derive(Clone)]
+struct TraitDef<'a> {
+    name: &'a str,
+    methods: Vec<Definition<'a>>,
+}
+
+// A constant is evaluated at compile time, the first time it is used.
+struct Const<'a> {
+    name: &'a str,
+    span: Span,
+    module: usize,
+    ty: Option<&'a Term>,
+    value: &'a Term,
+    // The block of `const NAME := comptime { ... }`.
+    body: Option<&'a [pre_ast::Stmt]>,
+    resolved: Option<(Expr, Ty)>,
+    resolving: bool,
+}
+
+struct LambdaHead<'a> {
+    captures: &'a [Option<NodeId>],
+    params: &'a [Option<NodeId>],
+    ret_ty: Option<&'a Term>,
+}
+
+// The names visible in a module: its own items and the `pub` items of the
+// modules it imports.
+#[derive(Default)]
+struct Namespace {
+    structs: HashMap<String, usize>,
+    generic_structs: HashMap<String, usize>,
+    enums: HashMap<String, usize>,
+    variants: HashMap<String, (usize, usize)>,
+    traits: HashMap<String, usize>,
+    funcs: HashMap<String, usize>,
+    generic_funcs: HashMap<String, usize>,
+    globals: HashMap<String, usize>,
+    consts: HashMap<String, usize>,
+    operators: HashMap<(symbol::Symbol, Fixity), symbol::Symbol>,
+}
+
+struct Resolver<'a> {
+    ast: &'a Ast,
+    modules: &'a [Module],
+    module: usize,
+    names: Namespace,
+    namespaces: Vec<Namespace>,
+    public: Vec<HashSet<String>>,
+    structs: Vec<StructDef>,
+    generic_structs: Vec<GenericStruct<'a>>,
+    struct_instances: HashMap<(usize, Vec<Ty>), usize>,
+    struct_origins: HashMap<usize, (usize, Vec<Ty>)>,
+    enums: Vec<EnumDef>,
+    traits: Vec<TraitDef<'a>>,
+    impls: Vec<ImplDef>,
+    impl_indices: HashMap<(usize, Ty), usize>,
+    signatures: Vec<Signature>,
+    func_spans: HashMap<usize, Span>,
+    generic_funcs: Vec<Definition<'a>>,
+    func_instances: HashMap<(usize, Vec<Ty>), usize>,
+    pending_instances: Vec<(usize, usize, Vec<Ty>)>,
+    type_params: HashMap<String, Ty>,
+    globals: Vec<Ty>,
+    global_spans: Vec<Span>,
+    lambdas: Vec<Func>,
+    outer_scopes: Vec<Scope>,
+    consts: Vec<Const<'a>>,
+    generators: Vec<(usize, Span, &'a [pre_ast::Stmt])>,
+    // Code to run at compile time, by the span of its `comptime` block or
+    // constant value: the function it became or, once it has been run, the value
+    // it evaluated to.
+    comptime_funcs: HashMap<Span, usize>,
+    comptime_values: &'a HashMap<Span, Expr>,
+    comptime: Vec<(usize, Func)>,
+    comptime_roots: Vec<(Span, usize)>,
+    in_comptime: bool,
+    index: Index,
+}
+
+struct Scope {
+    vars: Vec<Ty>,
+    spans: Vec<Span>,
+    boxed: Vec<bool>,
+    names: Vec<HashMap<String, usize>>,
+    ret_ty: Ty,
+    is_init: bool,
+    by_ref: HashSet<String>,
+    captures: Option<Vec<(usize, usize, bool)>>,
+    ref_captures: HashSet<String>,
+}
+
+enum Bracket<'a> {
+    Group(&'a Term),
+    Tuple(Vec<&'a Term>),
+}
+
+pub fn resolve(ast: &Ast, modules: &[Module]) -> Program {
+    let (program, _, roots) = resolve_with(ast, modules, &HashMap::new());
+    if roots.is_empty() {
+        return program;
+    }
+    let values = comptime::evaluate(&program, &roots);
+    resolve_with(ast, modules, &values).0
+}
+
+// Leaves the code meant to run at compile time unevaluated.
+pub fn analyze(ast: &Ast, modules: &[Module]) -> (Program, Index) {
+    let (program, index, _) = resolve_with(ast, modules, &HashMap::new());
+    (program, index)
+}
+
+// Resolves the program with the values of its compile-time code, if they are
+// known. Also returns the outermost pieces of compile-time code that are not:
+// they are made functions of the program, which can be called to evaluate
+// them.
+fn resolve_with(
+    ast: &Ast,
+    modules: &[Module],
+    comptime_values: &HashMap<Span, Expr>,
+) -> (Program, Index, Vec<(Span, usize)>) {
+    span::clear();
+    let mut resolver = Resolver {
+        ast,
+        modules,
+        module: 0,
+        names: Namespace::default(),
+        namespaces: modules.iter().map(|_| Namespace::default()).collect(),
+        public: vec![HashSet::new(); modules.len()],
+        structs: Vec::new(),
+        generic_structs: Vec::new(),
+        struct_instances: HashMap::new(),
+        struct_origins: HashMap::new(),
+        enums: Vec::new(),
+        traits: Vec::new(),
+        impls: Vec::new(),
+        impl_indices: HashMap::new(),
+        signatures: Vec::new(),
+        func_spans: HashMap::new(),
+        generic_funcs: Vec::new(),
+        func_instances: HashMap::new(),
+        pending_instances: Vec::new(),
+        type_params: HashMap::new(),
+        globals: Vec::new(),
+        global_spans: Vec::new(),
+        lambdas: Vec::new(),
+        outer_scopes: Vec::new(),
+        consts: Vec::new(),
+        generators: Vec::new(),
+        comptime_funcs: HashMap::new(),
+        comptime_values,
+        comptime: Vec::new(),
+        comptime_roots: Vec::new(),
+        in_comptime: false,
+        index: Index::default(),
+    };
+    let mut definitions = Vec::new();
+    let mut impl_definitions = Vec::new();
+    let mut init_stmts = Vec::new();
+    for module in 0..modules.len() {
+        resolver.enter_module(module);
+        resolver.import();
+        init_stmts.push(resolver.collect(&mut definitions, &mut impl_definitions));
+    }
+
+    let mut method_definitions = Vec::new();
+    for (module, trait_name, ty, body) in impl_definitions {
+        resolver.enter_module(module);
+        let Some(&trait_index) = resolver.names.traits.get(trait_name) else {
+            panic!("unknown trait `{trait_name}`");
+        };
+        let ty = resolver.resolve_ty(ty);
+        assert!(
+            !resolver
+                .impl_indices
+                .contains_key(&(trait_index, ty.clone())),
+            "duplicate implementation of `{trait_name}` for `{}`",
+            resolver.type_name(&ty)
+        );
+        let items: Vec<_> = body
+            .iter()
+            .map(|stmt| {
+                let definition = function_definition(ast, stmt)
+                    .unwrap_or_else(|| panic!("invalid item in implementation of `{trait_name}`"));
+                Definition {
+                    module,
+                    ..definition
+                }
+            })
+            .collect();
+        let trait_def = resolver.traits[trait_index].clone();
+        for item in &items {
+            assert!(
+                trait_def
+                    .methods
+                    .iter()
+                    .any(|method| method.name == item.name),
+                "`{}` is not a method of `{trait_name}`",
+                item.name
+            );
+        }
+        let methods = trait_def
+            .methods
+            .iter()
+            .map(|method| {
+                let Some(definition) = items.iter().find(|item| item.name == method.name) else {
+                    panic!(
+                        "missing method `{}` in implementation of `{trait_name}`",
+                        method.name
+                    );
+                };
+                let (signature, expected) =
+                    resolver.with_type_params(&["Self"], std::slice::from_ref(&ty), |resolver| {
+                        (
+                            resolver.resolve_signature(definition),
+                            resolver.resolve_signature(method),
+                        )
+                    });
+                assert!(
+                    signature == expected,
+                    "mismatched signature of method `{}`",
+                    method.name
+                );
+                let index = resolver.signatures.len();
+                resolver.define_func(definition, index, &signature);
+                resolver.signatures.push(signature);
+                let trait_name = resolver.qualified(method.module, trait_name);
+                let name = format!("<{ty:?} as {trait_name}>::{}", method.name);
+                method_definitions.push((index, definition.clone(), ty.clone(), name));
+                index
+            })
+            .collect();
+        resolver
+            .impl_indices
+            .insert((trait_index, ty.clone()), resolver.impls.len());
+        resolver.impls.push(ImplDef {
+            trait_index,
+            ty,
+            methods,
+        });
+    }
+
+    // Every module initializes its globals after the modules it imports.
+    let stmts: Vec<_> = init_stmts.iter().flatten().cloned().collect();
+    let mut scope = Scope::new(ast, Ty::unit(), true, &stmts);
+    let mut body = Vec::new();
+    for (module, stmts) in init_stmts.iter().enumerate() {
+        resolver.enter_module(module);
+        resolver.import();
+        body.extend(resolver.resolve_block(&mut scope, stmts));
+    }
+    let init = scope.into_func("<init>".to_owned(), Span::new(0, 0), 0, body);
+    for index in 0..resolver.consts.len() {
+        resolver.resolve_const(index);
+    }
+    for (module, span, body) in std::mem::take(&mut resolver.generators) {
+        resolver.with_module(module, |resolver| resolver.resolve_generator(span, body));
+    }
+
+    let mut funcs: Vec<_> = definitions
+        .iter()
+        .enumerate()
+        .map(|(index, &(ref definition, is_extern))| {
+            if !is_extern {
+                let name = resolver.qualified(definition.module, definition.name);
+                return Some(resolver.resolve_func(definition, index, name));
+            }
+            let signature = &resolver.signatures[index];
+            Some(Func {
+                name: definition.name.to_owned(),
+                span: definition.span,
+                num_params: signature.params.len(),
+                ret_ty: signature.ret_ty.clone(),
+                vars: signature.params.clone(),
+                boxed: vec![false; signature.params.len()],
+                captures: None,
+                body: Vec::new(),
+                external: true,
+            })
+        })
+        .collect();
+    for (index, definition, ty, name) in method_definitions {
+        let func = resolver.with_type_params(&["Self"], &[ty], |resolver| {
+            resolver.resolve_func(&definition, index, name)
+        });
+        funcs.push(Some(func));
+    }
+    while let Some((index, generic, type_args)) = resolver.pending_instances.pop() {
+        let definition = resolver.generic_funcs[generic].clone();
+        let name = resolver.qualified(definition.module, definition.name);
+        let name = format!("{name}{type_args:?}");
+        let func = resolver.with_type_params(&definition.type_params, &type_args, |resolver| {
+            resolver.resolve_func(&definition, index, name)
+        });
+        if funcs.len() <= index {
+            funcs.resize_with(index + 1, || None);
+        }
+        funcs[index] = Some(func);
+    }
+    for (index, func) in std::mem::take(&mut resolver.comptime) {
+        if funcs.len() <= index {
+            funcs.resize_with(index + 1, || None);
+        }
+        funcs[index] = Some(func);
+    }
+    let funcs = funcs.into_iter().map(Option::unwrap).collect();
+
+    // `main` is the one of the main module, which comes last.
+    let main = resolver.names.funcs.get("main").copied().filter(|&index| {
+        let signature = &resolver.signatures[index];
+        signature.params.is_empty() && signature.ret_ty == Ty::Int
+    });
+    let program = Program {
+        structs: resolver.structs,
+        enums: resolver.enums,
+        impls: resolver.impls,
+        funcs,
+        lambdas: resolver.lambdas,
+        globals: resolver.globals,
+        init,
+        main,
+    };
+    (program, resolver.index, resolver.comptime_roots)
+}
+
+pub fn symbols(ast: &Ast) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for stmt in &ast.stmts {
+        let unmarked = |id: NodeId| module::public(ast, id).unwrap_or(id);
+        let stmt = &match stmt {
+            pre_ast::Stmt::Block {
+                antecedent: Some(antecedent),
+                stmts,
+            } => pre_ast::Stmt::Block {
+                antecedent: Some(unmarked(*antecedent)),
+                stmts: stmts.clone(),
+            },
+            pre_ast::Stmt::Term(Some(term)) => pre_ast::Stmt::Term(Some(unmarked(*term))),
+            stmt => stmt.clone(),
+        };
+        let definition = extern_definition(ast, stmt).or_else(|| function_definition(ast, stmt));
+        if let Some(definition) = definition {
+            symbols.push(Symbol {
+                name: definition.name.to_owned(),
+                kind: SymbolKind::Func,
+                span: definition.span,
+            });
+            continue;
+        }
+        match stmt {
+            pre_ast::Stmt::Term(Some(term))
+            | pre_ast::Stmt::Block {
+                antecedent: Some(term),
+                ..
+            } if matches!(ast[*term].kind, TermKind::Assignment { .. }) => {
+                let TermKind::Assignment {
+                    left_hand_side: Some(left_hand_side),
+                    operator: Operator::Assign,
+                    ..
+                } = &ast[*term].kind
+                else {
+                    continue;
+                };
+                let (left_hand_side, kind) = match &ast[unmarked(*left_hand_side)].kind {
+                    TermKind::Prefixed { prefix, term } if prefix == "const" => {
+                        (*term, SymbolKind::Const)
+                    }
+                    _ => (unmarked(*left_hand_side), SymbolKind::Global),
+                };
+                if let TermKind::Declaration {
+                    term: Some(name), ..
+                } = &ast[left_hand_side].kind
+                {
+                    let name = &ast[*name];
+                    if let TermKind::Identifier(global) = &name.kind {
+                        symbols.push(Symbol {
+                            name: global.to_string(),
+                            kind,
+                            span: name.span,
+                        });
+                    }
+                }
+            }
+            pre_ast::Stmt::Block {
+                antecedent: Some(antecedent),
+                ..
+            } => {
+                let TermKind::Prefixed { prefix, term } = &ast[*antecedent].kind else {
+                    continue;
+                };
+                let term = &ast[*term];
+                let kind = match prefix.as_str() {
+                    "struct" => SymbolKind::Struct,
+                    "enum" => SymbolKind::Enum,
+                    "trait" => SymbolKind::Trait,
+                    _ => continue,
+                };
+                if let Some((name, _)) = generic_name(ast, term) {
+                    symbols.push(Symbol {
+                        name: name.to_owned(),
+                        kind,
+                        span: term.span,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    symbols
+}
+
+fn type_definition<'a>(
+    ast: &'a Ast,
+    stmt: &'a pre_ast::Stmt,
+    keyword: &str,
+) -> Option<(&'a str, Vec<&'a str>, &'a [pre_ast::Stmt])> {
+    let pre_ast::Stmt::Block {
+        antecedent: Some(antecedent),
+        stmts,
+    } = stmt
+    else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*antecedent].kind else {
+        return None;
+    };
+    let (name, type_params) = generic_name(ast, &ast[*term])?;
+    (prefix == keyword).then_some((name, type_params, stmts))
+}
+
+fn generic_name<'a>(ast: &'a Ast, term: &'a Term) -> Option<(&'a str, Vec<&'a str>)> {
+    match &term.kind {
+        TermKind::Identifier(name) => Some((name.as_str(), Vec::new())),
+        TermKind::Bracketed {
+            antecedent: Some(name),
+            bracket_kind: BracketKind::Square,
+            elements,
+            ..
+        } => {
+            let TermKind::Identifier(name) = &ast[*name].kind else {
+                return None;
+            };
+            let type_params = elements
+                .iter()
+                .map(|param| match param.map(|param| &ast[param].kind) {
+                    Some(TermKind::Identifier(param)) => param.as_str(),
+                    _ => panic!("invalid type parameter of `{name}`"),
+                })
+                .collect();
+            Some((name.as_str(), type_params))
+        }
+        _ => None,
+    }
+}
+
+fn struct_fields<'a>(
+    ast: &'a Ast,
+    name: &str,
+    body: &'a [pre_ast::Stmt],
+) -> Vec<(&'a str, &'a Term)> {
+    body.iter()
+        .map(|stmt| {
+            let kind = match stmt {
+                pre_ast::Stmt::Term(Some(term)) => &ast[*term].kind,
+                _ => panic!("invalid field of `{name}`"),
+            };
+            let TermKind::Declaration {
+                term: Some(field),
+                ty: Some(ty),
+            } = kind
+            else {
+                panic!("invalid field of `{name}`");
+            };
+            let TermKind::Identifier(field) = &ast[*field].kind else {
+                panic!("invalid field of `{name}`");
+            };
+            (field.as_str(), &ast[*ty])
+        })
+        .collect()
+}
+
+fn function_definition<'a>(ast: &'a Ast, stmt: &'a pre_ast::Stmt) -> Option<Definition<'a>> {
+    let pre_ast::Stmt::Block {
+        antecedent: Some(antecedent),
+        stmts,
+    } = stmt
+    else {
+        return None;
+    };
+    function_head(ast, &ast[*antecedent], stmts)
+}
+
+fn extern_definition<'a>(ast: &'a Ast, stmt: &pre_ast::Stmt) -> Option<Definition<'a>> {
+    let pre_ast::Stmt::Term(Some(term)) = stmt else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*term].kind else {
+        return None;
+    };
+    if prefix != "extern" {
+        return None;
+    }
+    let head = match &ast[*term].kind {
+        TermKind::Prefixed { prefix, term } if prefix == "fn" => &ast[*term],
+        _ => panic!("expected `extern fn`"),
+    };
+    let definition = function_head(ast, head, &[]).expect("invalid extern function");
+    Some(definition)
+}
+
+fn function_head<'a>(
+    ast: &'a Ast,
+    antecedent: &'a Term,
+    body: &'a [pre_ast::Stmt],
+) -> Option<Definition<'a>> {
+    let (head, ret_ty) = match &antecedent.kind {
+        TermKind::Declaration {
+            term: Some(term),
+            ty,
+        } => (&ast[*term], ty.map(|ty| &ast[ty])),
+        _ => (antecedent, None),
+    };
+    let TermKind::Bracketed {
+        antecedent: Some(name),
+        bracket_kind: BracketKind::Round,
+        elements,
+        ..
+    } = &head.kind
+    else {
+        return None;
+    };
+    let name = &ast[*name];
+    let span = name.span;
+    let (name, type_params) = generic_name(ast, name)?;
+    if KEYWORDS.contains(&name) {
+        return None;
+    }
+    Some(Definition {
+        name,
+        span,
+        type_params,
+        params: elements,
+        ret_ty,
+        body,
+        module: 0,
+    })
+}
+
+fn global_name<'a>(ast: &'a Ast, stmt: &pre_ast::Stmt) -> Option<&'a str> {
+    let (pre_ast::Stmt::Term(Some(term))
+    | pre_ast::Stmt::Block {
+        antecedent: Some(term),
+        ..
+    }) = stmt
+    else {
+        return None;
+    };
+    let TermKind::Assignment {
+        left_hand_side: Some(left_hand_side),
+        operator: Operator::Assign,
+        ..
+    } = &ast[*term].kind
+    else {
+        return None;
+    };
+    let TermKind::Declaration {
+        term: Some(name), ..
+    } = &ast[*left_hand_side].kind
+    else {
+        return None;
+    };
+    match &ast[*name].kind {
+        TermKind::Identifier(name) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+fn const_definition<'a>(ast: &'a Ast, stmt: &'a pre_ast::Stmt) -> Option<Const<'a>> {
+    let (term, body) = match stmt {
+        pre_ast::Stmt::Term(Some(term)) => (term, None),
+        pre_ast::Stmt::Block {
+            antecedent: Some(antecedent),
+            stmts,
+        } => (antecedent, Some(stmts.as_slice())),
+        _ => return None,
+    };
+    let TermKind::Assignment {
+        left_hand_side: Some(left_hand_side),
+        operator: Operator::Assign,
+        right_hand_side: Some(value),
+    } = &ast[*term].kind
+    else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*left_hand_side].kind else {
+        return None;
+    };
+    if prefix != "const" {
+        return None;
+    }
+    span::at(ast[*left_hand_side].span);
+    let TermKind::Declaration {
+        term: Some(name),
+        ty,
+    } = &ast[*term].kind
+    else {
+        panic!("expected `const NAME := VALUE`");
+    };
+    let name = &ast[*name];
+    let TermKind::Identifier(identifier) = &name.kind else {
+        panic!("expected `const NAME := VALUE`");
+    };
+    let value = &ast[*value];
+    assert!(
+        body.is_none() || matches!(&value.kind, TermKind::Identifier(name) if name == "comptime"),
+        "expected `const NAME := VALUE`"
+    );
+    Some(Const {
+        name: identifier.as_str(),
+        span: name.span,
+        module: 0,
+        ty: ty.map(|ty| &ast[ty]),
+        value,
+        body,
+        resolved: None,
+        resolving: false,
+    })
+}
+
+// A `comptime` block at the top level, which generates code.
+fn generator<'a>(ast: &'a Ast, stmt: &'a pre_ast::Stmt) -> Option<(Span, &'a [pre_ast::Stmt])> {
+    let pre_ast::Stmt::Block {
+        antecedent: Some(antecedent),
+        stmts,
+    } = stmt
+    else {
+        return None;
+    };
+    match &ast[*antecedent].kind {
+        TermKind::Identifier(name) if name == "comptime" => Some((ast[*antecedent].span, stmts)),
+        _ => None,
+    }
+}
+
+fn impl_definition<'a>(
+    ast: &'a Ast,
+    stmt: &'a pre_ast::Stmt,
+) -> Option<(&'a str, &'a Term, &'a [pre_ast::Stmt])> {
+    let pre_ast::Stmt::Block {
+        antecedent: Some(antecedent),
+        stmts,
+    } = stmt
+    else {
+        return None;
+    };
+    let TermKind::Prefixed { prefix, term } = &ast[*antecedent].kind else {
+        return None;
+    };
+    if prefix != "impl" {
+        return None;
+    }
+    let TermKind::Prefixed {
+        prefix: trait_name,
+        term,
+    } = &ast[*term].kind
+    else {
+        panic!("expected `impl Trait for Type`");
+    };
+    match &ast[*term].kind {
+        TermKind::Prefixed { prefix, term } if prefix == "for" => {
+            Some((trait_name.as_str(), &ast[*term], stmts))
+        }
+        _ => panic!("expected `impl Trait for Type`"),
+    }
+}
+
+fn mentions_self(ast: &Ast, term: &Term) -> bool {
+    match &term.kind {
+        TermKind::Identifier(name) => name == "Self",
+        TermKind::Declaration { .. } | TermKind::Bracketed { .. } => term
+            .kind
+            .children()
+            .into_iter()
+            .any(|child| mentions_self(ast, &ast[child])),
+        _ => false,
+    }
+}
+
+fn operator_method(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Add => "add",
+        Operator::Sub => "sub",
+        Operator::Mul => "mul",
+        Operator::Div => "div",
+        Operator::Rem => "rem",
+        Operator::Equal => "eq",
+        Operator::NotEqual => "ne",
+        Operator::Greater => "gt",
+        Operator::GreaterEqual => "ge",
+        Operator::Less => "lt",
+        Operator::LessEqual => "le",
+        Operator::Assign => unreachable!(),
+    }
+}
+
+fn antecedent_span(ast: &Ast, term: &Term) -> Span {
+    match &term.kind {
+        TermKind::Bracketed {
+            antecedent: Some(antecedent),
+            ..
+        } => ast[*antecedent].span,
+        _ => term.span,
+    }
+}
+
+fn generic_instantiation<'a>(
+    ast: &'a Ast,
+    term: &'a Term,
+) -> Option<(&'a str, &'a [Option<NodeId>])> {
+    let TermKind::Bracketed {
+        antecedent: Some(name),
+        bracket_kind: BracketKind::Square,
+        elements,
+        ..
+    } = &term.kind
+    else {
+        return None;
+    };
+    let TermKind::Identifier(name) = &ast[*name].kind else {
+        return None;
+    };
+    Some((name.as_str(), elements))
+}
+
+fn lambda_head<'a>(ast: &'a Ast, term: &'a Term) -> Option<LambdaHead<'a>> {
+    let (head, ret_ty) = match &term.kind {
+        TermKind::Declaration {
+            term: Some(term),
+            ty,
+        } => (&ast[*term], ty.map(|ty| &ast[ty])),
+        _ => (term, None),
+    };
+    let TermKind::Bracketed {
+        antecedent,
+        bracket_kind: BracketKind::Round,
+        elements,
+        ..
+    } = &head.kind
+    else {
+        return None;
+    };
+    let captures = match antecedent.map(|antecedent| &ast[antecedent].kind) {
+        None => &[][..],
+        Some(TermKind::Bracketed {
+            antecedent: None,
+            bracket_kind: BracketKind::Square,
+            elements,
+            ..
+        }) => elements,
+        Some(_) => return None,
+    };
+    Some(LambdaHead {
+        captures,
+        params: elements,
+        ret_ty,
+    })
+}
+
+fn value_block_head<'a>(ast: &'a Ast, antecedent: &'a Term) -> &'a Term {
+    match &antecedent.kind {
+        TermKind::Assignment {
+            right_hand_side: Some(right_hand_side),
+            ..
+        } => &ast[*right_hand_side],
+        _ => antecedent,
+    }
+}
+
+fn collect_ref_captures(ast: &Ast, stmts: &[pre_ast::Stmt], names: &mut HashSet<String>) {
+    for stmt in stmts {
+        match stmt {
+            pre_ast::Stmt::Term(term) => {
+                if let Some(term) = term {
+                    collect_ref_captures_in_term(ast, &ast[*term], names);
+                }
+            }
+            pre_ast::Stmt::Block { antecedent, stmts } => {
+                if let Some(antecedent) = antecedent {
+                    let antecedent = &ast[*antecedent];
+                    if let Some(head) = lambda_head(ast, value_block_head(ast, antecedent)) {
+                        names.extend(
+                            head.captures
+                                .iter()
+                                .map(|capture| capture_name(ast, capture)),
+                        );
+                    }
+                    collect_ref_captures_in_term(ast, antecedent, names);
+                }
+                collect_ref_captures(ast, stmts, names);
+            }
+            pre_ast::Stmt::Operator(_) => {}
+        }
+    }
+}
+
+fn collect_ref_captures_in_term(ast: &Ast, term: &Term, names: &mut HashSet<String>) {
+    let TermKind::Block { antecedent, stmts } = &term.kind else {
+        for child in term.kind.children() {
+            collect_ref_captures_in_term(ast, &ast[child], names);
+        }
+        return;
+    };
+    if let Some(antecedent) = antecedent {
+        let antecedent = &ast[*antecedent];
+        if let Some(head) = lambda_head(ast, antecedent) {
+            names.extend(
+                head.captures
+                    .iter()
+                    .map(|capture| capture_name(ast, capture)),
+            );
+        }
+        collect_ref_captures_in_term(ast, antecedent, names);
+    }
+    collect_ref_captures(ast, stmts, names);
+}
+
+fn capture_name(ast: &Ast, capture: &Option<NodeId>) -> String {
+    match capture.map(|capture| &ast[capture].kind) {
+        Some(TermKind::Identifier(name)) => name.to_string(),
+        _ => panic!("invalid capture"),
+    }
+}
+
+fn round_bracket<'a>(
+    ast: &'a Ast,
+    elements: &[Option<NodeId>],
+    has_trailing_comma: bool,
+) -> Bracket<'a> {
+    let mut elements: Vec<_> = elements
+        .iter()
+        .map(|element| &ast[element.expect("empty element in brackets")])
+        .collect();
+    if elements.len() == 1 && !has_trailing_comma {
+        Bracket::Group(elements.pop().unwrap())
+    } else {
+        Bracket::Tuple(elements)
+    }
+}
+
+fn zero(ty: &Ty) -> Expr {
+    match ty {
+        Ty::Int => Expr::Integer(0),
+        Ty::Float => Expr::Float(0.),
+        Ty::Bool => Expr::Boolean(false),
+        Ty::String => Expr::String(String::new()),
+        Ty::Tuple(tys) => Expr::Tuple(tys.iter().map(zero).collect()),
+        Ty::Array(elem_ty) => Expr::Array((**elem_ty).clone(), Vec::new()),
+        Ty::Ptr | Ty::Struct(_) | Ty::Enum(_) | Ty::Func(..) | Ty::Trait(_) => {
+            panic!("a variable of this type must be initialized")
+        }
+    }
+}
+
+fn single_element<'a>(
+    ast: &'a Ast,
+    elements: &[Option<NodeId>],
+    has_trailing_comma: bool,
+) -> &'a Term {
+    match elements {
+        [Some(element)] if !has_trailing_comma => &ast[*element],
+        _ => panic!("expected exactly one element in brackets"),
+    }
+}
+
+impl Scope {
+    fn new(ast: &Ast, ret_ty: Ty, is_init: bool, body: &[pre_ast::Stmt]) -> Scope {
+        let mut by_ref = HashSet::new();
+        collect_ref_captures(ast, body, &mut by_ref);
+        Scope {
+            vars: Vec::new(),
+            spans: Vec::new(),
+            boxed: Vec::new(),
+            names: if is_init {
+                Vec::new()
+            } else {
+                vec![HashMap::new()]
+            },
+            ret_ty,
+            is_init,
+            by_ref,
+            captures: None,
+            ref_captures: HashSet::new(),
+        }
+    }
+    fn into_func(self, name: String, span: Span, num_params: usize, body: Vec<Stmt>) -> Func {
+        Func {
+            name,
+            span,
+            num_params,
+            ret_ty: self.ret_ty,
+            vars: self.vars,
+            boxed: self.boxed,
+            captures: self.captures.map(|captures| {
+                captures
+                    .into_iter()
+                    .map(|(_, index, by_ref)| (index, by_ref))
+                    .collect()
+            }),
+            body,
+            external: false,
+        }
+    }
+    fn declare_local(&mut self, name: &str, ty: Ty, span: Span) -> usize {
+        let index = self.vars.len();
+        self.vars.push(ty);
+        self.spans.push(span);
+        self.boxed.push(self.by_ref.contains(name));
+        self.names
+            .last_mut()
+            .unwrap()
+            .insert(name.to_owned(), index);
+        index
+    }
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .rev()
+            .find_map(|names| names.get(name).copied())
+    }
+}
+
+impl<'a> Resolver<'a> {
+    fn term(&self, id: NodeId) -> &'a Term {
+        &self.ast[id]
+    }
+    fn enter_module(&mut self, module: usize) {
+        std::mem::swap(&mut self.names, &mut self.namespaces[self.module]);
+        self.module = module;
+        std::mem::swap(&mut self.names, &mut self.namespaces[module]);
+    }
+    fn with_module<R>(&mut self, module: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let prev = self.module;
+        self.enter_module(module);
+        let ret = f(self);
+        self.enter_module(prev);
+        ret
+    }
+    // Makes the `pub` items of the imported modules visible in the current one.
+    fn import(&mut self) {
+        fn merge<T: Clone + PartialEq>(
+            names: &mut HashMap<String, T>,
+            imported: &HashMap<String, T>,
+            public: &HashSet<String>,
+        ) {
+            for (name, item) in imported {
+                if public.contains(name) {
+                    let prev = names.insert(name.clone(), item.clone());
+                    assert!(
+                        prev.is_none() || prev.as_ref() == Some(item),
+                        "`{name}` is imported from more than one module"
+                    );
+                }
+            }
+        }
+        for &import in &self.modules[self.module].imports {
+            let (names, imported) = (&mut self.names, &self.namespaces[import]);
+            let public = &self.public[import];
+            merge(&mut names.structs, &imported.structs, public);
+            merge(
+                &mut names.generic_structs,
+                &imported.generic_structs,
+                public,
+            );
+            merge(&mut names.enums, &imported.enums, public);
+            merge(&mut names.variants, &imported.variants, public);
+            merge(&mut names.traits, &imported.traits, public);
+            merge(&mut names.funcs, &imported.funcs, public);
+            merge(&mut names.generic_funcs, &imported.generic_funcs, public);
+            merge(&mut names.globals, &imported.globals, public);
+            merge(&mut names.consts, &imported.consts, public);
+        }
+    }
+    // Functions of different modules may share a name, but not their symbols.
+    fn qualified(&self, module: usize, name: &str) -> String {
+        match &self.modules[module].name {
+            prefix if prefix.is_empty() => name.to_owned(),
+            prefix => format!("{prefix}::{name}"),
+        }
+    }
+    // Declares the items of the current module, leaving out the bodies of its
+    // functions and implementations. Returns the statements that initialize its
+    // globals.
+    fn collect(
+        &mut self,
+        definitions: &mut Vec<(Definition<'a>, bool)>,
+        impl_definitions: &mut Vec<(usize, &'a str, &'a Term, &'a [pre_ast::Stmt])>,
+    ) -> Vec<pre_ast::Stmt> {
+        let (ast, modules, module) = (self.ast, self.modules, self.module);
+        let items: Vec<_> = modules[module]
+            .stmts
+            .iter()
+            .zip(&modules[module].public)
+            .collect();
+        let type_definitions = |keyword| {
+            items
+                .iter()
+                .filter_map(|&(stmt, &public)| {
+                    let (name, type_params, body) = type_definition(ast, stmt, keyword)?;
+                    Some((name, type_params, body, public))
+                })
+                .collect::<Vec<_>>()
+        };
+        let struct_definitions = type_definitions("struct");
+        let enum_definitions = type_definitions("enum");
+        let mut plain_structs = Vec::new();
+        for (name, type_params, body, public) in struct_definitions {
+            let duplicate = self.names.structs.contains_key(name)
+                || self.names.generic_structs.contains_key(name);
+            assert!(!duplicate, "duplicate type `{name}`");
+            if public {
+                self.public[module].insert(name.to_owned());
+            }
+            if type_params.is_empty() {
+                self.names
+                    .structs
+                    .insert(name.to_owned(), self.structs.len());
+                plain_structs.push((self.structs.len(), name, body));
+                self.structs.push(StructDef {
+                    name: name.to_owned(),
+                    fields: Vec::new(),
+                });
+            } else {
+                self.names
+                    .generic_structs
+                    .insert(name.to_owned(), self.generic_structs.len());
+                self.generic_structs.push(GenericStruct {
+                    name,
+                    module,
+                    type_params,
+                    fields: struct_fields(ast, name, body),
+                });
+            }
+        }
+        let mut enum_indices = Vec::new();
+        for &(name, ref type_params, _, public) in &enum_definitions {
+            assert!(type_params.is_empty(), "generic enums are not supported");
+            let index = self.enums.len();
+            let prev = self.names.enums.insert(name.to_owned(), index);
+            assert!(
+                prev.is_none()
+                    && !self.names.structs.contains_key(name)
+                    && !self.names.generic_structs.contains_key(name),
+                "duplicate type `{name}`"
+            );
+            if public {
+                self.public[module].insert(name.to_owned());
+            }
+            enum_indices.push(index);
+            self.enums.push(EnumDef {
+                name: name.to_owned(),
+                variants: Vec::new(),
+            });
+        }
+        for (name, type_params, body, public) in type_definitions("trait") {
+            assert!(type_params.is_empty(), "generic traits are not supported");
+            let duplicate = self.names.structs.contains_key(name)
+                || self.names.generic_structs.contains_key(name)
+                || self.names.enums.contains_key(name)
+                || self.names.traits.contains_key(name);
+            assert!(!duplicate, "duplicate type `{name}`");
+            let methods: Vec<_> = body
+                .iter()
+                .map(|stmt| {
+                    let definition = match stmt {
+                        pre_ast::Stmt::Term(Some(head)) => function_head(ast, &ast[*head], &[]),
+                        _ => None,
+                    };
+                    let definition =
+                        definition.unwrap_or_else(|| panic!("invalid method of `{name}`"));
+                    assert!(
+                        definition.type_params.is_empty()
+                            && matches!(
+                                definition.params.first().map(|param| param.map(|param| &ast[param].kind)),
+                                Some(Some(TermKind::Identifier(param))) if param == "self"
+                            ),
+                        "method `{}` of `{name}` must take `self` first",
+                        definition.name
+                    );
+                    Definition {
+                        module,
+                        ..definition
+                    }
+                })
+                .collect();
+            if public {
+                self.public[module].insert(name.to_owned());
+            }
+            self.names.traits.insert(name.to_owned(), self.traits.len());
+            self.traits.push(TraitDef { name, methods });
+        }
+        for (index, name, body) in plain_structs {
+            let fields = struct_fields(ast, name, body)
+                .into_iter()
+                .map(|(field, ty)| (field.to_owned(), self.resolve_ty(ty)))
+                .collect();
+            self.structs[index].fields = fields;
+        }
+        for (index, &(name, _, body, public)) in enum_indices.into_iter().zip(&enum_definitions) {
+            let variants: Vec<_> = body
+                .iter()
+                .map(|stmt| {
+                    let pre_ast::Stmt::Term(Some(variant)) = stmt else {
+                        panic!("invalid variant of `{name}`");
+                    };
+                    match &ast[*variant].kind {
+                        TermKind::Identifier(variant) => (variant.to_string(), Vec::new()),
+                        TermKind::Bracketed {
+                            antecedent: Some(variant),
+                            bracket_kind: BracketKind::Round,
+                            elements,
+                            ..
+                        } => {
+                            let TermKind::Identifier(variant) = &ast[*variant].kind else {
+                                panic!("invalid variant of `{name}`");
+                            };
+                            let payload = elements
+                                .iter()
+                                .map(|ty| self.resolve_ty(&ast[ty.expect("empty payload")]))
+                                .collect();
+                            (variant.to_string(), payload)
+                        }
+                        _ => panic!("invalid variant of `{name}`"),
+                    }
+                })
+                .collect();
+            for (variant_index, (variant, _)) in variants.iter().enumerate() {
+                let prev = self
+                    .names
+                    .variants
+                    .insert(variant.clone(), (index, variant_index));
+                assert!(prev.is_none(), "duplicate variant `{variant}`");
+                if public {
+                    self.public[module].insert(variant.clone());
+                }
+            }
+            self.enums[index].variants = variants;
+        }
+
+        let mut init_stmts = Vec::new();
+        for (stmt, public) in items {
+            if let pre_ast::Stmt::Operator(decl) = stmt {
+                self.names.operators.insert(
+                    (symbol::Symbol::intern(&decl.symbol), decl.fixity),
+                    symbol::Symbol::intern(&decl.function),
+                );
+                continue;
+            }
+            if ["struct", "enum", "trait"]
+                .iter()
+                .any(|keyword| type_definition(ast, stmt, keyword).is_some())
+            {
+                continue;
+            }
+            if let Some((trait_name, ty, body)) = impl_definition(ast, stmt) {
+                assert!(!*public, "implementations cannot be `pub`");
+                impl_definitions.push((module, trait_name, ty, body));
+                continue;
+            }
+            if let Some((span, body)) = generator(ast, stmt) {
+                assert!(!*public, "`comptime` blocks cannot be `pub`");
+                self.generators.push((module, span, body));
+                continue;
+            }
+            if let Some(definition) = const_definition(ast, stmt) {
+                let name = definition.name;
+                assert!(
+                    !self.names.consts.contains_key(name),
+                    "duplicate constant `{name}`"
+                );
+                if *public {
+                    self.public[module].insert(name.to_owned());
+                }
+                self.names.consts.insert(name.to_owned(), self.consts.len());
+                self.consts.push(Const {
+                    module,
+                    ..definition
+                });
+                continue;
+            }
+            let extern_definition = extern_definition(ast, stmt);
+            let is_extern = extern_definition.is_some();
+            let Some(definition) = extern_definition.or_else(|| function_definition(ast, stmt))
+            else {
+                if *public {
+                    let name = global_name(ast, stmt).expect("only definitions can be `pub`");
+                    self.public[module].insert(name.to_owned());
+                }
+                init_stmts.push(stmt.clone());
+                continue;
+            };
+            let definition = Definition {
+                module,
+                ..definition
+            };
+            let duplicate = self.names.funcs.contains_key(definition.name)
+                || self.names.generic_funcs.contains_key(definition.name)
+                || self.names.structs.contains_key(definition.name)
+                || self.names.generic_structs.contains_key(definition.name)
+                || self.names.variants.contains_key(definition.name);
+            assert!(!duplicate, "duplicate function `{}`", definition.name);
+            if *public {
+                self.public[module].insert(definition.name.to_owned());
+            }
+            if definition.type_params.is_empty() {
+                let signature = self.resolve_signature(&definition);
+                if is_extern {
+                    let foreign = |ty: &Ty| {
+                        matches!(ty, Ty::Int | Ty::Float | Ty::Bool | Ty::String | Ty::Ptr)
+                    };
+                    span::enter(definition.span);
+                    assert!(
+                        signature.params.iter().all(foreign)
+                            && (foreign(&signature.ret_ty) || signature.ret_ty == Ty::unit()),
+                        "unsupported type in extern function `{}`",
+                        definition.name
+                    );
+                    span::leave();
+                }
+                let index = self.signatures.len();
+                self.define_func(&definition, index, &signature);
+                self.names.funcs.insert(definition.name.to_owned(), index);
+                self.signatures.push(signature);
+                definitions.push((definition, is_extern));
+            } else {
+                assert!(
+                    !is_extern,
+                    "extern function `{}` cannot be generic",
+                    definition.name
+                );
+                self.names
+                    .generic_funcs
+                    .insert(definition.name.to_owned(), self.generic_funcs.len());
+                self.generic_funcs.push(definition);
+            }
+        }
+        init_stmts
+    }
+    fn with_type_params<R>(
+        &mut self,
+        names: &[&str],
+        type_args: &[Ty],
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let type_params = names
+            .iter()
+            .map(|name| name.to_string())
+            .zip(type_args.iter().cloned())
+            .collect();
+        let prev = std::mem::replace(&mut self.type_params, type_params);
+        let ret = f(self);
+        self.type_params = prev;
+        ret
+    }
+    fn resolve_signature(&mut self, definition: &Definition) -> Signature {
+        self.with_module(definition.module, |resolver| {
+            resolver.resolve_signature_in_module(definition)
+        })
+    }
+    fn resolve_signature_in_module(&mut self, definition: &Definition) -> Signature {
+        let params = definition
+            .params
+            .iter()
+            .map(|param| match param.map(|param| &self.term(param).kind) {
+                Some(TermKind::Declaration {
+                    term: Some(_),
+                    ty: Some(ty),
+                }) => self.resolve_ty(self.term(*ty)),
+                Some(TermKind::Identifier(name)) if name == "self" => self
+                    .type_params
+                    .get("Self")
+                    .cloned()
+                    .expect("`self` outside of a method"),
+                _ => panic!("invalid parameter of `{}`", definition.name),
+            })
+            .collect();
+        let ret_ty = definition
+            .ret_ty
+            .map_or_else(Ty::unit, |ty| self.resolve_ty(ty));
+        Signature { params, ret_ty }
+    }
+    fn resolve_func(&mut self, definition: &Definition, index: usize, name: String) -> Func {
+        self.with_module(definition.module, |resolver| {
+            resolver.resolve_func_in_module(definition, index, name)
+        })
+    }
+    fn resolve_func_in_module(
+        &mut self,
+        definition: &Definition,
+        index: usize,
+        name: String,
+    ) -> Func {
+        span::enter(definition.span);
+        let signature = &self.signatures[index];
+        let num_params = signature.params.len();
+        let params = signature.params.clone();
+        let mut scope = Scope::new(self.ast, signature.ret_ty.clone(), false, definition.body);
+        for (param, ty) in definition.params.iter().zip(params) {
+            let param = self.term(param.unwrap());
+            let name = match &param.kind {
+                TermKind::Declaration {
+                    term: Some(name), ..
+                } => self.term(*name),
+                _ => param,
+            };
+            let span = name.span;
+            let TermKind::Identifier(name) = &name.kind else {
+                panic!("invalid parameter of `{}`", definition.name);
+            };
+            self.declare_local(&mut scope, name.as_str(), ty, span);
+        }
+        let body = self.resolve_block(&mut scope, definition.body);
+        assert!(
+            scope.ret_ty == Ty::unit() || simplify::diverges(&body),
+            "`{}` can reach its end without returning a value",
+            definition.name
+        );
+        span::leave();
+        scope.into_func(name, definition.span, num_params, body)
+    }
+    fn define_func(&mut self, definition: &Definition, index: usize, signature: &Signature) {
+        self.func_spans.insert(index, definition.span);
+        let ty = Ty::Func(signature.params.clone(), Box::new(signature.ret_ty.clone()));
+        self.reference(
+            definition.name,
+            ReferenceKind::Func,
+            definition.span,
+            definition.span,
+            &ty,
+        );
+    }
+    fn reference(
+        &mut self,
+        name: &str,
+        kind: ReferenceKind,
+        span: Span,
+        definition: Span,
+        ty: &Ty,
+    ) {
+        let ty = self.type_name(ty);
+        self.index.references.push(Reference {
+            name: name.to_owned(),
+            kind,
+            span,
+            definition,
+            ty,
+        });
+    }
+    fn func_reference(&mut self, name: &str, span: Span, index: usize) {
+        let signature = &self.signatures[index];
+        let ty = Ty::Func(signature.params.clone(), Box::new(signature.ret_ty.clone()));
+        let definition = self.func_spans[&index];
+        self.reference(name, ReferenceKind::Func, span, definition, &ty);
+    }
+    fn type_name(&self, ty: &Ty) -> String {
+        match ty {
+            Ty::Int => "int".to_owned(),
+            Ty::Float => "float".to_owned(),
+            Ty::Bool => "bool".to_owned(),
+            Ty::String => "string".to_owned(),
+            Ty::Ptr => "ptr".to_owned(),
+            Ty::Tuple(tys) if tys.len() == 1 => format!("({},)", self.type_name(&tys[0])),
+            Ty::Tuple(tys) => format!("({})", self.type_names(tys)),
+            Ty::Array(elem_ty) => format!("[{}]", self.type_name(elem_ty)),
+            Ty::Struct(index) => match self.struct_origins.get(index) {
+                Some((generic, type_args)) => format!(
+                    "{}[{}]",
+                    self.generic_structs[*generic].name,
+                    self.type_names(type_args)
+                ),
+                None => self.structs[*index].name.clone(),
+            },
+            Ty::Enum(index) => self.enums[*index].name.clone(),
+            Ty::Trait(index) => self.traits[*index].name.to_owned(),
+            Ty::Func(params, ret_ty) if **ret_ty == Ty::unit() => {
+                format!("fn({})", self.type_names(params))
+            }
+            Ty::Func(params, ret_ty) => format!(
+                "fn({}): {}",
+                self.type_names(params),
+                self.type_name(ret_ty)
+            ),
+        }
+    }
+    // Checks that a value has the type it needs, naming both if it doesn't.
+    fn expect_ty(&self, ty: &Ty, expected: &Ty, message: impl FnOnce() -> String) {
+        assert!(
+            ty == expected,
+            "{}: expected `{}`, found `{}`",
+            message(),
+            self.type_name(expected),
+            self.type_name(ty)
+        );
+    }
+    fn type_names(&self, tys: &[Ty]) -> String {
+        tys.iter()
+            .map(|ty| self.type_name(ty))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    fn instantiate_struct(&mut self, generic: usize, type_args: Vec<Ty>) -> usize {
+        let key = (generic, type_args);
+        if let Some(&index) = self.struct_instances.get(&key) {
+            return index;
+        }
+        let (_, type_args) = &key;
+        let def = self.generic_structs[generic].clone();
+        assert!(
+            def.type_params.len() == type_args.len(),
+            "wrong number of type arguments to `{}`: expected {}, found {}",
+            def.name,
+            def.type_params.len(),
+            type_args.len()
+        );
+        let index = self.structs.len();
+        self.structs.push(StructDef {
+            name: format!("{}{type_args:?}", def.name),
+            fields: Vec::new(),
+        });
+        self.struct_origins.insert(index, key.clone());
+        self.struct_instances.insert(key.clone(), index);
+        let fields = self.with_module(def.module, |resolver| {
+            resolver.with_type_params(&def.type_params, type_args, |resolver| {
+                def.fields
+                    .iter()
+                    .map(|&(field, ty)| (field.to_owned(), resolver.resolve_ty(ty)))
+                    .collect()
+            })
+        });
+        self.structs[index].fields = fields;
+        index
+    }
+    fn instantiate_func(&mut self, generic: usize, type_args: Vec<Ty>) -> usize {
+        let key = (generic, type_args);
+        if let Some(&index) = self.func_instances.get(&key) {
+            return index;
+        }
+        let (_, type_args) = &key;
+        let definition = self.generic_funcs[generic].clone();
+        assert!(
+            definition.type_params.len() == type_args.len(),
+            "wrong number of type arguments to `{}`: expected {}, found {}",
+            definition.name,
+            definition.type_params.len(),
+            type_args.len()
+        );
+        let signature = self.with_type_params(&definition.type_params, type_args, |resolver| {
+            resolver.resolve_signature(&definition)
+        });
+        let index = self.signatures.len();
+        self.signatures.push(signature);
+        self.func_spans.insert(index, definition.span);
+        self.pending_instances
+            .push((index, generic, type_args.clone()));
+        self.func_instances.insert(key, index);
+        index
+    }
+    fn resolve_type_args(&mut self, elements: &[Option<NodeId>]) -> Vec<Ty> {
+        elements
+            .iter()
+            .map(|ty| self.resolve_ty(self.term(ty.expect("empty type argument"))))
+            .collect()
+    }
+    fn infer_type_args(
+        &self,
+        term: &Term,
+        ty: &Ty,
+        type_params: &[&str],
+        bindings: &mut [Option<Ty>],
+    ) {
+        match (&term.kind, ty) {
+            (TermKind::Identifier(name), _) => {
+                if let Some(position) = type_params.iter().position(|param| name == param) {
+                    match &bindings[position] {
+                        Some(bound) => assert!(
+                            bound == ty,
+                            "conflicting types `{}` and `{}` for type parameter `{name}`",
+                            self.type_name(bound),
+                            self.type_name(ty)
+                        ),
+                        None => bindings[position] = Some(ty.clone()),
+                    }
+                }
+            }
+            (
+                TermKind::Bracketed {
+                    antecedent: None,
+                    bracket_kind: BracketKind::Square,
+                    elements,
+                    has_trailing_comma,
+                },
+                Ty::Array(elem_ty),
+            ) => {
+                let elem = single_element(self.ast, elements, *has_trailing_comma);
+                self.infer_type_args(elem, elem_ty, type_params, bindings);
+            }
+            (
+                TermKind::Bracketed {
+                    antecedent: None,
+                    bracket_kind: BracketKind::Round,
+                    elements,
+                    has_trailing_comma,
+                },
+                _,
+            ) => match (round_bracket(self.ast, elements, *has_trailing_comma), ty) {
+                (Bracket::Group(term), _) => self.infer_type_args(term, ty, type_params, bindings),
+                (Bracket::Tuple(terms), Ty::Tuple(tys)) => {
+                    for (term, ty) in terms.into_iter().zip(tys) {
+                        self.infer_type_args(term, ty, type_params, bindings);
+                    }
+                }
+                _ => {}
+            },
+            (
+                TermKind::Bracketed {
+                    antecedent: Some(name),
+                    bracket_kind: BracketKind::Round,
+                    elements,
+                    ..
+                },
+                Ty::Func(params, _),
+            ) if matches!(&self.term(*name).kind, TermKind::Identifier(name) if name == "fn") => {
+                for (term, ty) in elements.iter().zip(params) {
+                    let term = self.term(term.expect("empty parameter type"));
+                    self.infer_type_args(term, ty, type_params, bindings);
+                }
+            }
+            (
+                TermKind::Declaration {
+                    term: Some(func),
+                    ty: Some(ret_ty),
+                },
+                Ty::Func(_, func_ret_ty),
+            ) => {
+                self.infer_type_args(self.term(*func), ty, type_params, bindings);
+                self.infer_type_args(self.term(*ret_ty), func_ret_ty, type_params, bindings);
+            }
+            (_, Ty::Struct(index)) => {
+                let Some((name, elements)) = generic_instantiation(self.ast, term) else {
+                    return;
+                };
+                let Some(&(generic, ref type_args)) = self.struct_origins.get(index) else {
+                    return;
+                };
+                if self.names.generic_structs.get(name) != Some(&generic) {
+                    return;
+                }
+                for (term, ty) in elements.iter().zip(type_args) {
+                    let term = self.term(term.expect("empty type argument"));
+                    self.infer_type_args(term, ty, type_params, bindings);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn resolve_ty(&mut self, term: &Term) -> Ty {
+        span::enter(term.span);
+        let ty = self.resolve_ty_kind(term);
+        span::leave();
+        ty
+    }
+    fn resolve_ty_kind(&mut self, term: &Term) -> Ty {
+        if let TermKind::Identifier(name) = &term.kind {
+            let name = name.as_str();
+            if let Some(ty) = self.type_params.get(name) {
+                return ty.clone();
+            }
+            assert!(
+                !self.names.generic_structs.contains_key(name),
+                "missing type arguments to `{name}`"
+            );
+        }
+        if let Some((name, elements)) = generic_instantiation(self.ast, term) {
+            if let Some(&generic) = self.names.generic_structs.get(name) {
+                let type_args = self.resolve_type_args(elements);
+                return Ty::Struct(self.instantiate_struct(generic, type_args));
+            }
+        }
+        match &term.kind {
+            TermKind::Identifier(name) => match name.as_str() {
+                "int" => Ty::Int,
+                "float" => Ty::Float,
+                "bool" => Ty::Bool,
+                "string" => Ty::String,
+                "ptr" => Ty::Ptr,
+                _ => {
+                    if let Some(&index) = self.names.structs.get(name.as_str()) {
+                        Ty::Struct(index)
+                    } else if let Some(&index) = self.names.enums.get(name.as_str()) {
+                        Ty::Enum(index)
+                    } else if let Some(&index) = self.names.traits.get(name.as_str()) {
+                        Ty::Trait(index)
+                    } else {
+                        panic!("unknown type `{name}`");
+                    }
+                }
+            },
+            TermKind::Bracketed {
+                antecedent: None,
+                bracket_kind: BracketKind::Round,
+                elements,
+                has_trailing_comma,
+            } => match round_bracket(self.ast, elements, *has_trailing_comma) {
+                Bracket::Group(term) => self.resolve_ty(term),
+                Bracket::Tuple(terms) => Ty::Tuple(
+                    terms
+                        .into_iter()
+                        .map(|term| self.resolve_ty(term))
+                        .collect(),
+                ),
+            },
+            TermKind::Bracketed {
+                antecedent: None,
+                bracket_kind: BracketKind::Square,
+                elements,
+                has_trailing_comma,
+            } => Ty::Array(Box::new(self.resolve_ty(single_element(
+                self.ast,
+                elements,
+                *has_trailing_comma,
+            )))),
+            TermKind::Bracketed {
+                antecedent: Some(antecedent),
+                bracket_kind: BracketKind::Round,
+                elements,
+                ..
+            } if matches!(&self.term(*antecedent).kind, TermKind::Identifier(name) if name == "fn") =>
+            {
+                let params = elements
+                    .iter()
+                    .map(|ty| self.resolve_ty(self.term(ty.expect("empty parameter type"))))
+                    .collect();
+                Ty::Func(params, Box::new(Ty::unit()))
+            }
+            TermKind::Declaration {
+                term: Some(func),
+                ty: Some(ret_ty),
+            } => {
+                let Ty::Func(params, _) = self.resolve_ty(self.term(*func)) else {
+                    panic!("invalid type");
+                };
+                Ty::Func(params, Box::new(self.resolve_ty(self.term(*ret_ty))))
+            }
+            _ => panic!("invalid type"),
+        }
+    }
+    fn lookup(&mut self, scope: &mut Scope, name: &str) -> Option<usize> {
+        if let Some(index) = scope.lookup(name) {
+            return Some(index);
+        }
+        scope.captures.as_ref()?;
+        let mut outer = self.outer_scopes.pop().unwrap();
+        let index = self.lookup(&mut outer, name).map(|outer_index| {
+            let by_ref = scope.ref_captures.contains(name);
+            let index = scope.vars.len();
+            scope.vars.push(outer.vars[outer_index].clone());
+            scope.spans.push(outer.spans[outer_index]);
+            scope.boxed.push(by_ref || scope.by_ref.contains(name));
+            scope.names[0].insert(name.to_owned(), index);
+            let captures = scope.captures.as_mut().unwrap();
+            captures.push((outer_index, index, by_ref));
+            index
+        });
+        self.outer_scopes.push(outer);
+        index
+    }
+    fn declare(&mut self, scope: &mut Scope, name: &str, ty: Ty, span: Span) -> Pattern {
+        if scope.is_init && scope.names.len() == 1 {
+            self.reference(name, ReferenceKind::Global, span, span, &ty);
+            let index = self.globals.len();
+            self.globals.push(ty);
+            self.global_spans.push(span);
+            self.names.globals.insert(name.to_owned(), index);
+            Pattern::Global(index)
+        } else {
+            Pattern::Variable(self.declare_local(scope, name, ty, span))
+        }
+    }
+    fn declare_local(&mut self, scope: &mut Scope, name: &str, ty: Ty, span: Span) -> usize {
+        self.reference(name, ReferenceKind::Variable, span, span, &ty);
+        scope.declare_local(name, ty, span)
+    }
+    fn resolve_block(&mut self, scope: &mut Scope, stmts: &[pre_ast::Stmt]) -> Vec<Stmt> {
+        scope.names.push(HashMap::new());
+        let ret = self.resolve_stmts(scope, stmts);
+        scope.names.pop();
+        ret
+    }
+    fn resolve_stmts(&mut self, scope: &mut Scope, stmts: &[pre_ast::Stmt]) -> Vec<Stmt> {
+        let mut ret = Vec::new();
+        let mut stmts = stmts.iter().peekable();
+        while let Some(stmt) = stmts.next() {
+            match stmt {
+                pre_ast::Stmt::Term(None) => {}
+                pre_ast::Stmt::Term(Some(term)) => {
+                    ret.push(self.resolve_stmt(scope, self.term(*term)))
+                }
+                pre_ast::Stmt::Operator(decl) => {
+                    panic!(
+                        "operator `{}` must be declared at the top level",
+                        decl.symbol
+                    )
+                }
+                pre_ast::Stmt::Block {
+                    antecedent: None,
+                    stmts,
+                } => ret.push(Stmt::Block(self.resolve_block(scope, stmts))),
+                pre_ast::Stmt::Block {
+                    antecedent: Some(antecedent),
+                    stmts: body,
+                } => {
+                    let antecedent = self.term(*antecedent);
+                    span::enter(antecedent.span);
+                    if let Some(stmt) = self.resolve_value_block_stmt(scope, antecedent, body) {
+                        span::leave();
+                        ret.push(stmt);
+                        continue;
+                    }
+                    let Some((keyword, condition)) = keyword_with_condition(self.ast, antecedent)
+                    else {
+                        panic!("unexpected block");
+                    };
+                    let condition = self.resolve_condition(scope, condition);
+                    let body = self.resolve_block(scope, body);
+                    match keyword {
+                        "if" => {
+                            let else_body = match stmts.peek() {
+                                Some(pre_ast::Stmt::Block {
+                                    antecedent: Some(antecedent),
+                                    stmts: else_body,
+                                }) if matches!(
+                                    &self.term(*antecedent).kind,
+                                    TermKind::Identifier(name) if name == "else"
+                                ) =>
+                                {
+                                    stmts.next();
+                                    self.resolve_block(scope, else_body)
+                                }
+                                _ => Vec::new(),
+                            };
+                            ret.push(Stmt::If(condition, body, else_body, antecedent.span));
+                        }
+                        "while" => ret.push(Stmt::While(condition, body, antecedent.span)),
+                        _ => panic!("unexpected block"),
+                    }
+                    span::leave();
+                }
+            }
+        }
+        ret
+    }
+    fn resolve_value_block_stmt(
+        &mut self,
+        scope: &mut Scope,
+        antecedent: &Term,
+        body: &[pre_ast::Stmt],
+    ) -> Option<Stmt> {
+        if let TermKind::Assignment {
+            left_hand_side: Some(left_hand_side),
+            operator: Operator::Assign,
+            right_hand_side: Some(right_hand_side),
+        } = &antecedent.kind
+        {
+            let (value, ty) = self.resolve_value_block(scope, self.term(*right_hand_side), body)?;
+            let pattern = self.resolve_pattern(scope, self.term(*left_hand_side), &ty);
+            Some(Stmt::Assign(pattern, value))
+        } else {
+            if matches!(&antecedent.kind, TermKind::Identifier(name) if name == "comptime") {
+                let (value, _) = self.resolve_comptime_block(antecedent.span, body);
+                return Some(Stmt::Expr(value));
+            }
+            let ("match", scrutinee) = keyword_with_condition(self.ast, antecedent)? else {
+                return None;
+            };
+            let (value, _) = self.resolve_match(scope, scrutinee, body, false);
+            Some(Stmt::Expr(value))
+        }
+    }
+    fn resolve_value_block(
+        &mut self,
+        scope: &mut Scope,
+        antecedent: &Term,
+        body: &[pre_ast::Stmt],
+    ) -> Option<(Expr, Ty)> {
+        if let Some(("match", scrutinee)) = keyword_with_condition(self.ast, antecedent) {
+            return Some(self.resolve_match(scope, scrutinee, body, true));
+        }
+        if matches!(&antecedent.kind, TermKind::Identifier(name) if name == "comptime") {
+            return Some(self.resolve_comptime_block(antecedent.span, body));
+        }
+        let head = lambda_head(self.ast, antecedent)?;
+        Some(self.resolve_lambda(scope, antecedent.span, &head, body))
+    }
+    fn resolve_lambda(
+        &mut self,
+        scope: &mut Scope,
+        span: Span,
+        head: &LambdaHead,
+        body: &[pre_ast::Stmt],
+    ) -> (Expr, Ty) {
+        let params: Vec<_> = head
+            .params
+            .iter()
+            .map(|param| match param.map(|param| &self.term(param).kind) {
+                Some(TermKind::Declaration {
+                    term: Some(name),
+                    ty: Some(ty),
+                }) => {
+                    let name = self.term(*name);
+                    let TermKind::Identifier(param) = &name.kind else {
+                        panic!("invalid parameter of lambda");
+                    };
+                    (param, name.span, self.resolve_ty(self.term(*ty)))
+                }
+                _ => panic!("invalid parameter of lambda"),
+            })
+            .collect();
+        let ret_ty = head.ret_ty.map_or_else(Ty::unit, |ty| self.resolve_ty(ty));
+        let mut lambda_scope = Scope::new(self.ast, ret_ty.clone(), false, body);
+        lambda_scope.captures = Some(Vec::new());
+        lambda_scope.ref_captures = head
+            .captures
+            .iter()
+            .map(|capture| capture_name(self.ast, capture))
+            .collect();
+        for &(name, span, ref ty) in &params {
+            self.declare_local(&mut lambda_scope, name.as_str(), ty.clone(), span);
+        }
+        let outer = std::mem::replace(scope, lambda_scope);
+        self.outer_scopes.push(outer);
+        for name in scope.ref_captures.clone() {
+            assert!(
+                self.lookup(scope, &name).is_some() || self.names.globals.contains_key(&name),
+                "undefined variable `{name}`"
+            );
+        }
+        let body = self.resolve_block(scope, body);
+        span::enter(span);
+        assert!(
+            scope.ret_ty == Ty::unit() || simplify::diverges(&body),
+            "lambda can reach its end without returning a value"
+        );
+        span::leave();
+        let outer = self.outer_scopes.pop().unwrap();
+        let lambda_scope = std::mem::replace(scope, outer);
+        let captured = lambda_scope
+            .captures
+            .iter()
+            .flatten()
+            .map(|&(outer_index, _, _)| outer_index)
+            .collect();
+        let index = self.lambdas.len();
+        self.lambdas.push(lambda_scope.into_func(
+            format!("lambda{index}"),
+            span,
+            params.len(),
+            body,
+        ));
+        let param_tys = params.into_iter().map(|(_, _, ty)| ty).collect();
+        (
+            Expr::Closure(index, captured),
+            Ty::Func(param_tys, Box::new(ret_ty)),
+        )
+    }
+    fn resolve_match(
+        &mut self,
+        scope: &mut Scope,
+        scrutinee: &Term,
+        arms: &[pre_ast::Stmt],
+        has_value: bool,
+    ) -> (Expr, Ty) {
+        let (scrutinee, scrutinee_ty) = self.resolve_expr(scope, scrutinee);
+        let Ty::Enum(enum_index) = scrutinee_ty else {
+            panic!(
+                "cannot match on a value of type `{}`",
+                self.type_name(&scrutinee_ty)
+            );
+        };
+        let num_variants = self.enums[enum_index].variants.len();
+        let mut covered = vec![false; num_variants];
+        let mut has_wildcard = false;
+        let mut value_ty = None;
+        let mut resolved_arms = Vec::new();
+        for arm in arms {
+            let pre_ast::Stmt::Block {
+                antecedent: Some(pattern),
+                stmts: body,
+            } = arm
+            else {
+                panic!("invalid match arm");
+            };
+            assert!(!has_wildcard, "unreachable match arm");
+            scope.names.push(HashMap::new());
+            let (variant, bindings) = match &self.term(*pattern).kind {
+                TermKind::Identifier(name) if name == "_" => {
+                    has_wildcard = true;
+                    (None, Vec::new())
+                }
+                TermKind::Identifier(name) => (
+                    Some(self.variant_index(enum_index, name.as_str())),
+                    Vec::new(),
+                ),
+                TermKind::Bracketed {
+                    antecedent: Some(name),
+                    bracket_kind: BracketKind::Round,
+                    elements,
+                    ..
+                } => {
+                    let TermKind::Identifier(name) = &self.term(*name).kind else {
+                        panic!("invalid match arm");
+                    };
+                    let variant = self.variant_index(enum_index, name.as_str());
+                    let payload = self.enums[enum_index].variants[variant].1.clone();
+                    assert!(
+                        elements.len() == payload.len(),
+                        "wrong number of bindings for `{name}`: expected {}, found {}",
+                        payload.len(),
+                        elements.len()
+                    );
+                    let bindings = elements
+                        .iter()
+                        .zip(payload)
+                        .map(
+                            |(binding, ty)| match binding.map(|binding| self.term(binding)) {
+                                Some(Term {
+                                    kind: TermKind::Identifier(name),
+                                    ..
+                                }) if name == "_" => Pattern::Discard,
+                                Some(Term {
+                                    kind: TermKind::Identifier(name),
+                                    span,
+                                }) => Pattern::Variable(self.declare_local(
+                                    scope,
+                                    name.as_str(),
+                                    ty,
+                                    *span,
+                                )),
+                                _ => panic!("invalid binding in match arm"),
+                            },
+                        )
+                        .collect();
+                    (Some(variant), bindings)
+                }
+                _ => panic!("invalid match arm"),
+            };
+            if let Some(variant) = variant {
+                assert!(!covered[variant], "unreachable match arm");
+                assert!(
+                    self.enums[enum_index].variants[variant].1.is_empty() || !bindings.is_empty(),
+                    "missing bindings for `{}`",
+                    self.enums[enum_index].variants[variant].0
+                );
+                covered[variant] = true;
+            }
+            let (body, value) = if has_value {
+                let Some((pre_ast::Stmt::Term(Some(value)), body)) = body.split_last() else {
+                    panic!("match arm must end with a value");
+                };
+                let body = self.resolve_stmts(scope, body);
+                let (value, ty) = self.resolve_expr(scope, self.term(*value));
+                match &value_ty {
+                    Some(value_ty) => self.expect_ty(&ty, value_ty, || {
+                        "mismatched types of match arms".to_owned()
+                    }),
+                    None => value_ty = Some(ty),
+                }
+                (body, value)
+            } else {
+                (self.resolve_stmts(scope, body), Expr::Tuple(Vec::new()))
+            };
+            scope.names.pop();
+            resolved_arms.push(Arm {
+                variant,
+                bindings,
+                body,
+                value,
+            });
+        }
+        if !has_wildcard {
+            let def = &self.enums[enum_index];
+            let missing: Vec<_> = def
+                .variants
+                .iter()
+                .zip(&covered)
+                .filter(|(_, &covered)| !covered)
+                .map(|((name, _), _)| format!("`{name}`"))
+                .collect();
+            assert!(
+                missing.is_empty(),
+                "non-exhaustive match: missing {}",
+                missing.join(", ")
+            );
+        }
+        let value_ty = value_ty.unwrap_or_else(Ty::unit);
+        (
+            Expr::Match(
+                enum_index,
+                Box::new(scrutinee),
+                resolved_arms,
+                value_ty.clone(),
+            ),
+            value_ty,
+        )
+    }
+    fn variant_index(&self, enum_index: usize, name: &str) -> usize {
+        let def = &self.enums[enum_index];
+        def.variants
+            .iter()
+            .position(|(variant, _)| variant == name)
+            .unwrap_or_else(|| panic!("no variant `{name}` in `{}`", def.name))
+    }
+    fn resolve_condition(&mut self, scope: &mut Scope, term: &Term) -> Expr {
+        let (condition, ty) = self.resolve_expr(scope, term);
+        self.expect_ty(&ty, &Ty::Bool, || "mismatched type of condition".to_owned());
+        condition
+    }
+    fn resolve_stmt(&mut self, scope: &mut Scope, term: &Term) -> Stmt {
+        span::enter(term.span);
+        let stmt = self.resolve_stmt_kind(scope, term);
+        span::leave();
+        stmt
+    }
+    fn resolve_stmt_kind(&mut self, scope: &mut Scope, term: &Term) -> Stmt {
+        match &term.kind {
+            TermKind::Assignment {
+                left_hand_side: Some(left_hand_side),
+                operator: Operator::Assign,
+                right_hand_side: Some(right_hand_side),
+            } => {
+                let (value, ty) = self.resolve_expr(scope, self.term(*right_hand_side));
+                let pattern = self.resolve_pattern(scope, self.term(*left_hand_side), &ty);
+                Stmt::Assign(pattern, value)
+            }
+            TermKind::Declaration {
+                term: Some(name),
+                ty: Some(ty),
+            } => {
+                let ty = self.resolve_ty(self.term(*ty));
+                let name = self.term(*name);
+                let span = name.span;
+                let TermKind::Identifier(name) = &name.kind else {
+                    panic!("invalid declaration");
+                };
+                let value = zero(&ty);
+                Stmt::Assign(self.declare(scope, name.as_str(), ty, span), value)
+            }
+            TermKind::Bracketed {
+                antecedent: Some(antecedent),
+                bracket_kind: BracketKind::Round,
+                elements,
+                has_trailing_comma,
+            } if matches!(&self.term(*antecedent).kind, TermKind::Identifier(name) if name == "return") =>
+            {
+                let value = self.resolve_bracket(scope, elements, *has_trailing_comma);
+                let (value, ty) = self.coerce(value, &scope.ret_ty);
+                self.expect_ty(&ty, &scope.ret_ty, || "mismatched return type".to_owned());
+                Stmt::Return(value, term.span)
+            }
+            _ => Stmt::Expr(self.resolve_expr(scope, term).0),
+        }
+    }
+    fn resolve_pattern(&mut self, scope: &mut Scope, term: &Term, ty: &Ty) -> Pattern {
+        span::enter(term.span);
+        let pattern = self.resolve_pattern_kind(scope, term, ty);
+        span::leave();
+        pattern
+    }
+    fn resolve_pattern_kind(&mut self, scope: &mut Scope, term: &Term, ty: &Ty) -> Pattern {
+        match &term.kind {
+            TermKind::Identifier(name) if name == "_" => Pattern::Discard,
+            TermKind::Identifier(name) => {
+                let name = name.as_str();
+                let module = self.macro_module(term);
+                let local = match module {
+                    Some(_) => None,
+                    None => self.lookup(scope, name),
+                };
+                let (global, is_const) =
+                    self.with_module(module.unwrap_or(self.module), |resolver| {
+                        (
+                            resolver.names.globals.get(name).copied(),
+                            resolver.names.consts.contains_key(name),
+                        )
+                    });
+                let (pattern, kind, definition, var_ty) = if let Some(index) = local {
+                    let ty = scope.vars[index].clone();
+                    let definition = scope.spans[index];
+                    (
+                        Pattern::Variable(index),
+                        ReferenceKind::Variable,
+                        definition,
+                        ty,
+                    )
+                } else if let Some(index) = global {
+                    assert!(
+                        !self.in_comptime,
+                        "global `{name}` cannot be used at compile time"
+                    );
+                    let ty = self.globals[index].clone();
+                    let definition = self.global_spans[index];
+                    (
+                        Pattern::Global(index),
+                        ReferenceKind::Global,
+                        definition,
+                        ty,
+                    )
+                } else if is_const {
+                    panic!("cannot assign to constant `{name}`");
+                } else {
+                    panic!("undefined variable `{name}`");
+                };
+                self.reference(name, kind, term.span, definition, &var_ty);
+                self.coerce_pattern(pattern, &var_ty, ty, || {
+                    format!("mismatched types in assignment to `{name}`")
+                })
+            }
+            TermKind::Declaration {
+                term: Some(name),
+                ty: declared_ty,
+            } => {
+                let name = self.term(*name);
+                let span = name.span;
+                let TermKind::Identifier(name) = &name.kind else {
+                    panic!("invalid declaration");
+                };
+                let Some(declared_ty) = declared_ty else {
+                    return self.declare(scope, name.as_str(), ty.clone(), span);
+                };
+                let declared_ty = self.resolve_ty(self.term(*declared_ty));
+                let pattern = self.declare(scope, name.as_str(), declared_ty.clone(), span);
+                self.coerce_pattern(pattern, &declared_ty, ty, || {
+                    format!("mismatched types in declaration of `{name}`")
+                })
+            }
+            TermKind::Bracketed {
+                antecedent: None,
+                bracket_kind: BracketKind::Round,
+                elements,
+                has_trailing_comma,
+            } => match round_bracket(self.ast, elements, *has_trailing_comma) {
+                Bracket::Group(term) => self.resolve_pattern(scope, term, ty),
+                Bracket::Tuple(terms) => {
+                    let Ty::Tuple(tys) = ty else {
+                        panic!("cannot destructure a non-tuple value");
+                    };
+                    assert!(
+                        terms.len() == tys.len(),
+                        "mismatched tuple length: expected {}, found {}",
+                        tys.len(),
+                        terms.len()
+                    );
+                    let patterns = terms
+                        .into_iter()
+                        .zip(tys)
+                        .map(|(term, ty)| self.resolve_pattern(scope, term, ty))
+                        .collect();
+                    Pattern::Tuple(patterns)
+                }
+            },
+            TermKind::Bracketed {
+                antecedent: Some(array),
+                bracket_kind: BracketKind::Square,
+                elements,
+                has_trailing_comma,
+            } => {
+                let index = single_element(self.ast, elements, *has_trailing_comma);
+                let (elem_ty, array, index) = self.resolve_index(scope, self.term(*array), index);
+                let pattern = Pattern::Index(elem_ty.clone(), array, index, term.span);
+                self.coerce_pattern(pattern, &elem_ty, ty, || {
+                    "mismatched types in assignment to element".to_string()
+                })
+            }
+            TermKind::MemberAccess {
+                antecedent: Some(record),
+                member,
+            } => {
+                let (struct_index, field_index, record) =
+                    self.resolve_field(scope, self.term(*record), member.as_str());
+                let field_ty = self.structs[struct_index].fields[field_index].1.clone();
+                let pattern = Pattern::Field(struct_index, field_index, record);
+                self.coerce_pattern(pattern, &field_ty, ty, || {
+                    format!("mismatched types in assignment to `{member}`")
+                })
+            }
+            _ => panic!("invalid left-hand side of assignment"),
+        }
+    }
+    fn resolve_bracket(
+        &mut self,
+        scope: &mut Scope,
+        elements: &[Option<NodeId>],
+        has_trailing_comma: bool,
+    ) -> (Expr, Ty) {
+        match round_bracket(self.ast, elements, has_trailing_comma) {
+            Bracket::Group(term) => self.resolve_expr(scope, term),
+            Bracket::Tuple(terms) => {
+                let (exprs, tys) = terms
+                    .into_iter()
+                    .map(|term| self.resolve_expr(scope, term))
+                    .unzip();
+                (Expr::Tuple(exprs), Ty::Tuple(tys))
+            }
+        }
+    }
+    fn resolve_index(&mut self, scope: &mut Scope, array: &Term, index: &Term) -> (Ty, Expr, Expr) {
+        let (array, array_ty) = self.resolve_expr(scope, array);
+        let Ty::Array(elem_ty) = array_ty else {
+            panic!(
+                "cannot index a value of type `{}`",
+                self.type_name(&array_ty)
+            );
+        };
+        let (index, index_ty) = self.resolve_expr(scope, index);
+        self.expect_ty(&index_ty, &Ty::Int, || {
+            "mismatched type of index".to_owned()
+        });
+        (*elem_ty, array, index)
+    }
+    fn resolve_args(
+        &mut self,
+        scope: &mut Scope,
+        elements: &[Option<NodeId>],
+        params: &[Ty],
+        name: &str,
+    ) -> Vec<Expr> {
+        let args = elements
+            .iter()
+            .map(|element| self.resolve_expr(scope, self.term(element.expect("empty argument"))))
+            .collect();
+        self.check_args(args, params, name)
+    }
+    fn field_index(&self, struct_index: usize, member: &str) -> usize {
+        let def = &self.structs[struct_index];
+        def.fields
+            .iter()
+            .position(|(name, _)| name == member)
+            .unwrap_or_else(|| panic!("no field `{member}` in `{}`", def.name))
+    }
+    fn resolve_field(
+        &mut self,
+        scope: &mut Scope,
+        record: &Term,
+        member: &str,
+    ) -> (usize, usize, Expr) {
+        let (record, record_ty) = self.resolve_expr(scope, record);
+        let Ty::Struct(struct_index) = record_ty else {
+            panic!(
+                "no field `{member}` in a value of type `{}`",
+                self.type_name(&record_ty)
+            );
+        };
+        (struct_index, self.field_index(struct_index, member), record)
+    }
+    fn resolve_struct_literal(
+        &mut self,
+        scope: &mut Scope,
+        struct_index: usize,
+        elements: &[Option<NodeId>],
+    ) -> (Expr, Ty) {
+        let def = &self.structs[struct_index];
+        let name = def.name.clone();
+        let field_names: Vec<_> = def.fields.iter().map(|(field, _)| field.clone()).collect();
+        let field_names: Vec<_> = field_names.iter().map(String::as_str).collect();
+        let values = self.resolve_fields(scope, &name, &field_names, elements);
+        self.struct_literal(struct_index, values)
+    }
+    fn resolve_generic_struct_literal(
+        &mut self,
+        scope: &mut Scope,
+        generic: usize,
+        elements: &[Option<NodeId>],
+    ) -> (Expr, Ty) {
+        let def = self.generic_structs[generic].clone();
+        let field_names: Vec<_> = def.fields.iter().map(|&(field, _)| field).collect();
+        let values = self.resolve_fields(scope, def.name, &field_names, elements);
+        let mut bindings = vec![None; def.type_params.len()];
+        for (&(_, field_ty), (_, ty)) in def.fields.iter().zip(&values) {
+            self.infer_type_args(field_ty, ty, &def.type_params, &mut bindings);
+        }
+        let type_args = infer_result(bindings, &def.type_params, def.name);
+        let struct_index = self.instantiate_struct(generic, type_args);
+        self.struct_literal(struct_index, values)
+    }
+    fn resolve_fields(
+        &mut self,
+        scope: &mut Scope,
+        name: &str,
+        field_names: &[&str],
+        elements: &[Option<NodeId>],
+    ) -> Vec<(Expr, Ty)> {
+        let mut fields: Vec<_> = field_names.iter().map(|_| None).collect();
+        for (position, element) in elements.iter().enumerate() {
+            let element = self.term(element.expect("empty field"));
+            let (field_index, value) = match &element.kind {
+                TermKind::Assignment {
+                    left_hand_side: Some(field),
+                    operator: Operator::Assign,
+                    right_hand_side: Some(value),
+                } => {
+                    let TermKind::Identifier(field) = &self.term(*field).kind else {
+                        panic!("invalid field initializer");
+                    };
+                    let field_index = field_names
+                        .iter()
+                        .position(|name| field == name)
+                        .unwrap_or_else(|| panic!("no field `{field}` in `{name}`"));
+                    (field_index, self.term(*value))
+                }
+                _ => (position, element),
+            };
+            assert!(field_index < fields.len(), "too many fields for `{name}`");
+            assert!(
+                fields[field_index].is_none(),
+                "field `{}` initialized twice",
+                field_names[field_index]
+            );
+            fields[field_index] = Some(self.resolve_expr(scope, value));
+        }
+        fields
+            .into_iter()
+            .zip(field_names)
+            .map(|(value, field)| {
+                value.unwrap_or_else(|| panic!("missing field `{field}` of `{name}`"))
+            })
+            .collect()
+    }
+    fn struct_literal(&self, struct_index: usize, values: Vec<(Expr, Ty)>) -> (Expr, Ty) {
+        let fields = values
+            .into_iter()
+            .zip(&self.structs[struct_index].fields)
+            .map(|(value, (field, field_ty))| {
+                let (value, ty) = self.coerce(value, field_ty);
+                self.expect_ty(&ty, field_ty, || {
+                    format!("mismatched type of field `{field}`")
+                });
+                value
+            })
+            .collect();
+        (Expr::Struct(struct_index, fields), Ty::Struct(struct_index))
+    }
+    fn check_args(&self, args: Vec<(Expr, Ty)>, params: &[Ty], name: &str) -> Vec<Expr> {
+        assert!(
+            args.len() == params.len(),
+            "wrong number of arguments to `{name}`: expected {}, found {}",
+            params.len(),
+            args.len()
+        );
+        args.into_iter()
+            .zip(params)
+            .map(|(arg, param_ty)| {
+                let (arg, arg_ty) = self.coerce(arg, param_ty);
+                self.expect_ty(&arg_ty, param_ty, || {
+                    format!("mismatched argument type to `{name}`")
+                });
+                arg
+            })
+            .collect()
+    }
+    fn coerce(&self, (value, ty): (Expr, Ty), expected: &Ty) -> (Expr, Ty) {
+        if ty == Ty::String && *expected == Ty::Ptr {
+            return (value, Ty::Ptr);
+        }
+        let Ty::Trait(trait_index) = *expected else {
+            return (value, ty);
+        };
+        if ty == *expected {
+            return (value, ty);
+        }
+        let Some(&impl_index) = self.impl_indices.get(&(trait_index, ty.clone())) else {
+            panic!(
+                "`{}` does not implement `{}`",
+                self.type_name(&ty),
+                self.traits[trait_index].name
+            );
+        };
+        (
+            Expr::TraitObject(impl_index, Box::new(value)),
+            expected.clone(),
+        )
+    }
+    fn coerce_pattern(
+        &self,
+        pattern: Pattern,
+        target_ty: &Ty,
+        ty: &Ty,
+        message: impl FnOnce() -> String,
+    ) -> Pattern {
+        if target_ty == ty {
+            return pattern;
+        }
+        let Ty::Trait(trait_index) = *target_ty else {
+            panic!(
+                "{}: expected `{}`, found `{}`",
+                message(),
+                self.type_name(target_ty),
+                self.type_name(ty)
+            );
+        };
+        let Some(&impl_index) = self.impl_indices.get(&(trait_index, ty.clone())) else {
+            panic!(
+                "`{}` does not implement `{}`",
+                self.type_name(ty),
+                self.traits[trait_index].name
+            );
+        };
+        Pattern::Coerce(impl_index, Box::new(pattern))
+    }
+    fn find_method(&self, ty: &Ty, method: &str) -> Option<usize> {
+        let mut found = self.impls.iter().filter_map(|def| {
+            if def.ty != *ty {
+                return None;
+            }
+            let position = self.traits[def.trait_index]
+                .methods
+                .iter()
+                .position(|definition| definition.name == method)?;
+            Some(def.methods[position])
+        });
+        let index = found.next()?;
+        assert!(
+            found.next().is_none(),
+            "ambiguous method `{method}` for `{}`",
+            self.type_name(ty)
+        );
+        Some(index)
+    }
+    fn resolve_method_call(
+        &mut self,
+        receiver: (Expr, Ty),
+        method: &str,
+        args: Vec<(Expr, Ty)>,
+    ) -> (Expr, Ty) {
+        if let Ty::Trait(trait_index) = receiver.1 {
+            let trait_def = self.traits[trait_index].clone();
+            let Some(position) = trait_def
+                .methods
+                .iter()
+                .position(|definition| definition.name == method)
+            else {
+                panic!("no method `{method}` in `{}`", trait_def.name);
+            };
+            let definition = &trait_def.methods[position];
+            assert!(
+                !definition.params[1..]
+                    .iter()
+                    .flatten()
+                    .map(|&param| self.term(param))
+                    .chain(definition.ret_ty)
+                    .any(|term| mentions_self(self.ast, term)),
+                "method `{method}` cannot be called on a trait object"
+            );
+            let signature =
+                self.with_type_params(&["Self"], std::slice::from_ref(&receiver.1), |resolver| {
+                    resolver.resolve_signature(definition)
+                });
+            let args = self.check_args(args, &signature.params[1..], method);
+            return (
+                Expr::DynamicCall(
+                    position,
+                    Box::new(receiver.0),
+                    args,
+                    signature.ret_ty.clone(),
+                ),
+                signature.ret_ty,
+            );
+        }
+        let Some(index) = self.find_method(&receiver.1, method) else {
+            panic!("no method `{method}` for `{}`", self.type_name(&receiver.1));
+        };
+        let params = self.signatures[index].params.clone();
+        let args = self.check_args(
+            std::iter::once(receiver).chain(args).collect(),
+            &params,
+            method,
+        );
+        self.direct_call(index, args)
+    }
+    fn resolve_builtin(
+        &mut self,
+        scope: &mut Scope,
+        name: &str,
+        elements: &[Option<NodeId>],
+        span: Span,
+    ) -> (Expr, Ty) {
+        let (args, tys): (Vec<_>, Vec<_>) = elements
+            .iter()
+            .map(|element| self.resolve_expr(scope, self.term(element.expect("empty argument"))))
+            .unzip();
+        let expect = |expected: &[Ty]| {
+            assert!(tys == expected, "mismatched arguments to `{name}`");
+        };
+        let (builtin, ty) = match name {
+            "len" => {
+                assert!(matches!(tys[..], [Ty::Array(_)]), "`len` expects an array");
+                let array = args.into_iter().next().unwrap();
+                return (Expr::Length(Box::new(array)), Ty::Int);
+            }
+            "print" | "println" => {
+                let ty = match tys[..] {
+                    [] if name == "println" => None,
+                    [ref ty @ (Ty::Int | Ty::Float | Ty::Bool | Ty::String)] => Some(ty.clone()),
+                    _ => panic!("mismatched arguments to `{name}`"),
+                };
+                (Builtin::Print(ty, name == "println"), Ty::unit())
+            }
+            "read_line" => {
+                expect(&[]);
+                (Builtin::ReadLine, Ty::String)
+            }
+            "parse_int" => {
+                expect(&[Ty::String]);
+                (Builtin::ParseInt, Ty::Int)
+            }
+            "args" => {
+                expect(&[]);
+                (Builtin::Args, Ty::Array(Box::new(Ty::String)))
+            }
+            "abs" => match tys[..] {
+                [ref ty @ (Ty::Int | Ty::Float)] => (Builtin::Abs, ty.clone()),
+                _ => panic!("mismatched arguments to `{name}`"),
+            },
+            "min" | "max" => match tys[..] {
+                [ref ty @ (Ty::Int | Ty::Float), ref other] if ty == other => {
+                    let builtin = if name == "min" {
+                        Builtin::Min
+                    } else {
+                        Builtin::Max
+                    };
+                    (builtin, ty.clone())
+                }
+                _ => panic!("mismatched arguments to `{name}`"),
+            },
+            "sqrt" => {
+                expect(&[Ty::Float]);
+                (Builtin::Sqrt, Ty::Float)
+            }
+            "sin" => {
+                expect(&[Ty::Float]);
+                (Builtin::Sin, Ty::Float)
+            }
+            "pow" => {
+                expect(&[Ty::Float, Ty::Float]);
+                (Builtin::Pow, Ty::Float)
+            }
+            "exit" => {
+                expect(&[Ty::Int]);
+                (Builtin::Exit, Ty::unit())
+            }
+            "checked" | "wrapping" | "saturating" => {
+                assert!(args.len() == 1, "mismatched arguments to `{name}`");
+                let overflow = match name {
+                    "checked" => Overflow::Checked,
+                    "wrapping" => Overflow::Wrapping,
+                    _ => Overflow::Saturating,
+                };
+                let expr = args.into_iter().next().unwrap();
+                return (
+                    Expr::Overflow(overflow, Box::new(expr)),
+                    tys.into_iter().next().unwrap(),
+                );
+            }
+            _ => unreachable!(),
+        };
+        (Expr::Builtin(builtin, args, span), ty)
+    }
+    fn direct_call(&self, index: usize, args: Vec<Expr>) -> (Expr, Ty) {
+        let ret_ty = self.signatures[index].ret_ty.clone();
+        (
+            Expr::Call(Box::new(Expr::Func(index)), args, ret_ty.clone()),
+            ret_ty,
+        )
+    }
+    fn is_shadowed(&mut self, scope: &mut Scope, name: &str) -> bool {
+        self.lookup(scope, name).is_some()
+            || self.names.globals.contains_key(name)
+            || self.names.consts.contains_key(name)
+    }
+    // A name that isn't a local variable.
+    fn resolve_item(&mut self, name: &str, term: &Term) -> (Expr, Ty) {
+        if let Some(&index) = self.names.globals.get(name) {
+            assert!(
+                !self.in_comptime,
+                "global `{name}` cannot be used at compile time"
+            );
+            let ty = self.globals[index].clone();
+            let definition = self.global_spans[index];
+            self.reference(name, ReferenceKind::Global, term.span, definition, &ty);
+            (Expr::Global(index, term.span), ty)
+        } else if let Some(&index) = self.names.consts.get(name) {
+            let (value, ty) = self.resolve_const(index);
+            let definition = self.consts[index].span;
+            self.reference(name, ReferenceKind::Global, term.span, definition, &ty);
+            (value, ty)
+        } else if let Some(&(enum_index, variant)) = self.names.variants.get(name) {
+            assert!(
+                self.enums[enum_index].variants[variant].1.is_empty(),
+                "variant `{name}` expects a payload"
+            );
+            (
+                Expr::Variant(enum_index, variant, Vec::new()),
+                Ty::Enum(enum_index),
+            )
+        } else if let Some(&index) = self.names.funcs.get(name) {
+            self.func_reference(name, term.span, index);
+            let signature = &self.signatures[index];
+            (
+                Expr::Func(index),
+                Ty::Func(signature.params.clone(), Box::new(signature.ret_ty.clone())),
+            )
+        } else if self.names.generic_funcs.contains_key(name) {
+            panic!("missing type arguments to `{name}`");
+        } else {
+            panic!("undefined variable `{name}`");
+        }
+    }
+    // The module that a name of a macro body, which the body doesn't declare,
+    // is looked up in.
+    fn macro_module(&self, term: &Term) -> Option<usize> {
+        self.modules[self.module]
+            .macro_names
+            .get(&term.span)
+            .copied()
+    }
+    fn resolve_expr(&mut self, scope: &mut Scope, term: &Term) -> (Expr, Ty) {
+        span::enter(term.span);
+        let expr = self.resolve_expr_kind(scope, term);
+        span::leave();
+        expr
+    }
+    fn resolve_expr_kind(&mut self, scope: &mut Scope, term: &Term) -> (Expr, Ty) {
+        match &term.kind {
+            TermKind::Identifier(name) if name == "true" => (Expr::Boolean(true), Ty::Bool),
+            TermKind::Identifier(name) if name == "false" => (Expr::Boolean(false), Ty::Bool),
+            TermKind::Identifier(name) => {
+                let name = name.as_str();
+                let module = self.macro_module(term);
+                let local = match module {
+                    Some(_) => None,
+                    None => self.lookup(scope, name),
+                };
+                match local {
+                    Some(index) => {
+                        let ty = scope.vars[index].clone();
+                        let definition = scope.spans[index];
+                        self.reference(name, ReferenceKind::Variable, term.span, definition, &ty);
+                        (Expr::Variable(index), ty)
+                    }
+                    None => self.with_module(module.unwrap_or(self.module), |resolver| {
+                        resolver.resolve_item(name, term)
+                    }),
+                }
+            }
+            TermKind::Integer(value) => (Expr::Integer(*value), Ty::Int),
+            TermKind::Float(value) => (Expr::Float(*value), Ty::Float),
+            TermKind::StringLiteral(components) => {
+                if let [] | [StringLiteralComponent::String(_)] = &components[..] {
+                    let string = components
+                        .iter()
+                        .map(|component| match component {
+                            StringLiteralComponent::String(string) => string.as_str(),
+                            StringLiteralComponent::Expr(_) => unreachable!(),
+                        })
+                        .collect();
+                    return (Expr::String(string), Ty::String);
+                }
+                let (args, tys) = components
+                    .iter()
+                    .map(|component| match component {
+                        StringLiteralComponent::String(string) => {
+                            (Expr::String(string.clone()), Ty::String)
+                        }
+                        StringLiteralComponent::Expr(term) => {
+                            let term = self.term(*term);
+                            let (value, ty) = self.resolve_expr(scope, term);
+                            span::enter(term.span);
+                            assert!(
+                                matches!(ty, Ty::Int | Ty::Float | Ty::Bool | Ty::String),
+                                "cannot interpolate a value of type `{}`",
+                                self.type_name(&ty)
+                            );
+                            span::leave();
+                            (value, ty)
+                        }
+                    })
+                    .unzip();
+                (
+                    Expr::Builtin(Builtin::Format(tys), args, term.span),
+                    Ty::String,
+                )
+            }
+            TermKind::BinaryOperation {
+                left_operand: None,
+                operator: Operator::Sub,
+                right_operand: Some(operand),
+            } => {
+                let (operand, ty) = self.resolve_expr(scope, self.term(*operand));
+                assert!(
+                    matches!(ty, Ty::Int | Ty::Float),
+                    "cannot negate a value of type `{}`",
+                    self.type_name(&ty)
+                );
+                (Expr::Negation(Box::new(operand), term.span), ty)
+            }
+            TermKind::BinaryOperation {
+                left_operand: Some(left_operand),
+                operator,
+                right_operand: Some(right_operand),
+            } => {
+                let (left_operand, left_ty) = self.resolve_expr(scope, self.term(*left_operand));
+                let (right_operand, right_ty) = self.resolve_expr(scope, self.term(*right_operand));
+                if !matches!(left_ty, Ty::Int | Ty::Float | Ty::Bool) {
+                    return self.resolve_method_call(
+                        (left_operand, left_ty),
+                        operator_method(*operator),
+                        vec![(right_operand, right_ty)],
+                    );
+                }
+                assert!(
+                    left_ty == right_ty,
+                    "mismatched operand types `{}` and `{}` of `{}`",
+                    self.type_name(&left_ty),
+                    self.type_name(&right_ty),
+                    operator.symbol()
+                );
+                let ty = match (operator, &left_ty) {
+                    (Operator::Add | Operator::Sub | Operator::Mul | Operator::Div, Ty::Int)
+                    | (Operator::Add | Operator::Sub | Operator::Mul | Operator::Div, Ty::Float)
+                    | (Operator::Rem, Ty::Int) => left_ty,
+                    (Operator::Equal | Operator::NotEqual, Ty::Int | Ty::Float | Ty::Bool)
+                    | (
+                        Operator::Greater
+                        | Operator::GreaterEqual
+                        | Operator::Less
+                        | Operator::LessEqual,
+                        Ty::Int | Ty::Float,
+                    ) => Ty::Bool,
+                    _ => panic!(
+                        "`{}` is not defined for `{}`",
+                        operator.symbol(),
+                        self.type_name(&left_ty)
+                    ),
+                };
+                (
+                    Expr::BinaryOperation(
+                        *operator,
+                        Box::new(left_operand),
+                        Box::new(right_operand),
+                        term.span,
+                    ),
+                    ty,
+                )
+            }
+            TermKind::Bracketed {
+                antecedent: None,
+                bracket_kind: BracketKind::Round,
+                elements,
+                has_trailing_comma,
+            } => self.resolve_bracket(scope, elements, *has_trailing_comma),
+            TermKind::Bracketed {
+                antecedent: None,
+                bracket_kind: BracketKind::Square,
+                elements,
+                ..
+            } => {
+                let (elems, tys): (Vec<_>, Vec<_>) = elements
+                    .iter()
+                    .map(|element| {
+                        self.resolve_expr(scope, self.term(element.expect("empty array element")))
+                    })
+                    .unzip();
+                let Some(elem_ty) = tys.first().cloned() else {
+                    panic!("cannot infer the element type of an empty array");
+                };
+                assert!(
+                    tys.iter().all(|ty| *ty == elem_ty),
+                    "mismatched array element types"
+                );
+                (
+                    Expr::Array(elem_ty.clone(), elems),
+                    Ty::Array(Box::new(elem_ty)),
+                )
+            }
+            TermKind::Bracketed {
+                antecedent: Some(array),
+                bracket_kind: BracketKind::Square,
+                elements,
+                has_trailing_comma,
+            } => {
+                if let Some((name, type_args)) = generic_instantiation(self.ast, term) {
+                    if let Some(&generic) = self.names.generic_funcs.get(name) {
+                        if !self.is_shadowed(scope, name) {
+                            let type_args = self.resolve_type_args(type_args);
+                            let index = self.instantiate_func(generic, type_args);
+                            self.func_reference(name, antecedent_span(self.ast, term), index);
+                            let signature = &self.signatures[index];
+                            return (
+                                Expr::Func(index),
+                                Ty::Func(
+                                    signature.params.clone(),
+                                    Box::new(signature.ret_ty.clone()),
+                                ),
+                            );
+                        }
+                    }
+                }
+                let index = single_element(self.ast, elements, *has_trailing_comma);
+                let (elem_ty, array, index) = self.resolve_index(scope, self.term(*array), index);
+                (
+                    Expr::Index(elem_ty.clone(), Box::new(array), Box::new(index), term.span),
+                    elem_ty,
+                )
+            }
+            TermKind::Bracketed {
+                antecedent: Some(antecedent),
+                bracket_kind: BracketKind::Round,
+                elements,
+                has_trailing_comma,
+            } => self.resolve_call(
+                scope,
+                self.term(*antecedent),
+                elements,
+                *has_trailing_comma,
+                term.span,
+            ),
+            TermKind::Block {
+                antecedent: Some(antecedent),
+                stmts,
+            } => self
+                .resolve_value_block(scope, self.term(*antecedent), stmts)
+                .unwrap_or_else(|| panic!("unexpected block")),
+            TermKind::MemberAccess {
+                antecedent: Some(record),
+                member,
+            } => {
+                let (struct_index, field_index, record) =
+                    self.resolve_field(scope, self.term(*record), member.as_str());
+                let ty = self.structs[struct_index].fields[field_index].1.clone();
+                (Expr::Field(struct_index, field_index, Box::new(record)), ty)
+            }
+            TermKind::CustomOperation {
+                left_operand,
+                operator,
+                fixity,
+                right_operand,
+            } => {
+                let function = Term {
+                    kind: TermKind::Identifier(self.names.operators[&(*operator, *fixity)]),
+                    span: term.span,
+                };
+                let operands: Vec<_> = left_operand
+                    .iter()
+                    .chain(right_operand)
+                    .copied()
+                    .map(Some)
+                    .collect();
+                self.resolve_call(scope, &function, &operands, false, term.span)
+            }
+            _ => panic!("invalid expression"),
+        }
+    }
+
+    fn resolve_call(
+        &mut self,
+        scope: &mut Scope,
+        antecedent: &Term,
+        elements: &[Option<NodeId>],
+        has_trailing_comma: bool,
+        span: Span,
+    ) -> (Expr, Ty) {
+        if let TermKind::Bracketed {
+            antecedent: None,
+            bracket_kind: BracketKind::Square,
+            ..
+        } = antecedent.kind
+        {
+            let Ty::Array(elem_ty) = self.resolve_ty(antecedent) else {
+                unreachable!()
+            };
+            let (len, len_ty) = self.resolve_expr(
+                scope,
+                single_element(self.ast, elements, has_trailing_comma),
+            );
+            self.expect_ty(&len_ty, &Ty::Int, || {
+                "mismatched type of array length".to_owned()
+            });
+            return (
+                Expr::NewArray((*elem_ty).clone(), Box::new(len), span),
+                Ty::Array(elem_ty),
+            );
+        }
+        if let TermKind::Identifier(name) = &antecedent.kind {
+            let name = name.as_str();
+            let module = self.macro_module(antecedent);
+            if BUILTINS.contains(&name)
+                && !self.names.funcs.contains_key(name)
+                && (module.is_some() || self.lookup(scope, name).is_none())
+            {
+                return self.resolve_builtin(scope, name, elements, span);
+            }
+            // The functions a macro calls are those where it is defined, which
+            // the variables where it is used can't shadow.
+            let func = module.and_then(|module| {
+                self.with_module(module, |resolver| resolver.names.funcs.get(name).copied())
+            });
+            if let Some(index) = func {
+                self.func_reference(name, antecedent.span, index);
+                let params = self.signatures[index].params.clone();
+                let args = self.resolve_args(scope, elements, &params, name);
+                return self.direct_call(index, args);
+            }
+        }
+        if let TermKind::MemberAccess {
+            antecedent: Some(receiver),
+            member,
+        } = &antecedent.kind
+        {
+            let receiver = self.resolve_expr(scope, self.term(*receiver));
+            let field = match receiver.1 {
+                Ty::Struct(struct_index) => self.structs[struct_index]
+                    .fields
+                    .iter()
+                    .position(|(field, _)| member == field.as_str())
+                    .map(|field_index| (struct_index, field_index)),
+                _ => None,
+            };
+            let args: Vec<_> = elements
+                .iter()
+                .map(|element| {
+                    self.resolve_expr(scope, self.term(element.expect("empty argument")))
+                })
+                .collect();
+            let Some((struct_index, field_index)) = field else {
+                return self.resolve_method_call(receiver, member.as_str(), args);
+            };
+            let Ty::Func(params, ret_ty) = self.structs[struct_index].fields[field_index].1.clone()
+            else {
+                panic!("field `{member}` is not callable");
+            };
+            let callee = Expr::Field(struct_index, field_index, Box::new(receiver.0));
+            let args = self.check_args(args, &params, member.as_str());
+            return (
+                Expr::Call(Box::new(callee), args, (*ret_ty).clone()),
+                *ret_ty,
+            );
+        }
+        if let Some((name, type_args)) = generic_instantiation(self.ast, antecedent) {
+            if let Some(&generic) = self.names.generic_structs.get(name) {
+                let type_args = self.resolve_type_args(type_args);
+                let index = self.instantiate_struct(generic, type_args);
+                return self.resolve_struct_literal(scope, index, elements);
+            }
+            if let Some(&generic) = self.names.generic_funcs.get(name) {
+                if !self.is_shadowed(scope, name) {
+                    let type_args = self.resolve_type_args(type_args);
+                    let index = self.instantiate_func(generic, type_args);
+                    self.func_reference(name, antecedent_span(self.ast, antecedent), index);
+                    let params = self.signatures[index].params.clone();
+                    let args = self.resolve_args(scope, elements, &params, name);
+                    return self.direct_call(index, args);
+                }
+            }
+        }
+        if let TermKind::Identifier(name) = &antecedent.kind {
+            let name = name.as_str();
+            if let Some(&index) = self.names.structs.get(name) {
+                return self.resolve_struct_literal(scope, index, elements);
+            }
+            if let Some(&generic) = self.names.generic_structs.get(name) {
+                return self.resolve_generic_struct_literal(scope, generic, elements);
+            }
+            if let Some(&(enum_index, variant)) = self.names.variants.get(name) {
+                let payload_tys = self.enums[enum_index].variants[variant].1.clone();
+                let payload = self.resolve_args(scope, elements, &payload_tys, name);
+                return (
+                    Expr::Variant(enum_index, variant, payload),
+                    Ty::Enum(enum_index),
+                );
+            }
+            if let Some(&index) = self.names.funcs.get(name) {
+                if !self.is_shadowed(scope, name) {
+                    self.func_reference(name, antecedent.span, index);
+                    let params = self.signatures[index].params.clone();
+                    let args = self.resolve_args(scope, elements, &params, name);
+                    return self.direct_call(index, args);
+                }
+            }
+            if let Some(&generic) = self.names.generic_funcs.get(name) {
+                if !self.is_shadowed(scope, name) {
+                    let args: Vec<_> = elements
+                        .iter()
+                        .map(|element| {
+                            self.resolve_expr(scope, self.term(element.expect("empty argument")))
+                        })
+                        .collect();
+                    let definition = self.generic_funcs[generic].clone();
+                    let mut bindings = vec![None; definition.type_params.len()];
+                    for (param, (_, arg_ty)) in definition.params.iter().zip(&args) {
+                        if let Some(TermKind::Declaration {
+                            ty: Some(param_ty), ..
+                        }) = param.map(|param| &self.term(param).kind)
+                        {
+                            self.infer_type_args(
+                                self.term(*param_ty),
+                                arg_ty,
+                                &definition.type_params,
+                                &mut bindings,
+                            );
+                        }
+                    }
+                    let type_args = infer_result(bindings, &definition.type_params, name);
+                    let index = self.instantiate_func(generic, type_args);
+                    self.func_reference(name, antecedent.span, index);
+                    let params = self.signatures[index].params.clone();
+                    let args = self.check_args(args, &params, name);
+                    return self.direct_call(index, args);
+                }
+            }
+        }
+        let (callee, callee_ty) = self.resolve_expr(scope, antecedent);
+        let Ty::Func(params, ret_ty) = callee_ty else {
+            panic!(
+                "cannot call a value of type `{}`",
+                self.type_name(&callee_ty)
+            );
+        };
+        let args = self.resolve_args(scope, elements, &params, "closure");
+        (
+            Expr::Call(Box::new(callee), args, (*ret_ty).clone()),
+            *ret_ty,
+        )
+    }
+}
+
+fn infer_result(bindings: Vec<Option<Ty>>, type_params: &[&str], name: &str) -> Vec<Ty> {
+    bindings
+        .into_iter()
+        .zip(type_params)
+        .map(|(ty, param)| {
+            ty.unwrap_or_else(|| panic!("cannot infer type parameter `{param}` of `{name}`"))
+        })
+        .collect()
+}
+
+fn keyword_with_condition<'a>(ast: &'a Ast, term: &'a Term) -> Option<(&'a str, &'a Term)> {
+    let TermKind::Bracketed {
+        antecedent: Some(antecedent),
+        bracket_kind: BracketKind::Round,
+        elements,
+        has_trailing_comma: false,
+    } = &term.kind
+    else {
+        return None;
+    };
+    let TermKind::Identifier(keyword) = &ast[*antecedent].kind else {
+        return None;
+    };
+    match elements.as_slice() {
+        [Some(condition)] => Some((keyword.as_str(), &ast[*condition])),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use super::{generator, resolve_with, ReferenceKind, Resolver, Scope, Signature};
+use crate::ast::{Expr, Program, Stmt, Ty};
+use crate::module::Module;
+use crate::pre_ast::{self, Ast, Term};
+use crate::span::{self, Span};
+use crate::{codegen, parser, source_map};
+
+// Replaces each `comptime` block at the top level of a module by the code it
+// evaluates to. The block can only use what comes before it.
+pub fn generate(ast: &mut Ast, modules: &mut [Module]) {
+    for module in 0..modules.len() {
+        while let Some((position, span)) = modules[module]
+            .stmts
+            .iter()
+            .enumerate()
+            .find_map(|(position, stmt)| Some((position, generator(ast, stmt)?.0)))
+        {
+            let current = &modules[module];
+            let mut before = modules[..module].to_vec();
+            before.push(Module {
+                name: current.name.clone(),
+                stmts: current.stmts[..=position].to_vec(),
+                public: current.public[..=position].to_vec(),
+                imports: current.imports.clone(),
+                macro_names: current.macro_names.clone(),
+            });
+            let (program, _, roots) = resolve_with(ast, &before, &HashMap::new());
+            let root = roots.into_iter().find(|&(site, _)| site == span).unwrap();
+            let Some(Expr::String(code)) = evaluate(&program, &[root]).remove(&span) else {
+                unreachable!();
+            };
+            let path = format!("<generated by {}>", source_map::location(span));
+            let start = source_map::start(source_map::add(&path, &code));
+            let stmts = source_map::with_text(|text| parser::parse_into(ast, text, start));
+            modules[module].splice(ast, position, stmts);
+        }
+    }
+}
+
+pub(super) fn evaluate(program: &Program, roots: &[(Span, usize)]) -> HashMap<Span, Expr> {
+    let input = source_map::with_text(str::to_owned);
+    let values = codegen::evaluate(program, &input, roots);
+    roots.iter().map(|&(span, _)| span).zip(values).collect()
+}
+
+impl Resolver<'_> {
+    pub(super) fn resolve_const(&mut self, index: usize) -> (Expr, Ty) {
+        let definition = &self.consts[index];
+        if let Some(resolved) = &definition.resolved {
+            return resolved.clone();
+        }
+        assert!(
+            !definition.resolving,
+            "constant `{}` depends on itself",
+            definition.name
+        );
+        let (name, span, module, ty, value, body) = (
+            definition.name,
+            definition.span,
+            definition.module,
+            definition.ty,
+            definition.value,
+            definition.body,
+        );
+        self.consts[index].resolving = true;
+        // A constant is evaluated on its own even when other compile-time code
+        // uses it, so that its value is known wherever else it is used.
+        let in_comptime = std::mem::replace(&mut self.in_comptime, false);
+        let resolved = self.with_module(module, |resolver| {
+            resolver.with_type_params(&[], &[], |resolver| {
+                let expected = ty.map(|ty| resolver.resolve_ty(ty));
+                let resolved = match body {
+                    Some(body) => resolver.resolve_comptime_block(value.span, body),
+                    None => resolver.resolve_comptime(value.span, &[], value),
+                };
+                if let Some(expected) = expected {
+                    span::enter(value.span);
+                    resolver.expect_ty(&resolved.1, &expected, || {
+                        format!("mismatched types in declaration of `{name}`")
+                    });
+                    span::leave();
+                }
+                resolved
+            })
+        });
+        self.in_comptime = in_comptime;
+        self.reference(name, ReferenceKind::Global, span, span, &resolved.1);
+        self.consts[index].resolved = Some(resolved.clone());
+        resolved
+    }
+    pub(super) fn resolve_generator(&mut self, span: Span, body: &[pre_ast::Stmt]) {
+        let (_, ty) = self.resolve_comptime_block(span, body);
+        span::enter(span);
+        self.expect_ty(&ty, &Ty::String, || {
+            "mismatched type of generated code".to_owned()
+        });
+        span::leave();
+    }
+    pub(super) fn resolve_comptime_block(
+        &mut self,
+        span: Span,
+        body: &[pre_ast::Stmt],
+    ) -> (Expr, Ty) {
+        span::enter(span);
+        let Some((pre_ast::Stmt::Term(Some(value)), stmts)) = body.split_last() else {
+            panic!("`comptime` block must end with a value");
+        };
+        span::leave();
+        self.resolve_comptime(span, stmts, self.term(*value))
+    }
+    // Compile-time code stands for a call to a function made of it until it
+    // has been evaluated, and for its value afterwards.
+    fn resolve_comptime(
+        &mut self,
+        span: Span,
+        stmts: &[pre_ast::Stmt],
+        value: &Term,
+    ) -> (Expr, Ty) {
+        if let Some(value) = self.comptime_values.get(&span) {
+            let ty = match value {
+                Expr::Integer(_) => Ty::Int,
+                Expr::Float(_) => Ty::Float,
+                Expr::Boolean(_) => Ty::Bool,
+                Expr::String(_) => Ty::String,
+                _ => unreachable!(),
+            };
+            return (value.clone(), ty);
+        }
+        if let Some(&index) = self.comptime_funcs.get(&span) {
+            return self.direct_call(index, Vec::new());
+        }
+        span::enter(span);
+        let in_comptime = std::mem::replace(&mut self.in_comptime, true);
+        let (scope, body) = self.with_type_params(&[], &[], |resolver| {
+            let mut scope = Scope::new(resolver.ast, Ty::unit(), false, stmts);
+            let mut body = resolver.resolve_stmts(&mut scope, stmts);
+            let (value, ty) = resolver.resolve_expr(&mut scope, value);
+            assert!(
+                matches!(ty, Ty::Int | Ty::Float | Ty::Bool | Ty::String),
+                "a value of type `{}` cannot be computed at compile time",
+                resolver.type_name(&ty)
+            );
+            scope.ret_ty = ty;
+            body.push(Stmt::Return(value, span));
+            (scope, body)
+        });
+        self.in_comptime = in_comptime;
+        let index = self.signatures.len();
+        self.signatures.push(Signature {
+            params: Vec::new(),
+            ret_ty: scope.ret_ty.clone(),
+        });
+        self.func_spans.insert(index, span);
+        let func = scope.into_func(format!("<comptime {index}>"), span, 0, body);
+        self.comptime.push((index, func));
+        self.comptime_funcs.insert(span, index);
+        if !in_comptime {
+            self.comptime_roots.push((span, index));
+        }
+        span::leave();
+        self.direct_call(index, Vec::new())
+    }
+}
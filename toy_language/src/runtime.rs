@@ -0,0 +1,256 @@
+mod gc;
+
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::io::Write;
+
+use crate::error::ErrorKind;
+use crate::source_map;
+use crate::span::Span;
+pub use gc::{enable_stress as enable_gc_stress, register_code, register_root, StackMap};
+
+extern "C" {
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+thread_local! {
+    static ARGS: RefCell<Vec<*const c_char>> = const { RefCell::new(Vec::new()) };
+    // The constant or `comptime` block whose code is running, if any.
+    static COMPTIME: Cell<Option<Span>> = const { Cell::new(None) };
+}
+
+pub fn set_args(args: Vec<String>) {
+    let args = args
+        .into_iter()
+        .map(|arg| CString::new(arg).unwrap().into_raw() as *const c_char)
+        .collect();
+    ARGS.set(args);
+}
+
+pub fn symbols() -> Vec<(&'static str, *const u8)> {
+    vec![
+        ("new_array", new_array as *const u8),
+        ("index_out_of_bounds", index_out_of_bounds as *const u8),
+        ("integer_overflow", integer_overflow as *const u8),
+        ("division_by_zero", division_by_zero as *const u8),
+        ("null_dereference", null_dereference as *const u8),
+        ("null_string", null_string as *const u8),
+        ("alloc", alloc as *const u8),
+        ("print_int", print_int as *const u8),
+        ("print_float", print_float as *const u8),
+        ("print_bool", print_bool as *const u8),
+        ("print_string", print_string as *const u8),
+        ("print_newline", print_newline as *const u8),
+        ("read_line", read_line as *const u8),
+        ("int_to_string", int_to_string as *const u8),
+        ("float_to_string", float_to_string as *const u8),
+        ("bool_to_string", bool_to_string as *const u8),
+        ("concat", concat as *const u8),
+        ("parse_int", parse_int as *const u8),
+        ("args", args as *const u8),
+        ("sin", sin as *const u8),
+        ("pow", pow as *const u8),
+        ("exit", exit as *const u8),
+    ]
+}
+
+// Runs the code of a constant or `comptime` block at `span`. It can't print,
+// read input or exit, and its errors are reported as type errors at `span`.
+pub fn at_compile_time<T>(span: Span, f: impl FnOnce() -> T) -> T {
+    COMPTIME.set(Some(span));
+    let result = f();
+    COMPTIME.set(None);
+    result
+}
+
+fn forbid_at_compile_time(action: &str) {
+    if let Some(span) = COMPTIME.get() {
+        compile_time_error(span, &format!("cannot {action} at compile time"));
+    }
+}
+
+fn compile_time_error(span: Span, message: &str) -> ! {
+    eprintln!("type error at {}: {message}", source_map::location(span));
+    std::process::exit(ErrorKind::Type.exit_code());
+}
+
+fn runtime_error(line: i64, column: i64, message: &str, frame: *const usize) -> ! {
+    let location = source_map::line_location(line as usize, column as usize);
+    if let Some(span) = COMPTIME.get() {
+        let mut message = format!("{message} while evaluating at compile time\n    at {location}");
+        for name in gc::backtrace(frame) {
+            if !name.starts_with("<comptime ") {
+                message += &format!("\n    in {name}");
+            }
+        }
+        compile_time_error(span, &message);
+    }
+    eprintln!("runtime error at {location}: {message}");
+    for name in gc::backtrace(frame) {
+        eprintln!("    in {name}");
+    }
+    std::process::exit(ErrorKind::Runtime.exit_code());
+}
+
+pub extern "C" fn new_array(
+    len: i64,
+    elem_size: i64,
+    elem_refs: i64,
+    line: i64,
+    column: i64,
+    frame: *const usize,
+) -> *mut i64 {
+    if len < 0 {
+        runtime_error(line, column, &format!("negative array length {len}"), frame);
+    }
+    let size = 8 + len as usize * elem_size as usize;
+    let ptr = gc::allocate(size, 8, elem_size as usize, elem_refs as u64, frame) as *mut i64;
+    unsafe { ptr.write(len) };
+    ptr
+}
+
+pub extern "C" fn index_out_of_bounds(
+    index: i64,
+    len: i64,
+    line: i64,
+    column: i64,
+    frame: *const usize,
+) -> ! {
+    runtime_error(
+        line,
+        column,
+        &format!("index {index} out of bounds for length {len}"),
+        frame,
+    );
+}
+
+pub extern "C" fn integer_overflow(line: i64, column: i64, frame: *const usize) -> ! {
+    runtime_error(line, column, "integer overflow", frame);
+}
+
+pub extern "C" fn division_by_zero(line: i64, column: i64, frame: *const usize) -> ! {
+    runtime_error(line, column, "division by zero", frame);
+}
+
+pub extern "C" fn null_dereference(line: i64, column: i64, frame: *const usize) -> ! {
+    runtime_error(line, column, "null dereference", frame);
+}
+
+pub extern "C" fn null_string(line: i64, column: i64, frame: *const usize) -> ! {
+    runtime_error(
+        line,
+        column,
+        "extern function returned a null string",
+        frame,
+    );
+}
+
+pub extern "C" fn alloc(size: i64, refs: i64, frame: *const usize) -> *mut u8 {
+    gc::allocate(size as usize, 0, size as usize, refs as u64, frame)
+}
+
+pub fn lookup_symbol(name: &str) -> *const u8 {
+    let name = CString::new(name).unwrap();
+    unsafe { dlsym(std::ptr::null_mut(), name.as_ptr()) as *const u8 }
+}
+
+pub extern "C" fn print_int(value: i64) {
+    forbid_at_compile_time("print");
+    print!("{value}");
+}
+
+pub extern "C" fn print_float(value: f64) {
+    forbid_at_compile_time("print");
+    print!("{value}");
+}
+
+pub extern "C" fn print_bool(value: i8) {
+    forbid_at_compile_time("print");
+    print!("{}", value != 0);
+}
+
+pub extern "C" fn print_string(value: *const c_char) {
+    forbid_at_compile_time("print");
+    let value = unsafe { CStr::from_ptr(value) };
+    std::io::stdout().write_all(value.to_bytes()).unwrap();
+}
+
+pub extern "C" fn print_newline() {
+    forbid_at_compile_time("print");
+    println!();
+}
+
+pub extern "C" fn read_line(frame: *const usize) -> *mut u8 {
+    forbid_at_compile_time("read input");
+    std::io::stdout().flush().unwrap();
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).unwrap();
+    let line = line.trim_end_matches(['\n', '\r']).replace('\0', "");
+    new_string(line.as_bytes(), frame)
+}
+
+fn new_string(bytes: &[u8], frame: *const usize) -> *mut u8 {
+    let ptr = gc::allocate(bytes.len() + 1, 0, 0, 0, frame);
+    unsafe { ptr.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len()) };
+    ptr
+}
+
+pub extern "C" fn int_to_string(value: i64, frame: *const usize) -> *mut u8 {
+    new_string(value.to_string().as_bytes(), frame)
+}
+
+pub extern "C" fn float_to_string(value: f64, frame: *const usize) -> *mut u8 {
+    new_string(value.to_string().as_bytes(), frame)
+}
+
+pub extern "C" fn bool_to_string(value: i8, frame: *const usize) -> *mut u8 {
+    new_string((value != 0).to_string().as_bytes(), frame)
+}
+
+// The strings are copied before allocating, which may free them.
+pub extern "C" fn concat(
+    left: *const c_char,
+    right: *const c_char,
+    frame: *const usize,
+) -> *mut u8 {
+    let mut bytes = unsafe { CStr::from_ptr(left) }.to_bytes().to_vec();
+    bytes.extend_from_slice(unsafe { CStr::from_ptr(right) }.to_bytes());
+    new_string(&bytes, frame)
+}
+
+pub extern "C" fn parse_int(
+    value: *const c_char,
+    line: i64,
+    column: i64,
+    frame: *const usize,
+) -> i64 {
+    let value = unsafe { CStr::from_ptr(value) }.to_string_lossy();
+    value.trim().parse().unwrap_or_else(|_| {
+        runtime_error(line, column, &format!("invalid integer {value:?}"), frame);
+    })
+}
+
+pub extern "C" fn args(frame: *const usize) -> *mut i64 {
+    forbid_at_compile_time("read the program's arguments");
+    ARGS.with_borrow(|args| {
+        let ptr = gc::allocate(8 + args.len() * 8, 8, 8, 1, frame) as *mut i64;
+        unsafe {
+            ptr.write(args.len() as i64);
+            (ptr.add(1) as *mut *const c_char).copy_from_nonoverlapping(args.as_ptr(), args.len());
+        }
+        ptr
+    })
+}
+
+pub extern "C" fn sin(x: f64) -> f64 {
+    x.sin()
+}
+
+pub extern "C" fn pow(x: f64, y: f64) -> f64 {
+    x.powf(y)
+}
+
+pub extern "C" fn exit(code: i64) -> ! {
+    forbid_at_compile_time("exit");
+    std::process::exit(code as i32);
+}
@@ -0,0 +1,180 @@
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+pub type StackMap = (usize, usize, Vec<usize>);
+
+const MIN_THRESHOLD: usize = 1 << 20;
+
+struct Object {
+    size: usize,
+    start: usize,
+    stride: usize,
+    refs: u64,
+    marked: bool,
+}
+
+struct Heap {
+    objects: HashMap<usize, Object>,
+    size: usize,
+    threshold: usize,
+    stress: bool,
+    roots: Vec<usize>,
+    code: BTreeMap<usize, (usize, String)>,
+    stack_maps: HashMap<usize, (usize, Vec<usize>)>,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap {
+        objects: HashMap::new(),
+        size: 0,
+        threshold: MIN_THRESHOLD,
+        stress: false,
+        roots: Vec::new(),
+        code: BTreeMap::new(),
+        stack_maps: HashMap::new(),
+    });
+}
+
+pub fn enable_stress() {
+    HEAP.with_borrow_mut(|heap| heap.stress = true);
+}
+
+pub fn register_root(addr: usize) {
+    HEAP.with_borrow_mut(|heap| heap.roots.push(addr));
+}
+
+pub fn register_code(start: usize, size: usize, name: String, stack_maps: Vec<StackMap>) {
+    HEAP.with_borrow_mut(|heap| {
+        heap.code.insert(start, (start + size, name));
+        for (offset, span, slots) in stack_maps {
+            heap.stack_maps.insert(start + offset, (span, slots));
+        }
+    });
+}
+
+pub fn allocate(
+    size: usize,
+    start: usize,
+    stride: usize,
+    refs: u64,
+    frame: *const usize,
+) -> *mut u8 {
+    HEAP.with_borrow_mut(|heap| {
+        if heap.stress || heap.size + size > heap.threshold {
+            heap.collect(frame);
+            heap.threshold = (heap.size * 2).max(MIN_THRESHOLD);
+        }
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout(size)) };
+        heap.objects.insert(
+            ptr as usize,
+            Object {
+                size,
+                start,
+                stride,
+                refs,
+                marked: false,
+            },
+        );
+        heap.size += size;
+        ptr
+    })
+}
+
+pub fn backtrace(frame: *const usize) -> Vec<String> {
+    HEAP.with_borrow(|heap| {
+        heap.frames(frame)
+            .into_iter()
+            .filter_map(|(return_addr, _)| heap.function(return_addr))
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect()
+    })
+}
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(size.max(1), 8).unwrap()
+}
+
+impl Heap {
+    fn collect(&mut self, frame: *const usize) {
+        let mut slots = self.roots.clone();
+        for (return_addr, caller_fp) in self.frames(frame) {
+            if let Some((span, offsets)) = self.stack_maps.get(&return_addr) {
+                let sp = caller_fp - span;
+                slots.extend(offsets.iter().map(|offset| sp + offset));
+            }
+        }
+        let mut pending: Vec<usize> = slots
+            .into_iter()
+            .map(|slot| unsafe { *(slot as *const usize) })
+            .collect();
+        while let Some(ptr) = pending.pop() {
+            let Some(object) = self.objects.get_mut(&ptr) else {
+                continue;
+            };
+            if object.marked {
+                continue;
+            }
+            object.marked = true;
+            for offset in object.ref_offsets() {
+                pending.push(unsafe { *((ptr + offset) as *const usize) });
+            }
+        }
+        let mut freed = 0;
+        let stress = self.stress;
+        self.objects.retain(|&ptr, object| {
+            if object.marked {
+                object.marked = false;
+                return true;
+            }
+            freed += object.size;
+            unsafe {
+                if stress {
+                    (ptr as *mut u8).write_bytes(0xde, object.size);
+                }
+                std::alloc::dealloc(ptr as *mut u8, layout(object.size));
+            }
+            false
+        });
+        self.size -= freed;
+    }
+    fn frames(&self, frame: *const usize) -> Vec<(usize, usize)> {
+        let mut frames = Vec::new();
+        let mut fp = frame;
+        loop {
+            let (caller_fp, return_addr) = unsafe { (*fp, *fp.add(1)) };
+            if self.function(return_addr).is_none() {
+                return frames;
+            }
+            frames.push((return_addr, caller_fp));
+            fp = caller_fp as *const usize;
+        }
+    }
+    fn function(&self, addr: usize) -> Option<&str> {
+        self.code
+            .range(..=addr)
+            .next_back()
+            .filter(|(_, (end, _))| addr < *end)
+            .map(|(_, (_, name))| name.as_str())
+    }
+}
+
+impl Object {
+    fn ref_offsets(&self) -> Vec<usize> {
+        if self.refs == u64::MAX {
+            return (self.start..self.size).step_by(8).collect();
+        }
+        if self.stride == 0 {
+            return Vec::new();
+        }
+        (self.start..self.size)
+            .step_by(self.stride)
+            .flat_map(|elem| {
+                (0..64)
+                    .filter(|bit| self.refs & 1 << bit != 0)
+                    .map(move |bit| elem + bit * 8)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ast::{Builtin, Expr, Func, Pattern, Program, Stmt};
+use crate::pre_ast::Operator;
+use crate::span::Span;
+
+struct Simplifier {
+    warnings: Vec<(Span, &'static str)>,
+}
+
+// Folds operations on literals and removes the code that can never run,
+// returning warnings about the statements that could not be reached. An
+// operation that would fail at run time, like a division by zero or an
+// overflow, is left for the program to fail on.
+pub fn simplify(program: &mut Program) -> Vec<(Span, &'static str)> {
+    let mut simplifier = Simplifier {
+        warnings: Vec::new(),
+    };
+    let funcs: Vec<&mut Func> = program
+        .funcs
+        .iter_mut()
+        .chain(&mut program.lambdas)
+        .chain([&mut program.init])
+        .collect();
+    for func in funcs {
+        simplifier.block(&mut func.body);
+    }
+    // The instances of a generic function share their source.
+    let mut warnings = simplifier.warnings;
+    warnings.sort_by_key(|(span, _)| (span.start, span.end));
+    warnings.dedup();
+    warnings
+}
+
+// Whether running the statements cannot reach their end, once simplified.
+pub fn diverges(stmts: &[Stmt]) -> bool {
+    let mut simplifier = Simplifier {
+        warnings: Vec::new(),
+    };
+    simplifier.block(&mut stmts.to_vec()).is_some()
+}
+
+impl Simplifier {
+    // Returns where the block stops being able to complete, if it can't.
+    fn block(&mut self, stmts: &mut Vec<Stmt>) -> Option<(Span, &'static str)> {
+        let mut simplified = Vec::new();
+        let mut diverges = None;
+        for stmt in std::mem::take(stmts) {
+            if let Some((span, message)) = diverges {
+                self.warnings.push((span, message));
+                break;
+            }
+            let (stmt, stmt_diverges) = self.stmt(stmt);
+            simplified.extend(stmt);
+            diverges = stmt_diverges;
+        }
+        *stmts = simplified;
+        diverges
+    }
+    fn stmt(&mut self, stmt: Stmt) -> (Option<Stmt>, Option<(Span, &'static str)>) {
+        match stmt {
+            Stmt::Expr(mut expr) => {
+                self.expr(&mut expr);
+                let diverges = match expr {
+                    Expr::Builtin(Builtin::Exit, _, span) => {
+                        Some((span, "unreachable code after `exit`"))
+                    }
+                    _ => None,
+                };
+                (Some(Stmt::Expr(expr)), diverges)
+            }
+            Stmt::Assign(mut pattern, mut value) => {
+                self.pattern(&mut pattern);
+                self.expr(&mut value);
+                (Some(Stmt::Assign(pattern, value)), None)
+            }
+            Stmt::Block(mut stmts) => {
+                let diverges = self.block(&mut stmts);
+                (Some(Stmt::Block(stmts)), diverges)
+            }
+            Stmt::If(mut condition, mut then_stmts, mut else_stmts, span) => {
+                self.expr(&mut condition);
+                let then_diverges = self.block(&mut then_stmts);
+                let else_diverges = self.block(&mut else_stmts);
+                match condition {
+                    Expr::Boolean(true) => (Some(Stmt::Block(then_stmts)), then_diverges),
+                    Expr::Boolean(false) => (Some(Stmt::Block(else_stmts)), else_diverges),
+                    condition => {
+                        let diverges = (then_diverges.is_some() && else_diverges.is_some())
+                            .then_some((span, "unreachable code after `if` that always returns"));
+                        (
+                            Some(Stmt::If(condition, then_stmts, else_stmts, span)),
+                            diverges,
+                        )
+                    }
+                }
+            }
+            Stmt::While(mut condition, mut stmts, span) => {
+                self.expr(&mut condition);
+                self.block(&mut stmts);
+                match condition {
+                    Expr::Boolean(false) => (None, None),
+                    // A loop can only be left by returning.
+                    Expr::Boolean(true) => (
+                        Some(Stmt::While(condition, stmts, span)),
+                        Some((span, "unreachable code after infinite loop")),
+                    ),
+                    condition => (Some(Stmt::While(condition, stmts, span)), None),
+                }
+            }
+            Stmt::Return(mut value, span) => {
+                self.expr(&mut value);
+                (
+                    Some(Stmt::Return(value, span)),
+                    Some((span, "unreachable code after `return`")),
+                )
+            }
+        }
+    }
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Variable(_) | Pattern::Global(_) | Pattern::Discard => {}
+            Pattern::Tuple(patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            Pattern::Index(_, array, index, _) => {
+                self.expr(array);
+                self.expr(index);
+            }
+            Pattern::Field(_, _, expr) => self.expr(expr),
+            Pattern::Coerce(_, pattern) => self.pattern(pattern),
+        }
+    }
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Variable(_)
+            | Expr::Global(..)
+            | Expr::Func(_)
+            | Expr::Closure(..)
+            | Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Boolean(_)
+            | Expr::String(_) => {}
+            Expr::Tuple(exprs)
+            | Expr::Array(_, exprs)
+            | Expr::Builtin(_, exprs, _)
+            | Expr::Struct(_, exprs)
+            | Expr::Variant(_, _, exprs) => {
+                for expr in exprs {
+                    self.expr(expr);
+                }
+            }
+            Expr::NewArray(_, expr, _)
+            | Expr::Length(expr)
+            | Expr::Field(_, _, expr)
+            | Expr::TraitObject(_, expr) => self.expr(expr),
+            Expr::Index(_, array, index, _) => {
+                self.expr(array);
+                self.expr(index);
+            }
+            Expr::Match(_, value, arms, _) => {
+                self.expr(value);
+                for arm in arms {
+                    for binding in &mut arm.bindings {
+                        self.pattern(binding);
+                    }
+                    self.block(&mut arm.body);
+                    self.expr(&mut arm.value);
+                }
+            }
+            Expr::DynamicCall(_, receiver, args, _) => {
+                self.expr(receiver);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Call(func, args, _) => {
+                self.expr(func);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Negation(operand, _) => {
+                self.expr(operand);
+                let folded = match **operand {
+                    Expr::Integer(value) => value.checked_neg().map(Expr::Integer),
+                    Expr::Float(value) => Some(Expr::Float(-value)),
+                    _ => None,
+                };
+                if let Some(folded) = folded {
+                    *expr = folded;
+                }
+            }
+            Expr::BinaryOperation(operator, left, right, _) => {
+                self.expr(left);
+                self.expr(right);
+                if let Some(folded) = fold(operator, left, right) {
+                    *expr = folded;
+                }
+            }
+            Expr::Overflow(_, inner) => {
+                self.expr(inner);
+                if matches!(**inner, Expr::Integer(_)) {
+                    *expr = std::mem::replace(inner, Expr::Integer(0));
+                }
+            }
+        }
+    }
+}
+
+// Integer operations are only folded when they succeed, which is when every
+// overflow mode gives the same result.
+fn fold(operator: &Operator, left: &Expr, right: &Expr) -> Option<Expr> {
+    let folded = match (left, right) {
+        (&Expr::Integer(left), &Expr::Integer(right)) => match operator {
+            Operator::Add => Expr::Integer(left.checked_add(right)?),
+            Operator::Sub => Expr::Integer(left.checked_sub(right)?),
+            Operator::Mul => Expr::Integer(left.checked_mul(right)?),
+            Operator::Div => Expr::Integer(left.checked_div(right)?),
+            Operator::Rem => Expr::Integer(left.checked_rem(right)?),
+            Operator::Equal => Expr::Boolean(left == right),
+            Operator::NotEqual => Expr::Boolean(left != right),
+            Operator::Greater => Expr::Boolean(left > right),
+            Operator::GreaterEqual => Expr::Boolean(left >= right),
+            Operator::Less => Expr::Boolean(left < right),
+            Operator::LessEqual => Expr::Boolean(left <= right),
+            Operator::Assign => unreachable!(),
+        },
+        (&Expr::Float(left), &Expr::Float(right)) => match operator {
+            Operator::Add => Expr::Float(left + right),
+            Operator::Sub => Expr::Float(left - right),
+            Operator::Mul => Expr::Float(left * right),
+            Operator::Div => Expr::Float(left / right),
+            Operator::Equal => Expr::Boolean(left == right),
+            Operator::NotEqual => Expr::Boolean(left != right),
+            Operator::Greater => Expr::Boolean(left > right),
+            Operator::GreaterEqual => Expr::Boolean(left >= right),
+            Operator::Less => Expr::Boolean(left < right),
+            Operator::LessEqual => Expr::Boolean(left <= right),
+            Operator::Rem | Operator::Assign => unreachable!(),
+        },
+        (&Expr::Boolean(left), &Expr::Boolean(right)) => match operator {
+            Operator::Equal => Expr::Boolean(left == right),
+            Operator::NotEqual => Expr::Boolean(left != right),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(folded)
+}
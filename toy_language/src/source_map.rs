@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+
+use crate::span::Span;
+
+// The files of a program are laid out one after the other, each starting on a
+// new line, so an offset alone tells which file it is in.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FileId(u32);
+
+struct File {
+    path: String,
+    start: usize,
+    first_line: usize,
+    expansion: Option<Expansion>,
+}
+
+// Each expansion of a macro gets a copy of the text of its definition, so that
+// the spans of the expanded code are its own. They are reported at the
+// definition, along with where the macro was used.
+struct Expansion {
+    origin: usize,
+    origin_line: usize,
+    note: String,
+}
+
+#[derive(Default)]
+struct SourceMap {
+    text: String,
+    files: Vec<File>,
+}
+
+thread_local! {
+    static SOURCE_MAP: RefCell<SourceMap> = RefCell::default();
+}
+
+pub fn add(path: &str, source: &str) -> FileId {
+    add_file(path, source, None)
+}
+
+// Copies the text of `origin`, which starts a line, for an expansion of the
+// macro `name` at `call`.
+pub fn add_expansion(origin: Span, name: &str, call: Span) -> FileId {
+    let (text, origin_line) = with_text(|text| {
+        let (line, _) = origin.line_column(text);
+        (text[origin.start..origin.end].to_owned(), line)
+    });
+    let expansion = Expansion {
+        origin: origin.start,
+        origin_line,
+        note: format!("expanded from `{name}` at {}", location(call)),
+    };
+    add_file(&format!("<expansion of `{name}`>"), &text, Some(expansion))
+}
+
+fn add_file(path: &str, source: &str, expansion: Option<Expansion>) -> FileId {
+    SOURCE_MAP.with_borrow_mut(|map| {
+        if !map.text.is_empty() && !map.text.ends_with('\n') {
+            map.text.push('\n');
+        }
+        let id = FileId(map.files.len().try_into().unwrap());
+        map.files.push(File {
+            path: path.to_owned(),
+            start: map.text.len(),
+            first_line: map.text.matches('\n').count() + 1,
+            expansion,
+        });
+        map.text.push_str(source);
+        id
+    })
+}
+
+pub fn clear() {
+    SOURCE_MAP.take();
+}
+
+// All the files, at the offsets their spans refer to.
+pub fn with_text<R>(f: impl FnOnce(&str) -> R) -> R {
+    SOURCE_MAP.with_borrow(|map| f(&map.text))
+}
+
+pub fn files() -> Vec<FileId> {
+    SOURCE_MAP.with_borrow(|map| (0..map.files.len() as u32).map(FileId).collect())
+}
+
+pub fn file(offset: usize) -> FileId {
+    SOURCE_MAP.with_borrow(|map| {
+        let index = map.files.partition_point(|file| file.start <= offset);
+        FileId(index.saturating_sub(1) as u32)
+    })
+}
+
+pub fn path(file: FileId) -> String {
+    SOURCE_MAP.with_borrow(|map| map.files[file.index()].path.clone())
+}
+
+pub fn start(file: FileId) -> usize {
+    SOURCE_MAP.with_borrow(|map| map.files[file.index()].start)
+}
+
+// The text of a file, without the offset it starts at.
+pub fn source(file: FileId) -> String {
+    SOURCE_MAP.with_borrow(|map| {
+        let end = map
+            .files
+            .get(file.index() + 1)
+            .map_or(map.text.len(), |next| next.start);
+        map.text[map.files[file.index()].start..end].to_owned()
+    })
+}
+
+pub fn location(span: Span) -> String {
+    let (line, column) = with_text(|text| span.line_column(text));
+    line_location(line, column)
+}
+
+// Where a line and column of the whole text are, as `path:line:column`.
+pub fn line_location(line: usize, column: usize) -> String {
+    let expansion = SOURCE_MAP.with_borrow(|map| {
+        match map.files.iter().rev().find(|file| file.first_line <= line) {
+            Some(File {
+                expansion: Some(expansion),
+                first_line,
+                ..
+            }) => Ok((
+                expansion.origin_line + line - first_line,
+                expansion.note.clone(),
+            )),
+            Some(file) => Err(format!(
+                "{}:{}:{column}",
+                file.path,
+                line - file.first_line + 1
+            )),
+            None => Err(format!("{line}:{column}")),
+        }
+    });
+    match expansion {
+        Ok((line, note)) => format!("{} ({note})", line_location(line, column)),
+        Err(location) => location,
+    }
+}
+
+// The span of the text that `span` was copied from, if it is in an expansion.
+pub fn origin(span: Span) -> Span {
+    let file = file(span.start);
+    SOURCE_MAP.with_borrow(|map| {
+        let file = &map.files[file.index()];
+        match &file.expansion {
+            Some(expansion) => Span::new(
+                span.start - file.start + expansion.origin,
+                span.end - file.start + expansion.origin,
+            ),
+            None => span,
+        }
+    })
+}
+
+impl FileId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
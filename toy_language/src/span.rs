@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<Span>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+    pub fn line_column(&self, input: &str) -> (usize, usize) {
+        let before = &input[..self.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+// The innermost span being processed, so that a panic can be reported at the
+// place that caused it.
+pub fn current() -> Option<Span> {
+    CONTEXT.with_borrow(|context| context.last().copied())
+}
+
+pub fn at(span: Span) {
+    CONTEXT.with_borrow_mut(|context| {
+        context.clear();
+        context.push(span);
+    });
+}
+
+pub fn enter(span: Span) {
+    CONTEXT.with_borrow_mut(|context| context.push(span));
+}
+
+pub fn leave() {
+    CONTEXT.with_borrow_mut(|context| context.pop());
+}
+
+pub fn clear() {
+    CONTEXT.with_borrow_mut(Vec::clear);
+}
@@ -0,0 +1,94 @@
+// Checks the arena of the pre-AST: compacting it and comparing trees.
+use toy_language::parser;
+use toy_language::pre_ast::{self, Ast, NodeId, Operator, Stmt, Term, TermKind};
+use toy_language::span::Span;
+use toy_language::symbol::Symbol;
+
+// Checks that every child comes before its parent in the arena.
+fn assert_well_formed(ast: &Ast) {
+    let mut pending: Vec<_> = ast.stmts.iter().flat_map(Stmt::terms).collect();
+    while let Some(id) = pending.pop() {
+        assert!(id.index() < ast.len());
+        for child in ast[id].kind.children() {
+            assert!(child < id, "{child:?} is not before {id:?}");
+            pending.push(child);
+        }
+    }
+}
+
+#[test]
+fn compact_drops_unreachable_terms() {
+    let mut ast = parser::parse(
+        "a := 1;
+        b := f(2, 3 * 4);
+        g(x: int): int { y := [x, a]; return(y[0]); }
+        c := \"{a}{b}\";",
+    );
+    ast.stmts.remove(1);
+    ast.compact();
+    let expected = parser::parse(
+        "a := 1;
+        g(x: int): int { y := [x, a]; return(y[0]); }
+        c := \"{a}{b}\";",
+    );
+    assert_eq!(ast.len(), expected.len());
+    assert_well_formed(&ast);
+    assert!(ast == expected);
+    assert_eq!(pre_ast::print(&ast), pre_ast::print(&expected));
+}
+
+#[test]
+fn compact_keeps_a_reachable_tree() {
+    let source = "x := (1 + 2) * [3][0]; if (x > 1) { x = -x; }";
+    let mut ast = parser::parse(source);
+    let len = ast.len();
+    ast.compact();
+    assert_eq!(ast.len(), len);
+    assert!(ast == parser::parse(source));
+}
+
+fn integer(ast: &mut Ast, value: i64, start: usize) -> NodeId {
+    ast.push(Term {
+        kind: TermKind::Integer(value),
+        span: Span::new(start, start + 1),
+    })
+}
+
+fn sum(ast: &mut Ast, left: NodeId, right: NodeId) -> NodeId {
+    let id = ast.push(Term {
+        kind: TermKind::BinaryOperation {
+            left_operand: Some(left),
+            operator: Operator::Add,
+            right_operand: Some(right),
+        },
+        span: Span::new(0, 5),
+    });
+    ast.stmts.push(Stmt::Term(Some(id)));
+    id
+}
+
+#[test]
+fn equality_ignores_the_arena_layout() {
+    let mut ast = Ast::default();
+    let one = integer(&mut ast, 1, 0);
+    let two = integer(&mut ast, 2, 4);
+    sum(&mut ast, one, two);
+
+    // The same tree, with its terms in another order, an unreachable term in
+    // between and other spans.
+    let mut other = Ast::default();
+    let two = integer(&mut other, 2, 10);
+    other.push(Term {
+        kind: TermKind::Identifier(Symbol::intern("unreachable")),
+        span: Span::new(0, 11),
+    });
+    let one = integer(&mut other, 1, 20);
+    sum(&mut other, one, two);
+    assert!(ast == other);
+
+    let mut swapped = Ast::default();
+    let one = integer(&mut swapped, 1, 0);
+    let two = integer(&mut swapped, 2, 4);
+    sum(&mut swapped, two, one);
+    assert!(ast != swapped);
+}
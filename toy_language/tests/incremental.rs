@@ -0,0 +1,258 @@
+// Applies random edits to documents and checks that reparsing them
+// incrementally always gives the tree a parse from scratch gives.
+use std::panic::AssertUnwindSafe;
+
+use toy_language::parser::{self, Incremental};
+use toy_language::pre_ast;
+use toy_language::span::Span;
+
+const DOCUMENTS: &[&str] = &[
+    "operator infixl 30 <+> vadd;
+operator prefix 50 ~ negate;
+vadd(a: int, b: int): int {
+    return(a + b);
+}
+negate(a: int): int {
+    return(-a);
+}
+// The operators apply from here on.
+main(): int {
+    x := 1 <+> ~2 <+> 3;
+    /* nested /* comments */ too */
+    println(\"sum {x <+> 1} and {\"inner {~x}\"}\");
+    return(x);
+}
+",
+    "/* leading comment */
+x := [[1, 2], [3]];
+f(a: int): int {
+    if (a > 0) {
+        return(a * 2);
+    } else {
+        return(0);
+    }
+}
+operator infixr 45 ^ power;
+power(a: int, b: int): int {
+    return(a);
+}
+y := f(1) ^ f(2) ^ 3;
+s := \"escaped \\{ brace {y} // not a comment\";
+",
+    "struct Point {
+    x: int,
+    y: int,
+}
+// comment with \"quotes\"
+add := (p: Point): int { return(p.x + p.y); };
+operator postfix 60 ! fact;
+fact(n: int): int {
+    return(n);
+}
+main(): int {
+    p := Point(1, 2);
+    return(add(p)! + \"{p.x!}\".len);
+}
+",
+];
+
+// Text that is inserted by the edits, chosen to open and close comments,
+// strings, interpolations and brackets, and to use or declare operators.
+const FRAGMENTS: &[&str] = &[
+    "operator infixl 30 <+> vadd;\n",
+    "operator prefix 50 ~ negate;\n",
+    "operator infix 20 <=> cmp;\n",
+    "a <+> b",
+    "~",
+    "<+>",
+    "<=>",
+    "!",
+    "^",
+    "/*",
+    "*/",
+    "// ",
+    "\n",
+    "\"",
+    "\"a{b}c\"",
+    "{",
+    "}",
+    "\\{",
+    "(",
+    ")",
+    "[",
+    "]",
+    ";",
+    ",",
+    ".",
+    ":",
+    " ",
+    "x",
+    "1",
+    "2.5",
+    "return(x);",
+    "f(x) { y := 1; }\n",
+];
+
+// A xorshift generator, so that every run makes the same edits.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+// Finds the spans of the text between `open` and `close` on one line, like
+// the insides of comments and string literals.
+fn spans_between(text: &str, open: &str, close: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find(open) {
+        let start = offset + start + open.len();
+        let line_end = text[start..]
+            .find('\n')
+            .map_or(text.len(), |end| start + end);
+        let end = text[start..line_end]
+            .find(close)
+            .map_or(line_end, |end| start + end);
+        spans.push(Span::new(start, end));
+        offset = start;
+    }
+    spans
+}
+
+fn random_edit(rng: &mut Rng, text: &str) -> (Span, String) {
+    let offset = |rng: &mut Rng, span: Span| span.start + rng.below(span.end - span.start + 1);
+    let whole = Span::new(0, text.len());
+    match rng.below(6) {
+        // Declare an operator at the start of a line.
+        0 => {
+            let mut starts: Vec<usize> = text.match_indices('\n').map(|(i, _)| i + 1).collect();
+            starts.push(0);
+            let start = starts[rng.below(starts.len())];
+            let decl = rng.pick(&FRAGMENTS[..3]);
+            (Span::new(start, start), decl.to_owned())
+        }
+        // Remove an operator declaration.
+        1 => match spans_between(text, "operator", ";").as_slice() {
+            [] => (Span::new(0, 0), String::new()),
+            spans => {
+                let span = spans[rng.below(spans.len())];
+                let start = span.start - "operator".len();
+                let end = (span.end + 1).min(text.len());
+                (Span::new(start, end), String::new())
+            }
+        },
+        // Edit inside a comment or a string literal, including the
+        // expressions interpolated into it.
+        2 | 3 => {
+            let mut spans = spans_between(text, "//", "\n");
+            spans.extend(spans_between(text, "/*", "*/"));
+            spans.extend(spans_between(text, "\"", "\""));
+            spans.extend(spans_between(text, "{", "}"));
+            let span = match spans.as_slice() {
+                [] => whole,
+                spans => spans[rng.below(spans.len())],
+            };
+            let start = offset(rng, span);
+            let end = (start + rng.below(3)).min(span.end.max(start));
+            (Span::new(start, end), rng.pick(FRAGMENTS).to_owned())
+        }
+        // Replace text anywhere.
+        _ => {
+            let start = offset(rng, whole);
+            let end = (start + rng.below(8)).min(text.len());
+            let replacement = match rng.below(3) {
+                0 => String::new(),
+                _ => rng.pick(FRAGMENTS).to_owned(),
+            };
+            (Span::new(start, end), replacement)
+        }
+    }
+}
+
+// Returns the tree as JSON, or `None` if the source doesn't parse.
+fn parse(f: impl FnOnce() -> String) -> Option<String> {
+    quietly(f).ok()
+}
+
+fn quietly<T>(f: impl FnOnce() -> T) -> std::thread::Result<T> {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    std::panic::set_hook(hook);
+    result
+}
+
+#[test]
+fn incremental_parses_match_full_parses() {
+    let mut parsed = 0;
+    let mut failed = 0;
+    for seed in 1..=60u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut text = DOCUMENTS[seed as usize % DOCUMENTS.len()].to_owned();
+        let mut incremental = Incremental::new(text.clone());
+        let mut undo = Vec::new();
+        let mut last_parsed = true;
+        for step in 0..150 {
+            // Undoing edits gets back to text that parses, so that edits are
+            // also made to documents whose last parse succeeded.
+            let (span, replacement) = match rng.below(4) {
+                0..3 if !last_parsed && !undo.is_empty() => undo.pop().unwrap(),
+                _ => {
+                    let (span, replacement) = random_edit(&mut rng, &text);
+                    let removed = text[span.start..span.end].to_owned();
+                    undo.push((
+                        Span::new(span.start, span.start + replacement.len()),
+                        removed,
+                    ));
+                    (span, replacement)
+                }
+            };
+            text.replace_range(span.start..span.end, &replacement);
+            // The edit itself reparses, which may fail.
+            let _ = quietly(|| incremental.edit(span, &replacement));
+            assert_eq!(incremental.text(), text);
+            let expected = parse(|| pre_ast::json(&parser::parse(&text)));
+            let actual = parse(|| pre_ast::json(incremental.ast()));
+            assert!(
+                actual == expected,
+                "seed {seed}, step {step}: replacing {span:?} with {replacement:?} gives\n\
+                 {text}\nincremental: {actual:?}\nfull: {expected:?}"
+            );
+            last_parsed = expected.is_some();
+            match expected {
+                Some(_) => parsed += 1,
+                None => failed += 1,
+            }
+        }
+    }
+    // Both kinds of edits need to be common for the comparison to mean much.
+    assert!(
+        parsed > 2000 && failed > 2000,
+        "{parsed} parsed, {failed} failed"
+    );
+}
+
+#[test]
+fn edit_that_comments_out_a_declaration() {
+    let text = "// note\noperator infixl 30 <+> add;\nx := 1 <+> 2;\n";
+    let mut incremental = Incremental::new(text.to_owned());
+    incremental.ast();
+    // Joining the lines turns the declaration into part of the comment, so
+    // the statement after it can't be reused.
+    let _ = quietly(|| incremental.edit(Span::new(7, 8), " "));
+    let text = incremental.text().to_owned();
+    let expected = parse(|| pre_ast::json(&parser::parse(&text)));
+    let actual = parse(|| pre_ast::json(incremental.ast()));
+    assert_eq!(actual, expected);
+}
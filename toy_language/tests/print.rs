@@ -0,0 +1,68 @@
+// Checks that printing a parsed program gives source that parses back to the
+// same tree, and that printing is idempotent.
+use toy_language::{parser, pre_ast};
+
+fn assert_round_trips(source: &str) {
+    let ast = parser::parse(source);
+    let printed = pre_ast::print(&ast);
+    let reparsed = parser::parse(&printed);
+    assert!(
+        reparsed == ast,
+        "printed source parses differently:\n{printed}"
+    );
+    assert_eq!(pre_ast::print(&reparsed), printed);
+}
+
+#[test]
+fn escapes() {
+    assert_round_trips(r#"s := "quote \" backslash \\ brace \{ tab	end";"#);
+    assert_round_trips(r#"s := "\\{not interpolated}\\";"#);
+    assert_round_trips("s := \"line\nbreak\";");
+}
+
+#[test]
+fn interpolation() {
+    assert_round_trips(r#"s := "x = {1 + 2}, nested {"in{x}ner \{"}!";"#);
+    assert_round_trips(r#"s := "{a}{b}";"#);
+}
+
+#[test]
+fn floats() {
+    assert_round_trips("f := 1.0; g := 0.1; h := 2.50;");
+    assert_round_trips("f := 100000000000000000000.0;");
+    assert_round_trips("f := 9223372036854775807.5;");
+    assert_round_trips("f := 0.0000000000000000000001;");
+    assert_round_trips("f := -1.5 * 3.0;");
+}
+
+#[test]
+fn nested_brackets() {
+    assert_round_trips("n := [[1, 2], [3]]((1,), ((2)));");
+    assert_round_trips("k := ((((x))));");
+    assert_round_trips("t := ((), (1,), [], [[]]);");
+    assert_round_trips("c := f(x)[i](y)(z)[j][k];");
+}
+
+#[test]
+fn lambdas() {
+    assert_round_trips("add := (x: int): int { return(x + 1); };");
+    assert_round_trips("bump := [count](by: int) { count = count + by; };");
+    assert_round_trips("f := (): (int): int { return((x: int): int { return(x); }); };");
+    assert_round_trips("apply((x: int): int { return(x); }, 1);");
+}
+
+#[test]
+fn user_operators() {
+    assert_round_trips(
+        "
+        operator infixl 30 <+> vadd;
+        operator prefix 50 ~ negate;
+        operator postfix 60 ! fact;
+        operator infixr 45 ^ power;
+        v := a <+> ~b <+> -c;
+        w := 3! ^ 2! ^ 1;
+        x := (a <+> b) ^ c;
+        ",
+    );
+    assert_round_trips("z := - -x; y := 1 - -2; w := (1 - 2) - (3 - 4);");
+}
@@ -0,0 +1,54 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn literals_indexing_and_assignment() {
+    assert_output(
+        "sum(a: [int]): int {
+    s := 0;
+    i := 0;
+    while (i < len(a)) {
+        s = s + a[i];
+        i = i + 1;
+    }
+    return(s);
+}
+main(): int {
+    a := [1, 2, 3];
+    b := [int](4);
+    b[3] = 10;
+    grid := [[1, 2], [3, 4]];
+    grid[1][0] = 7;
+    pairs := [(1, 2.5), (3, 4.5)];
+    (x:, y:) = pairs[1];
+    empty: [int];
+    if (y != 4.5) {
+        return(1);
+    }
+    return(sum(a) + sum(b) * 10 + grid[1][0] * 20 + x + len(empty));
+}
+",
+        "",
+        249,
+    );
+}
+
+#[test]
+fn index_out_of_bounds() {
+    assert_error(
+        "get(a: [int], i: int): int { return(a[i]); }
+main(): int { a := [1, 2, 3]; return(get(a, 3)); }
+",
+        13,
+        "runtime error at main.toy:1:37: index 3 out of bounds for length 3\n",
+    );
+}
+
+#[test]
+fn negative_length() {
+    assert_error(
+        "main(): int { n := -1; a := [int](n); return(0); }
+",
+        13,
+        "runtime error at main.toy:1:29: negative array length -1",
+    );
+}
@@ -0,0 +1,59 @@
+use crate::assert_output;
+
+#[test]
+fn lambdas_and_captures() {
+    assert_output(
+        "apply(f: fn(int): int, x: int): int {
+    return(f(x));
+}
+twice(x: int): int { return(x * 2); }
+make_adder(n: int): fn(int): int {
+    return((x: int): int { return(x + n); });
+}
+main(): int {
+    count := 0;
+    bump := [count](by: int) { count = count + by; };
+    bump(5);
+    bump(2);
+    snapshot := (): int { return(count); };
+    count = 100;
+    fs := [make_adder(3), twice];
+    if (apply(fs[0], 1) != 4) {
+        return(1);
+    }
+    if (apply(fs[1], 1) != 2) {
+        return(2);
+    }
+    if (snapshot() != 7) {
+        return(3);
+    }
+    if (make_adder(4)(1) != 5) {
+        return(4);
+    }
+    return(count);
+}
+",
+        "",
+        100,
+    );
+}
+
+#[test]
+fn nested_captures_by_reference() {
+    assert_output(
+        "main(): int {
+    total := 0;
+    add := [total](x: int) {
+        inner := [total]() { total = total + x; };
+        inner();
+        inner();
+    };
+    add(3);
+    add(4);
+    return(total);
+}
+",
+        "",
+        14,
+    );
+}
@@ -0,0 +1,116 @@
+use crate::{assert_error, assert_output, run_files};
+
+#[test]
+fn constants_and_generated_code() {
+    assert_output(
+        "fib(n: int): int {
+    if (n < 2) {
+        return(n);
+    }
+    return(fib(n - 1) + fib(n - 2));
+}
+const SIZE := fib(10);
+const NAME: string = \"toy\";
+const HALF := 0.5;
+const BIG := SIZE > 50;
+comptime {
+    code := \"\";
+    i := 0;
+    while (i < 3) {
+        code = \"{code}get{i}(): int \\{ return({i * i}); } \";
+        i = i + 1;
+    }
+    code;
+}
+main(): int {
+    a := [int](SIZE);
+    println(len(a));
+    println(\"{NAME} {HALF} {BIG}\");
+    println(get2());
+    println(comptime { x := 6; x * 7; });
+    return(SIZE % 7);
+}
+",
+        "55\ntoy 0.5 true\n4\n42\n",
+        6,
+    );
+}
+
+#[test]
+fn constant_errors() {
+    assert_error(
+        "const A := B;\nconst B := A;\nmain(): int { return(A); }\n",
+        12,
+        "type error at main.toy:2:12: constant `A` depends on itself\n",
+    );
+    assert_error(
+        "g := 1;\nconst A := g + 1;\nmain(): int { return(A); }\n",
+        12,
+        "type error at main.toy:2:12: global `g` cannot be used at compile time\n",
+    );
+    assert_error(
+        "const A := (1, 2);\nmain(): int { return(0); }\n",
+        12,
+        "type error at main.toy:1:12: a value of type `(int, int)` cannot be computed at compile time\n",
+    );
+    assert_error(
+        "const A := 1;\nmain(): int { A = 2; return(A); }\n",
+        12,
+        "type error at main.toy:2:15: cannot assign to constant `A`\n",
+    );
+}
+
+#[test]
+fn generated_code_errors() {
+    assert_error(
+        "comptime { 42; }\nmain(): int { return(0); }\n",
+        12,
+        "type error at main.toy:1:1: mismatched type of generated code: expected `string`, found `int`\n",
+    );
+    // Errors in the generated code point into it.
+    assert_error(
+        "comptime { \"x := ;\"; }\nmain(): int { return(0); }\n",
+        12,
+        "type error at <generated by main.toy:1:1>:1:1: invalid expression\n",
+    );
+}
+
+#[test]
+fn runtime_errors_at_compile_time() {
+    assert_error(
+        "const A := 1 / 0;\nmain(): int { return(A); }\n",
+        12,
+        "type error at main.toy:1:12: division by zero while evaluating at compile time\n    \
+         at main.toy:1:12\n",
+    );
+    assert_error(
+        "f(): int { return(1 / 0); }\nconst A := f();\nmain(): int { return(A); }\n",
+        12,
+        "type error at main.toy:2:12: division by zero while evaluating at compile time\n    \
+         at main.toy:1:19\n    in f\n",
+    );
+}
+
+#[test]
+fn effects_at_compile_time() {
+    assert_error(
+        "main(): int { x := comptime { println(3); 4; }; return(x); }\n",
+        12,
+        "type error at main.toy:1:20: cannot print at compile time\n",
+    );
+    assert_error(
+        "main(): int { return(comptime { read_line(); 1; }); }\n",
+        12,
+        "type error at main.toy:1:22: cannot read input at compile time\n",
+    );
+    let source = "const A := comptime { exit(0); 1; };\nmain(): int { return(A); }\n";
+    for options in [&["--emit=obj", "-o", "main.o"][..], &["--emit=clif"]] {
+        let output = run_files(options, &[("main.toy", source)]);
+        assert_eq!(output.stdout, "");
+        assert_eq!(
+            output.stderr,
+            "type error at main.toy:1:12: cannot exit at compile time\n"
+        );
+        assert_eq!(output.code, 12);
+    }
+}
@@ -0,0 +1,103 @@
+use serde_json::json;
+
+use crate::run_files;
+
+const SOURCE: &str = "x := f(-1.5, \"a{y}\",)[0];\ny := 100000000000000000000.0;\n";
+
+#[test]
+fn ast_json() {
+    let output = run_files(&["--emit=ast-json"], &[("main.toy", SOURCE)]);
+    assert_eq!(output.code, 0, "{}", output.stderr);
+    let dump: serde_json::Value = serde_json::from_str(&output.stdout).unwrap();
+    let identifier = |start: usize, name: &str| {
+        let span = [start, start + 1];
+        json!({"kind": "identifier", "span": span, "name": name})
+    };
+    let declaration = |start: usize, name: &str| {
+        json!({
+            "kind": "declaration",
+            "span": [start, start + 3],
+            "term": identifier(start, name),
+            "type": null,
+        })
+    };
+    let call = json!({
+        "kind": "bracketed",
+        "span": [5, 21],
+        "antecedent": identifier(5, "f"),
+        "bracket": "round",
+        "elements": [
+            {
+                "kind": "binary_operation",
+                "span": [7, 11],
+                "operator": "-",
+                "left": null,
+                "right": {"kind": "float", "span": [8, 11], "value": 1.5},
+            },
+            {
+                "kind": "string",
+                "span": [13, 19],
+                "components": [{"kind": "text", "value": "a"}, identifier(16, "y")],
+            },
+        ],
+        "trailing_comma": true,
+    });
+    assert_eq!(
+        dump,
+        json!({
+            "version": 1,
+            "stmts": [
+                {
+                    "kind": "term_stmt",
+                    "term": {
+                        "kind": "assignment",
+                        "span": [0, 24],
+                        "operator": "=",
+                        "left": declaration(0, "x"),
+                        "right": {
+                            "kind": "bracketed",
+                            "span": [5, 24],
+                            "antecedent": call,
+                            "bracket": "square",
+                            "elements": [{"kind": "integer", "span": [22, 23], "value": 0}],
+                            "trailing_comma": false,
+                        },
+                    },
+                },
+                {
+                    "kind": "term_stmt",
+                    "term": {
+                        "kind": "assignment",
+                        "span": [26, 54],
+                        "operator": "=",
+                        "left": declaration(26, "y"),
+                        "right": {"kind": "float", "span": [31, 54], "value": 1e20},
+                    },
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn ast_sexpr() {
+    let output = run_files(&["--emit=ast-sexpr"], &[("main.toy", SOURCE)]);
+    assert_eq!(
+        output.stdout,
+        "(program (version 1)
+  (term_stmt (term (assignment (span 0 24) (operator \"=\") \
+(left (declaration (span 0 3) (term (identifier (span 0 1) (name \"x\"))) (type nil))) \
+(right (bracketed (span 5 24) (antecedent (bracketed (span 5 21) \
+(antecedent (identifier (span 5 6) (name \"f\"))) (bracket \"round\") \
+(elements (binary_operation (span 7 11) (operator \"-\") (left nil) \
+(right (float (span 8 11) (value 1.5)))) (string (span 13 19) \
+(components (text (value \"a\")) (identifier (span 16 17) (name \"y\"))))) \
+(trailing_comma true))) (bracket \"square\") (elements (integer (span 22 23) (value 0))) \
+(trailing_comma false))))))
+  (term_stmt (term (assignment (span 26 54) (operator \"=\") \
+(left (declaration (span 26 29) (term (identifier (span 26 27) (name \"y\"))) (type nil))) \
+(right (float (span 31 54) (value 1e20)))))))
+"
+    );
+    assert_eq!(output.code, 0);
+}
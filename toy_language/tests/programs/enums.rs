@@ -0,0 +1,48 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn match_on_payloads() {
+    assert_output(
+        "enum Shape { Circle(float); Rect(float, float); Empty; }
+area(s: Shape): float {
+    a := match (s) {
+        Circle(r) { r * r * 3.0; }
+        Rect(w, h) { p := w * h; p; }
+        Empty { 0.0; }
+    };
+    return(a);
+}
+main(): int {
+    shapes := [Circle(1.0), Rect(2.0, 5.0), Empty];
+    total := 0.0;
+    rects := 0;
+    i := 0;
+    while (i < len(shapes)) {
+        total = total + area(shapes[i]);
+        match (shapes[i]) {
+            Rect(_, h) { rects = rects + 1; }
+            _ { }
+        }
+        i = i + 1;
+    }
+    if (total != 13.0) {
+        return(0);
+    }
+    return(rects);
+}
+",
+        "",
+        1,
+    );
+}
+
+#[test]
+fn non_exhaustive_match() {
+    assert_error(
+        "enum E { A; B(int); }
+f(e: E): int { match (e) { A { return(1); } } return(0); }
+",
+        12,
+        "type error at main.toy:2:16: non-exhaustive match: missing `B`\n",
+    );
+}
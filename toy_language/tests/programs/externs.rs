@@ -0,0 +1,75 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn calls_into_libc() {
+    assert_output(
+        "extern fn strlen(s: string): int;
+extern fn labs(x: int): int;
+extern fn atof(s: string): float;
+extern fn getenv(name: string): ptr;
+main(): int {
+    f := labs;
+    if (atof(\"1.5\") * 2.0 != 3.0) { return(1); }
+    return(strlen(\"abcd\") + f(-3));
+}
+",
+        "",
+        7,
+    );
+}
+
+#[test]
+fn unresolved_symbol() {
+    assert_error(
+        "extern fn nosuchthing(s: string): float;
+main(): int { x := nosuchthing(\"4.5\"); return(0); }
+",
+        12,
+        "type error: unresolved extern function `nosuchthing`\n",
+    );
+}
+
+#[test]
+fn null_string_result() {
+    assert_error(
+        "extern fn getenv(name: string): string;
+extern fn strlen(s: string): int;
+show(name: string): int {
+    f := getenv;
+    return(strlen(f(name)));
+}
+main(): int {
+    return(show(\"TOY_LANGUAGE_UNSET_VARIABLE\"));
+}
+",
+        13,
+        "runtime error at main.toy:1:11: extern function returned a null string\n",
+    );
+}
+
+#[test]
+fn new_arrays_hold_empty_strings() {
+    assert_output(
+        "extern fn strlen(s: string): int;
+main(): int {
+    a := [string](2);
+    b := [(int, string)](3);
+    (_, s:) = b[2];
+    return(strlen(a[1]) + strlen(s) + strlen(\"{a[0]}{s}x\"));
+}
+",
+        "",
+        1,
+    );
+}
+
+#[test]
+fn unsupported_parameter_type() {
+    assert_error(
+        "extern fn f(a: [int]): int;
+main(): int { return(0); }
+",
+        12,
+        "type error at main.toy:1:11: unsupported type in extern function `f`\n",
+    );
+}
@@ -0,0 +1,90 @@
+use crate::{assert_output, run_files};
+
+#[test]
+fn reference_in_last_word_of_wide_variant() {
+    let fields = ["int"; 63].join(", ");
+    let values: Vec<_> = (0..63).map(|value| value.to_string()).collect();
+    let discards = ["_"; 63].join(", ");
+    let output = run_files(
+        &["--gc-stress"],
+        &[(
+            "main.toy",
+            &format!(
+                "enum Big {{ Wide({fields}, [int]); Empty; }}
+main(): int {{
+    b := Wide({}, [7, 8, 9]);
+    i := 0;
+    while (i < 100) {{
+        a := [i, i + 1];
+        i = i + 1;
+    }}
+    match (b) {{
+        Wide({discards}, s) {{ println(s[1]); }}
+        Empty {{ }}
+    }}
+    return(0);
+}}
+",
+                values.join(", ")
+            ),
+        )],
+    );
+    assert_eq!(output.stdout, "8\n", "stderr: {}", output.stderr);
+}
+
+#[test]
+fn linked_list_survives_collections() {
+    assert_output(
+        "enum List { Cons(int, List); Nil; }
+sum(list: List): int {
+    s := match (list) {
+        Cons(head, tail) { head + sum(tail); }
+        Nil { 0; }
+    };
+    return(s);
+}
+main(): int {
+    list := Nil;
+    i := 1;
+    while (i <= 200) {
+        list = Cons(i, list);
+        garbage := [i, i, i];
+        i = i + 1;
+    }
+    println(sum(list));
+    return(0);
+}
+",
+        "20100\n",
+        0,
+    );
+}
+
+#[test]
+fn closures_globals_and_strings_survive_collections() {
+    assert_output(
+        "struct Node { value: int; next: [Node]; }
+kept: [string] = [\"a\", \"b\"];
+make(n: int): fn(): string {
+    label := \"n{n}\";
+    return((): string { return(\"{label}!\"); });
+}
+main(): int {
+    fs := [make(1), make(2)];
+    nodes := [Node](3);
+    i := 0;
+    while (i < 3) {
+        nodes[i] = Node(i, [Node](0));
+        i = i + 1;
+    }
+    kept[1] = \"c{nodes[2].value}\";
+    println(fs[0]());
+    println(fs[1]());
+    println(kept[1]);
+    return(0);
+}
+",
+        "n1!\nn2!\nc2\n",
+        0,
+    );
+}
@@ -0,0 +1,48 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn generic_functions_and_structs() {
+    assert_output(
+        "struct Pair[A, B] {
+    first: A;
+    second: B;
+}
+swap[A, B](p: Pair[A, B]): Pair[B, A] {
+    return(Pair(p.second, p.first));
+}
+id[T](x: T): T { return(x); }
+map[T, U](xs: [T], f: fn(T): U): [U] {
+    out := [U](len(xs));
+    i := 0;
+    while (i < len(xs)) { out[i] = f(xs[i]); i = i + 1; }
+    return(out);
+}
+main(): int {
+    q := swap(Pair[int, float](1, 2.5));
+    if (q.first != 2.5) {
+        return(1);
+    }
+    halves := map([1, 2, 3], (x: int): float { return(id[float](0.5)); });
+    if (halves[2] != 0.5) {
+        return(2);
+    }
+    s := id(\"s\");
+    g := id[int];
+    return(g(10) + q.second);
+}
+",
+        "",
+        11,
+    );
+}
+
+#[test]
+fn missing_type_arguments() {
+    assert_error(
+        "id[T](x: T): T { return(x); }
+main(): int { f := id; return(0); }
+",
+        12,
+        "type error at main.toy:2:20: missing type arguments to `id`\n",
+    );
+}
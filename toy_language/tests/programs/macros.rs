@@ -0,0 +1,211 @@
+use crate::{assert_error, assert_files_output, assert_output, run_files};
+
+#[test]
+fn expands_statements_and_expressions() {
+    // The `tmp` the macro declares is not the caller's `tmp`.
+    assert_output(
+        "macro swap(a, b) {
+    tmp := a;
+    a = b;
+    b = tmp;
+}
+macro square(x) {
+    x * x;
+}
+main(): int {
+    tmp := 1;
+    other := 2;
+    swap(tmp, other);
+    println(\"{tmp} {other}\");
+    return(square(tmp + 1));
+}
+",
+        "2 1\n",
+        9,
+    );
+}
+
+#[test]
+fn locals_do_not_escape() {
+    assert_error(
+        "macro declare(x) {
+    y := x;
+}
+main(): int {
+    declare(1);
+    return(y);
+}
+",
+        12,
+        "type error at main.toy:6:12: undefined variable `y`\n",
+    );
+}
+
+#[test]
+fn imports_public_macros() {
+    assert_files_output(
+        &[
+            (
+                "main.toy",
+                "import \"lib/ops.toy\";\nmain(): int { return(divide(84, 2)); }\n",
+            ),
+            ("lib/ops.toy", "pub macro divide(a, b) {\n    a / b;\n}\n"),
+        ],
+        "",
+        42,
+    );
+}
+
+#[test]
+fn errors_in_expansions() {
+    assert_error(
+        "macro bad(x) {
+    x + \"a\";
+}
+main(): int {
+    return(bad(1));
+}
+",
+        12,
+        "type error at main.toy:2:5 (expanded from `bad` at main.toy:5:12): mismatched operand types `int` and `string` of `+`\n",
+    );
+    let output = run_files(
+        &[],
+        &[
+            (
+                "main.toy",
+                "import \"lib/ops.toy\";\nmain(): int { return(divide(1, 0)); }\n",
+            ),
+            ("lib/ops.toy", "pub macro divide(a, b) {\n    a / b;\n}\n"),
+        ],
+    );
+    assert_eq!(
+        output.stderr,
+        "runtime error at lib/ops.toy:2:5 (expanded from `divide` at main.toy:2:22): division by zero\n    in main\n"
+    );
+    assert_eq!(output.code, 13);
+}
+
+#[test]
+fn invalid_calls() {
+    assert_error(
+        "macro twice(x) {
+    x;
+    x;
+}
+main(): int {
+    return(twice(1));
+}
+",
+        11,
+        "syntax error at main.toy:6:12: `twice` does not expand to an expression (defined at main.toy:1:1)\n",
+    );
+    assert_error(
+        "macro one(x) {
+    x;
+}
+main(): int {
+    return(one(1, 2));
+}
+",
+        11,
+        "syntax error at main.toy:5:12: `one` takes 1 arguments but 2 were given (defined at main.toy:1:1)\n",
+    );
+}
+
+#[test]
+fn recursion_limit() {
+    assert_error(
+        "macro forever(x) {
+    forever(x);
+}
+main(): int {
+    forever(1);
+    return(0);
+}
+",
+        11,
+        "syntax error at main.toy:5:5: too many nested expansions of `forever` (defined at main.toy:1:1)\n",
+    );
+}
+
+#[test]
+fn names_refer_to_the_definition_site() {
+    // The caller's locals don't capture the names the macro body uses.
+    assert_output(
+        "g := 5;
+macro getg() {
+    g;
+}
+main(): int {
+    g := 40;
+    return(getg());
+}
+",
+        "",
+        5,
+    );
+    assert_output(
+        "helper(x: int): int {
+    return(x + 1);
+}
+macro inc(x) {
+    helper(x);
+}
+main(): int {
+    helper := 100;
+    return(inc(helper));
+}
+",
+        "",
+        101,
+    );
+    assert_output(
+        "count := 0;
+macro bump() {
+    count = count + 1;
+}
+main(): int {
+    count := 10;
+    bump();
+    bump();
+    println(count);
+    return(get());
+}
+get(): int {
+    return(count);
+}
+",
+        "10\n",
+        2,
+    );
+}
+
+#[test]
+fn names_refer_to_the_defining_module() {
+    assert_files_output(
+        &[
+            (
+                "main.toy",
+                "import \"lib/secret.toy\";
+main(): int {
+    secret := 1;
+    return(reveal() + secret);
+}
+",
+            ),
+            (
+                "lib/secret.toy",
+                "secret(): int {
+    return(7);
+}
+pub macro reveal() {
+    secret();
+}
+",
+            ),
+        ],
+        "",
+        8,
+    );
+}
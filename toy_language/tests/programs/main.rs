@@ -0,0 +1,107 @@
+// End-to-end tests that compile and run toy programs with the driver.
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod arrays;
+mod closures;
+mod comptime;
+mod dumps;
+mod enums;
+mod externs;
+mod gc;
+mod generics;
+mod macros;
+mod modules;
+mod objects;
+mod operators;
+mod overflow;
+mod prelude;
+mod runtime_errors;
+mod simplify;
+mod structs;
+mod syntax_errors;
+mod traits;
+mod tuples;
+mod type_errors;
+
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i32,
+}
+
+// Writes the files into a fresh directory and runs the driver on the first.
+pub fn run_files(options: &[&str], files: &[(&str, &str)]) -> Output {
+    run_with_input(options, files, "")
+}
+
+pub fn run_with_input(options: &[&str], files: &[(&str, &str)], input: &str) -> Output {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("programs")
+        .join(COUNT.fetch_add(1, Ordering::Relaxed).to_string());
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, source) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    let mut child = Command::new(env!("CARGO_BIN_EXE_toy_language"))
+        .current_dir(&dir)
+        .arg(files[0].0)
+        .args(options)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    Output {
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+        code: output.status.code().expect("killed by a signal"),
+    }
+}
+
+pub fn run(source: &str) -> Output {
+    run_files(&[], &[("main.toy", source)])
+}
+
+// Checks that the program exits with `code` after printing `stdout`, also
+// when garbage is collected on every allocation.
+pub fn assert_output(source: &str, stdout: &str, code: i32) {
+    assert_files_output(&[("main.toy", source)], stdout, code);
+}
+
+// Like `assert_output`, for a program whose main module is the first file.
+pub fn assert_files_output(files: &[(&str, &str)], stdout: &str, code: i32) {
+    for options in [&[][..], &["--gc-stress"]] {
+        let output = run_files(options, files);
+        assert_eq!(
+            output.stdout, stdout,
+            "{options:?} stderr: {}",
+            output.stderr
+        );
+        assert_eq!(output.code, code, "{options:?} stderr: {}", output.stderr);
+    }
+}
+
+// Checks that the program fails with `code` and an error starting with
+// `stderr`.
+pub fn assert_error(source: &str, code: i32, stderr: &str) {
+    let output = run(source);
+    assert!(
+        output.stderr.starts_with(stderr),
+        "expected {stderr:?}, got {:?}",
+        output.stderr
+    );
+    assert_eq!(output.code, code);
+}
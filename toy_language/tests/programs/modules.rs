@@ -0,0 +1,162 @@
+use crate::{assert_files_output, run_files};
+
+const MATH: &str = "pub square(x: int): int {
+    return(helper(x) * x);
+}
+helper(x: int): int {
+    return(x);
+}
+pub counter := square(3);
+";
+
+fn assert_files_error(files: &[(&str, &str)], code: i32, stderr: &str) {
+    let output = run_files(&[], files);
+    assert!(
+        output.stderr.starts_with(stderr),
+        "expected {stderr:?}, got {:?}",
+        output.stderr
+    );
+    assert_eq!(output.code, code);
+}
+
+#[test]
+fn imports_public_items() {
+    assert_files_output(
+        &[
+            (
+                "main.toy",
+                "import \"lib/math.toy\";
+import \"lib/text.toy\";
+helper(): int {
+    return(100);
+}
+main(): int {
+    println(square(7));
+    println(greet(\"toy\"));
+    println(counter);
+    println(helper());
+    return(square(2) + 1);
+}
+",
+            ),
+            ("lib/math.toy", MATH),
+            // Paths are relative to the importing file, and a module imported
+            // twice is loaded once.
+            (
+                "lib/text.toy",
+                "import \"math.toy\";
+pub greet(name: string): string {
+    return(\"hello {name} {square(2)}\");
+}
+",
+            ),
+        ],
+        "49\nhello toy 4\n9\n100\n",
+        5,
+    );
+}
+
+#[test]
+fn private_items_are_hidden() {
+    assert_files_error(
+        &[
+            (
+                "main.toy",
+                "import \"lib/math.toy\";\nmain(): int { return(helper(1)); }\n",
+            ),
+            ("lib/math.toy", MATH),
+        ],
+        12,
+        "type error at main.toy:2:22: undefined variable `helper`\n",
+    );
+}
+
+#[test]
+fn files_that_differ_in_extension() {
+    let files = [
+        (
+            "main.toy",
+            "import \"util.toy\";
+import \"util.tpl\";
+main(): int {
+    return(first() * 10 + second());
+}
+",
+        ),
+        (
+            "util.toy",
+            "pub first(): int { return(helper()); }\nhelper(): int { return(1); }\n",
+        ),
+        (
+            "util.tpl",
+            "pub second(): int { return(helper()); }\nhelper(): int { return(2); }\n",
+        ),
+    ];
+    assert_files_output(&files, "", 12);
+    // The functions named `helper` get different symbols.
+    let output = run_files(&["--emit=obj", "-o", "main.o"], &files);
+    assert_eq!(output.stderr, "");
+    assert_eq!(output.code, 0);
+}
+
+#[test]
+fn import_cycle() {
+    assert_files_error(
+        &[
+            (
+                "main.toy",
+                "import \"a.toy\";\nmain(): int { return(0); }\n",
+            ),
+            ("a.toy", "import \"b.toy\";\npub a(): int { return(0); }\n"),
+            ("b.toy", "import \"a.toy\";\n"),
+        ],
+        11,
+        "syntax error at b.toy:1:8: import cycle: a.toy -> b.toy -> a.toy\n",
+    );
+}
+
+#[test]
+fn missing_module() {
+    assert_files_error(
+        &[("main.toy", "import \"nope.toy\";\n")],
+        11,
+        "syntax error at main.toy:1:8: cannot read `nope.toy`: ",
+    );
+}
+
+#[test]
+fn errors_in_imported_files() {
+    assert_files_error(
+        &[
+            ("main.toy", "import \"lib/bad.toy\";\n"),
+            ("lib/bad.toy", "x := (1 + ;\n"),
+        ],
+        11,
+        "syntax error at lib/bad.toy:1:11: expected `,` or `)`\n",
+    );
+    assert_files_error(
+        &[
+            (
+                "main.toy",
+                "import \"lib/bad.toy\";\nmain(): int { return(f()); }\n",
+            ),
+            ("lib/bad.toy", "pub f(): int { return(1 + \"a\"); }\n"),
+        ],
+        12,
+        "type error at lib/bad.toy:1:23: mismatched operand types `int` and `string` of `+`\n",
+    );
+    assert_files_error(
+        &[
+            (
+                "main.toy",
+                "import \"lib/bad.toy\";\nmain(): int { return(f([1])); }\n",
+            ),
+            ("lib/bad.toy", "pub f(a: [int]): int { return(a[5]); }\n"),
+        ],
+        13,
+        "runtime error at lib/bad.toy:1:31: index 5 out of bounds for length 1
+    in lib/bad.toy::f
+    in main
+",
+    );
+}
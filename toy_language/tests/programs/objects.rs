@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::run_files;
+
+// Stands in for the runtime functions the test programs call.
+const RUNTIME: &str = r#"
+#include <stdio.h>
+#include <stdlib.h>
+
+void toy_rt_print_int(long value) { printf("%ld", value); }
+void toy_rt_print_newline(void) { printf("\n"); }
+void toy_rt_integer_overflow(long line, long column, void *frame) { abort(); }
+long helper(long x) { return x * 1000; }
+"#;
+
+// Emits an object for the program and links it with the runtime stand-ins,
+// returning what the executable prints and its exit status.
+fn link_and_run(name: &str, source: &str) -> (String, i32) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("objects");
+    std::fs::create_dir_all(&dir).unwrap();
+    let object = dir.join(format!("{name}.o"));
+    let runtime = dir.join(format!("{name}_runtime.c"));
+    let executable = dir.join(name);
+    std::fs::write(&runtime, RUNTIME).unwrap();
+    let output = run_files(
+        &["--emit=obj", "-o", object.to_str().unwrap()],
+        &[("main.toy", source)],
+    );
+    assert_eq!(output.code, 0, "{}", output.stderr);
+    let status = Command::new("cc")
+        .arg(&object)
+        .arg(&runtime)
+        .arg("-o")
+        .arg(&executable)
+        .status()
+        .expect("cannot run cc");
+    assert!(status.success());
+    let output = Command::new(&executable).output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        output.status.code().unwrap(),
+    )
+}
+
+#[test]
+fn exports_an_entry_point() {
+    // The program's own `helper` is local, so it doesn't clash with the C one.
+    let (stdout, code) = link_and_run(
+        "entry_point",
+        "base := 40;
+helper(x: int): int {
+    return(x + 2);
+}
+main(): int {
+    println(helper(base));
+    return(7);
+}
+",
+    );
+    assert_eq!(stdout, "42\n");
+    assert_eq!(code, 7);
+}
+
+#[test]
+fn initializes_globals_without_main() {
+    let (stdout, code) = link_and_run("no_main", "println(6 * 7);\n");
+    assert_eq!(stdout, "42\n");
+    assert_eq!(code, 0);
+}
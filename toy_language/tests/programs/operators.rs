@@ -0,0 +1,81 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn declared_operators() {
+    assert_output(
+        "operator infixr 60 ^ power;
+operator postfix 70 ! factorial;
+operator prefix 50 ~ negate;
+operator infix 15 <=> compare;
+power(base: int, exponent: int): int {
+    if (exponent == 0) {
+        return(1);
+    }
+    return(base * power(base, exponent - 1));
+}
+factorial(n: int): int {
+    if (n < 2) {
+        return(1);
+    }
+    return(n * factorial(n - 1));
+}
+negate(x: int): int {
+    return(0 - x);
+}
+compare(a: int, b: int): int {
+    if (a < b) {
+        return(-1);
+    }
+    if (a > b) {
+        return(1);
+    }
+    return(0);
+}
+main(): int {
+    println(2 ^ 3 ^ 2);
+    println(3! + 1);
+    println(~2 * 3);
+    println(~3!);
+    println(1 + 2 <=> 3);
+    println(2<=>1);
+    return(2 ^ 2!);
+}
+",
+        "512\n7\n-6\n-6\n0\n1\n",
+        4,
+    );
+}
+
+#[test]
+fn chained_non_associative_operator() {
+    assert_error(
+        "operator infix 15 <=> compare;
+compare(a: int, b: int): int {
+    return(0);
+}
+main(): int {
+    return(1 <=> 2 <=> 3);
+}
+",
+        11,
+        "syntax error at main.toy:6:20: chained non-associative operator\n",
+    );
+}
+
+#[test]
+fn adjacent_symbols() {
+    // `=-`, `<-` and `*-` are not operators, so they are read as two.
+    assert_output(
+        "main(): int {
+    x := 0;
+    x=-2;
+    if (x<-1) {
+        println(x*-1);
+    }
+    return(x*-3);
+}
+",
+        "2\n",
+        6,
+    );
+}
@@ -0,0 +1,63 @@
+use crate::{assert_error, assert_output, run_files};
+
+#[test]
+fn scoped_overflow_modes() {
+    assert_output(
+        "main(): int {
+    big := 9223372036854775807;
+    small := -big - 1;
+    println(wrapping(big + 1) == small);
+    println(saturating(big + 1) == big);
+    println(saturating(small - 1) == small);
+    println(saturating(big * (-2)) == small);
+    println(saturating(-small) == big);
+    println(wrapping(-small) == small);
+    println(checked(3 * 4 + 1 - 6));
+    m := small;
+    println(wrapping(m / (-1)) == m);
+    println(saturating(m / (-1)) == big);
+    println(m % (-1));
+    return(0);
+}
+",
+        "true\ntrue\ntrue\ntrue\ntrue\ntrue\n7\ntrue\ntrue\n0\n",
+        0,
+    );
+}
+
+#[test]
+fn checked_by_default() {
+    assert_error(
+        "main(): int {
+    x := 9223372036854775807;
+    return(x + 1);
+}
+",
+        13,
+        "runtime error at main.toy:3:12: integer overflow\n    in main\n",
+    );
+    assert_error(
+        "main(): int { z := 0; return(5 % z); }
+",
+        13,
+        "runtime error at main.toy:1:30: division by zero\n",
+    );
+}
+
+#[test]
+fn mode_option() {
+    let source = "main(): int {
+    x := 9223372036854775807;
+    println(x + 1);
+    println(checked(1 + 1));
+    return(0);
+}
+";
+    let output = run_files(&["--overflow=wrapping"], &[("main.toy", source)]);
+    assert_eq!(output.stdout, "-9223372036854775808\n2\n");
+    let output = run_files(&["--overflow=saturating"], &[("main.toy", source)]);
+    assert_eq!(output.stdout, "9223372036854775807\n2\n");
+    let output = run_files(&["--overflow=sometimes"], &[("main.toy", source)]);
+    assert!(output.stderr.contains("unknown overflow mode `sometimes`"));
+    assert_eq!(output.code, 2);
+}
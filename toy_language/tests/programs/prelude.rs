@@ -0,0 +1,86 @@
+use crate::{assert_error, assert_output, run_with_input};
+
+#[test]
+fn input_and_math() {
+    let output = run_with_input(
+        &[],
+        &[(
+            "main.toy",
+            "main(): int {
+    print(\"name? \");
+    name := read_line();
+    print(\"hello, \");
+    println(name);
+    n := parse_int(read_line());
+    println(n * 2);
+    println(abs(-3) + min(4, 9) + max(1, 2));
+    println(abs(-1.5));
+    println(sqrt(16.0) + pow(2.0, 10.0));
+    println(sin(0.0) == 0.0);
+    println(min(2.5, 1.5));
+    println();
+    if (n > 10) {
+        exit(7);
+    }
+    return(3);
+}
+",
+        )],
+        "toy\n21\n",
+    );
+    assert_eq!(
+        output.stdout,
+        "name? hello, toy\n42\n9\n1.5\n1028\ntrue\n1.5\n\n"
+    );
+    assert_eq!(output.code, 7);
+}
+
+#[test]
+fn program_arguments() {
+    let output = run_with_input(
+        &["--", "a", "bc"],
+        &[(
+            "main.toy",
+            "main(): int {
+    a := args();
+    println(a[1]);
+    return(len(a));
+}
+",
+        )],
+        "",
+    );
+    assert_eq!(output.stdout, "bc\n");
+    assert_eq!(output.code, 2);
+}
+
+#[test]
+fn invalid_integer() {
+    assert_error(
+        "main(): int { return(parse_int(\"abc\")); }
+",
+        13,
+        "runtime error at main.toy:1:22: invalid integer \"abc\"\n",
+    );
+}
+
+#[test]
+fn mismatched_arguments() {
+    assert_error(
+        "main(): int { return(sqrt(4)); }
+",
+        12,
+        "type error at main.toy:1:22: mismatched arguments to `sqrt`\n",
+    );
+    assert_output(
+        "main(): int {
+    print(1);
+    print(true);
+    println(\"!\");
+    return(0);
+}
+",
+        "1true!\n",
+        0,
+    );
+}
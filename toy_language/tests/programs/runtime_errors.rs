@@ -0,0 +1,111 @@
+use crate::{assert_error, assert_output, run_files};
+
+#[test]
+fn backtrace_through_lambdas() {
+    assert_error(
+        "divide(a: int, b: int): int {
+    return(a / b);
+}
+helper(x: int): int {
+    f := (y: int): int { return(divide(10, y)); };
+    return(f(x));
+}
+main(): int {
+    println(divide(7, 2));
+    return(helper(0));
+}
+",
+        13,
+        "runtime error at main.toy:2:12: division by zero\n    in divide\n    in lambda0\n    in helper\n    in main\n",
+    );
+}
+
+#[test]
+fn null_dereference_during_initialization() {
+    assert_error(
+        "struct Point { x: int; y: int; }
+first := read();
+origin := Point(1, 2);
+read(): int { return(origin.x); }
+main(): int { return(first); }
+",
+        13,
+        "runtime error at main.toy:4:22: null dereference\n    in read\n    in <init>\n",
+    );
+}
+
+#[test]
+fn missing_return() {
+    assert_error(
+        "f(x: int): int {
+    if (x < 2) {
+        return(1);
+    }
+}
+main(): int { return(f(1)); }
+",
+        12,
+        "type error at main.toy:1:1: `f` can reach its end without returning a value\n",
+    );
+    assert_error(
+        "main(): int { }
+",
+        12,
+        "type error at main.toy:1:1: `main` can reach its end without returning a value\n",
+    );
+    assert_error(
+        "main(): int {
+    f := (): int { };
+    return(f());
+}
+",
+        12,
+        "type error at main.toy:2:10: lambda can reach its end without returning a value\n",
+    );
+    assert_output(
+        "sign(x: int): int {
+    if (x < 0) {
+        return(-1);
+    } else {
+        return(1);
+    }
+}
+main(): int {
+    while (true) {
+        return(sign(-5) + 2);
+    }
+}
+",
+        "",
+        1,
+    );
+}
+
+#[test]
+fn exit_code_of_main() {
+    assert_output(
+        "main(): int {
+    println(\"done\");
+    exit(5);
+}
+",
+        "done\n",
+        5,
+    );
+}
+
+#[test]
+fn location_in_a_later_file() {
+    let output = run_files(
+        &["lib.toy"],
+        &[
+            ("main.toy", "main(): int {\n    return(half(1));\n}\n"),
+            ("lib.toy", "half(x: int): int {\n    return(x / 0);\n}\n"),
+        ],
+    );
+    assert_eq!(
+        output.stderr,
+        "runtime error at lib.toy:2:12: division by zero\n    in half\n    in main\n"
+    );
+    assert_eq!(output.code, 13);
+}
@@ -0,0 +1,77 @@
+use crate::{assert_error, run, run_files};
+
+// The typed AST without whitespace, to look for folded constants in.
+fn typed_ast(source: &str) -> String {
+    let output = run_files(&["--emit=typed-ast"], &[("main.toy", source)]);
+    assert_eq!(output.code, 0, "{}", output.stderr);
+    output.stdout.split_whitespace().collect()
+}
+
+#[test]
+fn folds_constants() {
+    let ast = typed_ast(
+        "main(): int {
+    x := 6 * 7 + 0;
+    println(1.5 * 2.0);
+    if (true) {
+        println(x);
+    } else {
+        println(0);
+    }
+    while (false) {
+        println(1);
+    }
+    return(x);
+}
+",
+    );
+    assert!(ast.contains("Assign(Variable(0,),Integer(42,),)"), "{ast}");
+    assert!(ast.contains("Float(3.0,)"), "{ast}");
+    assert!(!ast.contains("If(") && !ast.contains("While("), "{ast}");
+}
+
+#[test]
+fn warns_about_unreachable_code() {
+    let output = run("main(): int {
+    return(g(1) + f());
+    println(2);
+}
+f(): int {
+    while (true) {
+        return(1);
+    }
+    return(2);
+}
+g(x: int): int {
+    if (x > 0) {
+        return(1);
+    } else {
+        return(2);
+    }
+    return(3);
+}
+");
+    assert_eq!(
+        output.stderr,
+        "warning at main.toy:2:5: unreachable code after `return`
+warning at main.toy:6:5: unreachable code after infinite loop
+warning at main.toy:12:5: unreachable code after `if` that always returns
+"
+    );
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.code, 2);
+}
+
+#[test]
+fn keeps_runtime_errors() {
+    assert_error(
+        "main(): int {\n    println(1);\n    return(10 / (2 - 2));\n}\n",
+        13,
+        "runtime error at main.toy:3:12: division by zero\n    in main\n",
+    );
+    assert_error(
+        "main(): int {\n    return(9223372036854775807 + 1);\n}\n",
+        13,
+        "runtime error at main.toy:2:12: integer overflow\n    in main\n",
+    );
+}
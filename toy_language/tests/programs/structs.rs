@@ -0,0 +1,42 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn nested_structs_and_field_assignment() {
+    assert_output(
+        "struct Point {
+    x: int;
+    flag: bool;
+    y: int;
+}
+struct Segment {
+    from: Point;
+    to: Point;
+}
+origin: Point = Point(0, false, 0);
+main(): int {
+    p := Point(x = 3, y = 4, flag = true);
+    s := Segment(origin, p);
+    s.to.y = 10;
+    ps := [Point(1, true, 2), p];
+    ps[0].x = 5;
+    if (p.flag) {
+        return((s.to.y - s.from.y) * 10 + ps[0].x);
+    }
+    return(0);
+}
+",
+        "",
+        105,
+    );
+}
+
+#[test]
+fn unknown_field() {
+    assert_error(
+        "struct Point { x: int; }
+main(): int { p := Point(1); return(p.z); }
+",
+        12,
+        "type error at main.toy:2:37: no field `z` in `Point`\n",
+    );
+}
@@ -0,0 +1,157 @@
+use crate::{assert_error, run_files};
+
+#[test]
+fn missing_semicolon() {
+    assert_error(
+        "main(): int {\n    x := 1 y;\n    return(x);\n}\n",
+        11,
+        "syntax error at main.toy:2:12: expected `;` or `{`\n",
+    );
+}
+
+#[test]
+fn unclosed_block() {
+    assert_error(
+        "main(): int {\n    return(0);\n",
+        11,
+        "syntax error at main.toy:3:1: expected `}`\n",
+    );
+}
+
+#[test]
+fn mismatched_brackets() {
+    assert_error(
+        "main(): int {\n    return(f(1]);\n}\n",
+        11,
+        "syntax error at main.toy:2:15: expected `,` or `)`\n",
+    );
+    assert_error(
+        "main(): int {\n    a := [1, 2);\n    return(0);\n}\n",
+        11,
+        "syntax error at main.toy:2:15: expected `,` or `]`\n",
+    );
+}
+
+#[test]
+fn missing_member_name() {
+    assert_error(
+        "main(): int {\n    return(x.);\n}\n",
+        11,
+        "syntax error at main.toy:2:14: expected a name after `.`\n",
+    );
+}
+
+#[test]
+fn unknown_fixity() {
+    assert_error(
+        "operator infixx 5 <+> add;\n",
+        11,
+        "syntax error at main.toy:1:10: unknown fixity `infixx`\n",
+    );
+}
+
+#[test]
+fn unterminated_literals() {
+    assert_error(
+        "s := \"abc;\n",
+        10,
+        "lexical error at main.toy:1:6: unterminated string literal\n",
+    );
+    assert_error(
+        "x := 1;\n/* a /* b */\n",
+        10,
+        "lexical error at main.toy:2:1: unterminated comment\n",
+    );
+}
+
+#[test]
+fn bad_interpolation() {
+    assert_error(
+        "s := \"a{}b\";\n",
+        10,
+        "lexical error at main.toy:1:9: expected an expression to interpolate\n",
+    );
+    assert_error(
+        "s := \"a{1 2}b\";\n",
+        10,
+        "lexical error at main.toy:1:11: expected `}` after an interpolated expression\n",
+    );
+}
+
+#[test]
+fn integer_literal_too_large() {
+    assert_error(
+        "x := 99999999999999999999;\n",
+        10,
+        "lexical error at main.toy:1:6: integer literal `99999999999999999999` is too large\n",
+    );
+}
+
+#[test]
+fn float_literal_too_large() {
+    let literal = format!("1{}.0", "0".repeat(400));
+    assert_error(
+        &format!("x := {literal};\n"),
+        10,
+        &format!("lexical error at main.toy:1:6: float literal `{literal}` is too large\n"),
+    );
+}
+
+#[test]
+fn unexpected_character() {
+    assert_error(
+        "x := 1 $ 2;\n",
+        10,
+        "lexical error at main.toy:1:8: unexpected character '$'\n",
+    );
+}
+
+#[test]
+fn duplicate_operator() {
+    assert_error(
+        "operator infixl 30 + add;\n",
+        11,
+        "syntax error at main.toy:1:25: duplicate operator `+`\n",
+    );
+    let output = run_files(
+        &["--emit=tokens"],
+        &[("main.toy", "operator infixl 30 + add;\n")],
+    );
+    assert_eq!(output.code, 11);
+}
+
+#[test]
+fn tokens_of_declared_operators() {
+    let output = run_files(
+        &["--emit=tokens"],
+        &[(
+            "main.toy",
+            "operator infixl 30 <+> add;\nx := 1 <+> \"{2}\";\n",
+        )],
+    );
+    assert_eq!(
+        output.stdout,
+        "main.toy:1:1 Identifier(\"operator\")
+main.toy:1:10 Identifier(\"infixl\")
+main.toy:1:17 Integer(30)
+main.toy:1:20 Symbol(\"<+>\")
+main.toy:1:24 Identifier(\"add\")
+main.toy:1:27 Semicolon
+main.toy:2:1 Identifier(\"x\")
+main.toy:2:3 Colon
+main.toy:2:4 Symbol(\"=\")
+main.toy:2:6 Integer(1)
+main.toy:2:8 Symbol(\"<+>\")
+main.toy:2:12 StringLiteral(\"{2}\")
+main.toy:2:17 Semicolon
+"
+    );
+    assert_eq!(output.code, 0);
+}
+
+#[test]
+fn unknown_stage() {
+    let output = run_files(&["--emit=bytecode"], &[("main.toy", "x := 1;\n")]);
+    assert!(output.stderr.ends_with("error: unknown stage `bytecode`\n"));
+    assert_eq!(output.code, 2);
+}
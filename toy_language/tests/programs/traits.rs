@@ -0,0 +1,74 @@
+use crate::assert_output;
+
+#[test]
+fn static_and_dynamic_dispatch() {
+    assert_output(
+        "trait Shape {
+    area(self): float;
+    sides(self): int;
+}
+struct Circle { r: float; }
+struct Rect { w: float; h: float; }
+impl Shape for Circle {
+    area(self): float { return(self.r * self.r * 3.0); }
+    sides(self): int { return(0); }
+}
+impl Shape for Rect {
+    area(self): float { return(self.w * self.h); }
+    sides(self): int { return(4); }
+}
+impl Shape for int {
+    area(self): float { return(1.0); }
+    sides(self): int { return(self); }
+}
+describe[T](x: T): int { return(x.sides()); }
+main(): int {
+    s: Shape = Rect(2.0, 3.0);
+    all := [s, s, s];
+    all[1] = Circle(1.0);
+    all[2] = 7;
+    total := 0.0;
+    i := 0;
+    while (i < len(all)) {
+        total = total + all[i].area();
+        i = i + 1;
+    }
+    if (total != 10.0) {
+        return(0);
+    }
+    return(describe(Rect(1.0, 1.0)) + describe(5));
+}
+",
+        "",
+        9,
+    );
+}
+
+#[test]
+fn operator_overloading() {
+    assert_output(
+        "trait Add {
+    add(self, other: Self): Self;
+}
+trait Eq {
+    eq(self, other: Self): bool;
+}
+struct Vec2 { x: int; y: int; }
+impl Add for Vec2 {
+    add(self, other: Vec2): Vec2 { return(Vec2(self.x + other.x, self.y + other.y)); }
+}
+impl Eq for Vec2 {
+    eq(self, other: Vec2): bool { return(self.x == other.x); }
+}
+main(): int {
+    v := Vec2(1, 2) + Vec2(10, 20);
+    if (v == Vec2(11, 0)) {
+        return(v.x + v.y);
+    }
+    return(0);
+}
+",
+        "",
+        33,
+    );
+}
@@ -0,0 +1,83 @@
+use crate::{assert_error, assert_output};
+
+#[test]
+fn multiple_return_values() {
+    assert_output(
+        "divmod(a: int, b: int): (int, int) {
+    return(a / b, a % b);
+}
+main(): int {
+    (q:, r:) = divmod(47, 10);
+    return(q * 10 + r);
+}
+",
+        "",
+        47,
+    );
+}
+
+#[test]
+fn destructuring_and_swapping() {
+    assert_output(
+        "fib(n: int): int {
+    (a: int, b:) = (0, 1);
+    i := 0;
+    while (i < n) {
+        (a, b) = (b, a + b);
+        i = i + 1;
+    }
+    return(a);
+}
+main(): int {
+    (f: float, _) = (2.5, true);
+    one: (int,) = (7,);
+    (seven:,) = one;
+    if (f != 2.5) {
+        return(1);
+    }
+    return(fib(10) + seven);
+}
+",
+        "",
+        62,
+    );
+}
+
+#[test]
+fn mismatched_tuple_types() {
+    assert_error(
+        "main(): int {
+    p: (int, int) = (1, 2.5);
+    return(0);
+}
+",
+        12,
+        "type error at main.toy:2:",
+    );
+}
+
+#[test]
+fn string_interpolation() {
+    assert_output(
+        "label(x: int): (string, int) {
+    return(\"a{x + 1}b {\"world\"} {1.5} {x > 3}\", x);
+}
+main(): int {
+    (s:, x:) = label(41);
+    t := \"{s}{x}\";
+    return(x);
+}
+",
+        "",
+        41,
+    );
+    assert_error(
+        "main(): int {
+    s := \"{(1, 2)}\";
+    return(0);
+}
+",
+        12,
+        "type error at main.toy:2:12: cannot interpolate a value of type `(int, int)`",
+    );
+}
@@ -0,0 +1,73 @@
+use crate::assert_error;
+
+#[test]
+fn mismatched_operands() {
+    assert_error(
+        "main(): int {\n    x := 1 + \"a\";\n    return(0);\n}\n",
+        12,
+        "type error at main.toy:2:10: mismatched operand types `int` and `string` of `+`\n",
+    );
+}
+
+#[test]
+fn mismatched_condition() {
+    assert_error(
+        "main(): int {\n    if (1) {\n    }\n    return(0);\n}\n",
+        12,
+        "type error at main.toy:2:5: mismatched type of condition: expected `bool`, found `int`\n",
+    );
+}
+
+#[test]
+fn mismatched_return_type() {
+    assert_error(
+        "main(): int {\n    return(\"a\");\n}\n",
+        12,
+        "type error at main.toy:2:5: mismatched return type: expected `int`, found `string`\n",
+    );
+}
+
+#[test]
+fn mismatched_arguments() {
+    let source = "f(x: [int]): int {\n    return(0);\n}\n";
+    assert_error(
+        &format!("{source}main(): int {{\n    return(f((1, 2.0)));\n}}\n"),
+        12,
+        "type error at main.toy:5:12: mismatched argument type to `f`: \
+         expected `[int]`, found `(int, float)`\n",
+    );
+    assert_error(
+        &format!("{source}main(): int {{\n    return(f([1], [2]));\n}}\n"),
+        12,
+        "type error at main.toy:5:12: wrong number of arguments to `f`: expected 1, found 2\n",
+    );
+}
+
+#[test]
+fn mismatched_declaration() {
+    assert_error(
+        "main(): int {\n    x: int = \"s\";\n    return(0);\n}\n",
+        12,
+        "type error at main.toy:2:5: mismatched types in declaration of `x`: \
+         expected `int`, found `string`\n",
+    );
+}
+
+#[test]
+fn values_of_the_wrong_type() {
+    assert_error(
+        "main(): int {\n    x := -\"a\";\n    return(0);\n}\n",
+        12,
+        "type error at main.toy:2:10: cannot negate a value of type `string`\n",
+    );
+    assert_error(
+        "main(): int {\n    x := 1;\n    return(x(2));\n}\n",
+        12,
+        "type error at main.toy:3:12: cannot call a value of type `int`\n",
+    );
+    assert_error(
+        "main(): int {\n    x := (1, true);\n    return(x[0]);\n}\n",
+        12,
+        "type error at main.toy:3:12: cannot index a value of type `(int, bool)`\n",
+    );
+}
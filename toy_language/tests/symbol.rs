@@ -0,0 +1,35 @@
+// Checks that interning gives equal names the same symbol.
+use std::collections::HashSet;
+
+use toy_language::symbol::Symbol;
+
+#[test]
+fn equal_names_intern_to_the_same_symbol() {
+    let name = String::from("interned_name");
+    assert_eq!(Symbol::intern(&name), Symbol::intern("interned_name"));
+    assert_ne!(
+        Symbol::intern("interned_name"),
+        Symbol::intern("other_name")
+    );
+    let symbols: HashSet<_> = ["a", "b", "a", "c", "b"].map(Symbol::intern).into();
+    assert_eq!(symbols.len(), 3);
+}
+
+#[test]
+fn symbols_keep_their_names() {
+    let symbol = Symbol::intern("kept_name");
+    assert_eq!(symbol.as_str(), "kept_name");
+    assert_eq!(symbol, "kept_name");
+    assert!(symbol != *"other_name");
+    assert_eq!(symbol.to_string(), "kept_name");
+    assert_eq!(format!("{symbol:?}"), "\"kept_name\"");
+    assert_eq!(Symbol::intern(""), "");
+}
+
+#[test]
+fn symbols_are_shared_between_threads() {
+    let symbol = std::thread::spawn(|| Symbol::intern("threaded_name"))
+        .join()
+        .unwrap();
+    assert_eq!(symbol, Symbol::intern("threaded_name"));
+}
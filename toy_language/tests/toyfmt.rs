@@ -0,0 +1,104 @@
+// Runs the toyfmt binary on files and stdin.
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn toyfmt(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_toyfmt"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn file(name: &str, source: &str) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("toyfmt");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, source).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn formats_stdin() {
+    let output = toyfmt(&[], "x:=1;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "x := 1;\n");
+}
+
+#[test]
+fn keeps_only_meaningful_trailing_commas() {
+    let output = toyfmt(&[], "t:=(1,2,);u:=(4,);a:=[1,2,];");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "t := (1, 2);\nu := (4,);\na := [1, 2];\n"
+    );
+}
+
+#[test]
+fn formats_files_in_place() {
+    let path = file("in_place.toy", "x:=1;");
+    let output = toyfmt(&[&path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "x := 1;\n");
+}
+
+#[test]
+fn check_lists_unformatted_files() {
+    let formatted = file("formatted.toy", "x := 1;\n");
+    let unformatted = file("unformatted.toy", "x:=1;");
+    let output = toyfmt(&["--check", &formatted, &unformatted], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{unformatted}\n")
+    );
+    assert_eq!(std::fs::read_to_string(&unformatted).unwrap(), "x:=1;");
+}
+
+#[test]
+fn rejects_unknown_options() {
+    let output = toyfmt(&["--chek"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).ends_with("toyfmt: unknown option `--chek`\n"));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn reports_unreadable_files() {
+    let path = file("missing.toy", "");
+    std::fs::remove_file(&path).unwrap();
+    let output = toyfmt(&[&path], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with(&format!("toyfmt: {path}: ")));
+}
+
+#[test]
+fn reports_syntax_errors() {
+    let bad = file("bad.toy", "x := 1;\ny := (1 + ;\n");
+    let good = file("good.toy", "x:=1;");
+    let output = toyfmt(&[&bad, &good], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with(&format!("toyfmt: {bad}: 2:")));
+    // The other files are still formatted.
+    assert_eq!(std::fs::read_to_string(&good).unwrap(), "x := 1;\n");
+
+    let output = toyfmt(&[], "f(];");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("toyfmt: <stdin>: 1:"));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,164 @@
+// Drives the language server over stdio.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+fn message(body: Value) -> String {
+    let body = body.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+// Sends the notifications and requests, then shuts the server down and
+// returns the results of the requests, or their errors, in order.
+fn session(messages: &[Value]) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_toylsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut input = message(json!({
+        "jsonrpc": "2.0", "id": 0, "method": "initialize",
+        "params": {"capabilities": {}},
+    }));
+    input += &message(json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}));
+    let mut num_requests = 0;
+    for body in messages {
+        let mut body = body.clone();
+        body["jsonrpc"] = json!("2.0");
+        if body.get("id").is_none()
+            && !body["method"]
+                .as_str()
+                .unwrap()
+                .starts_with("textDocument/did")
+        {
+            num_requests += 1;
+            body["id"] = json!(num_requests);
+        }
+        input += &message(body);
+    }
+    input += &message(json!({"jsonrpc": "2.0", "id": 1000, "method": "shutdown"}));
+    input += &message(json!({"jsonrpc": "2.0", "method": "exit"}));
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut responses = vec![Value::Null; num_requests];
+    loop {
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            stdout.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                len = value.trim().parse().unwrap();
+            } else if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; len];
+        stdout.read_exact(&mut body).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        match body["id"].as_u64() {
+            Some(1000) => break,
+            Some(id @ 1..) => {
+                let response = body.get("error").unwrap_or(&body["result"]);
+                responses[id as usize - 1] = response.clone();
+            }
+            _ => {}
+        }
+    }
+    child.wait().unwrap();
+    responses
+}
+
+fn position(line: u32, character: u32) -> Value {
+    json!({"line": line, "character": character})
+}
+
+fn request(method: &str, uri: &str, line: u32, character: u32) -> Value {
+    json!({
+        "method": method,
+        "params": {"textDocument": {"uri": uri}, "position": position(line, character)},
+    })
+}
+
+fn edit(uri: &str, start: (u32, u32), end: (u32, u32), text: &str) -> Value {
+    json!({
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": {"uri": uri, "version": 1},
+            "contentChanges": [{
+                "range": {"start": position(start.0, start.1), "end": position(end.0, end.1)},
+                "text": text,
+            }],
+        },
+    })
+}
+
+#[test]
+fn keeps_references_while_the_document_has_errors() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("toylsp");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("lib.toy"),
+        "pub twice(x: int): int {\n    return(x * 2);\n}\n",
+    )
+    .unwrap();
+    let path = dir.join("main.toy");
+    let uri = format!("file://{}", path.display());
+    let lib_uri = format!("file://{}", dir.join("lib.toy").display());
+    let text = "import \"lib.toy\";
+helper(x: int): int {
+    return(twice(x));
+}
+main(): int {
+    return(helper(1));
+}
+";
+    let open = json!({
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "languageId": "toy", "version": 0, "text": text}},
+    });
+    let responses = session(&[
+        open,
+        // A type error.
+        edit(&uri, (5, 18), (5, 19), "\"one\""),
+        request("textDocument/hover", &uri, 5, 12),
+        request("textDocument/definition", &uri, 2, 12),
+        // A syntax error before the references moves them down a line.
+        edit(&uri, (0, 17), (0, 17), "\nx := (;"),
+        request("textDocument/definition", &uri, 6, 12),
+        request("textDocument/definition", &uri, 3, 12),
+        // The edited call is forgotten.
+        request("textDocument/hover", &uri, 6, 19),
+    ]);
+    assert_eq!(
+        responses[0]["contents"]["value"],
+        "```\nhelper: fn(int): int\n```"
+    );
+    assert_eq!(responses[1]["uri"], lib_uri);
+    assert_eq!(responses[1]["range"]["start"], position(0, 4));
+    assert_eq!(responses[2]["uri"], uri);
+    assert_eq!(responses[2]["range"]["start"], position(2, 0));
+    assert_eq!(responses[3]["uri"], lib_uri);
+    assert_eq!(responses[3]["range"]["start"], position(0, 4));
+    assert_eq!(responses[4], Value::Null);
+}
+
+#[test]
+fn rejects_invalid_parameters() {
+    let uri = "file:///invalid.toy";
+    let responses = session(&[
+        json!({"method": "textDocument/didOpen", "params": {"textDocument": {"uri": uri}}}),
+        json!({"method": "textDocument/hover", "params": {"position": position(0, 0)}}),
+        json!({"method": "textDocument/documentSymbol", "params": {"textDocument": {"uri": uri}}}),
+    ]);
+    assert_eq!(responses[0]["code"], -32602);
+    assert_eq!(responses[1], Value::Null);
+}